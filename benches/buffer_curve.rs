@@ -0,0 +1,84 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! Sampling buffer curves at the temperatures of a log, rebuilding the curve for each temperature against building it
+//! once.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use libcaliph::buffers::{BufferCurve, BufferSet, BufferTable, InterpolationMethod};
+use libcaliph::routines::{interp_ph4, ph4_curve};
+
+/// Logged temperatures drifting over the range of the tables, in ˚C
+fn logged_temperatures(count: usize) -> Vec<f64> {
+    (0..count)
+        .map(|index| 5.0 + 85.0 * (index as f64 * 0.618_034).fract())
+        .collect()
+}
+
+fn sample_log(c: &mut Criterion) {
+    let technical = BufferSet::technical();
+    let table = match &technical.find(&4.01).unwrap().curve {
+        BufferCurve::Table(table) => table.clone(),
+        BufferCurve::Equation(_) => unreachable!(),
+    };
+
+    for interpolation in [InterpolationMethod::Linear, InterpolationMethod::Akima] {
+        let mut group = c.benchmark_group(format!("buffer_curve/{}", interpolation));
+        for count in [100, 10_000] {
+            let temperatures = logged_temperatures(count);
+            group.throughput(Throughput::Elements(count as u64));
+
+            group.bench_with_input(
+                BenchmarkId::new("rebuilt", count),
+                &temperatures,
+                |b, temperatures| {
+                    b.iter(|| {
+                        temperatures
+                            .iter()
+                            .map(|t| {
+                                BufferTable::new(
+                                    table.temperature().to_vec(),
+                                    table.ph().to_vec(),
+                                    interpolation,
+                                )
+                                .sample(t)
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                },
+            );
+
+            let curve = BufferCurve::Table(BufferTable::new(
+                table.temperature().to_vec(),
+                table.ph().to_vec(),
+                interpolation,
+            ));
+            group.bench_with_input(
+                BenchmarkId::new("precomputed", count),
+                &temperatures,
+                |b, temperatures| b.iter(|| curve.ph_all(black_box(temperatures))),
+            );
+        }
+        group.finish();
+    }
+
+    let temperatures = logged_temperatures(10_000);
+    let mut group = c.benchmark_group("interp_ph4");
+    group.throughput(Throughput::Elements(temperatures.len() as u64));
+    group.bench_function("each", |b| {
+        b.iter(|| {
+            black_box(&temperatures)
+                .iter()
+                .map(interp_ph4)
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("ph_all", |b| {
+        b.iter(|| ph4_curve().ph_all(black_box(&temperatures)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, sample_log);
+criterion_main!(benches);
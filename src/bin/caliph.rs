@@ -14,8 +14,11 @@ Copyright 2021 Peter Dunne */
 //!-----------------
 //!Slope   0.96308
 //!Offset  0.18657
+//!Exact fit, no residual information
+//!-----------------
+//!Slope   61.43 mV/pH     103.8 % pass
+//!Zero    -4.4 mV         pass
 //!-----------------
-//!
 //!```
 //!
 //!Optional temperature argument:
@@ -28,6 +31,10 @@ Copyright 2021 Peter Dunne */
 //!-----------------
 //!Slope   0.96828
 //!Offset  0.16052
+//!Exact fit, no residual information
+//!-----------------
+//!Slope   60.54 mV/pH     103.3 % pass
+//!Zero    -3.7 mV         pass
 //!-----------------
 //!```
 //!
@@ -41,42 +48,819 @@ Copyright 2021 Peter Dunne */
 //!-----------------
 //!Slope   0.96828
 //!Offset  0.16052
+//!Exact fit, no residual information
+//!-----------------
+//!Slope   60.54 mV/pH     103.3 % pass
+//!Zero    -3.7 mV         pass
 //!-----------------
 //!
 //!Saved to calibration.ph
 //!```
+//!
+//!Any number of buffer solutions can be used, by giving their nominal pH values with `-b`.
+//!The temperature dependence of each buffer is taken from the buffer set chosen with `--buffer-set`,
+//!either `technical` (4.01, 7.00, 10.01, the default) or `nist`/`din19266` (1.68, 4.01, 6.86, 9.18, 10.01, 12.45).
+//!A buffer not in the set is an error, exit code 8, rather than being used uncorrected:
+//!
+//!```console
+//!$ caliph 1.5 3.8 6.6 8.9 9.8 -b 1.68,4.01,6.86,9.18,10.01 --buffer-set nist
+//!
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!Slope   1.00795 ± 0.00540
+//!Offset  0.17958 ± 0.03704
+//!RMSE    0.02903
+//!R²      0.99991
+//!Adj. R² 0.99989
+//!DoF     3
+//!-----------------
+//!Buffer  Reading Residual
+//!1.6790  1.5     -0.0125
+//!4.0050  3.8     -0.0048
+//!6.8650  6.6     +0.0330
+//!9.1800  8.9     +0.0297
+//!10.0120 9.8     -0.0454
+//!-----------------
+//!Slope   58.69 mV/pH     99.2 %  pass
+//!Zero    13.8 mV         pass
+//!-----------------
+//!```
+//!
+//!Replicate readings, robust, weighted, segmented and polynomial fits, one-point adjustments, temperature units and
+//!policies, acceptance limits, and the uncertainty budget of `caliph report` are described in the README, together
+//!with all the options and exit codes.
 
-use anyhow::Result;
-use libcaliph::args::CalibArgs;
-use libcaliph::routines::ph_calibration;
-use std::fs::File;
-use std::io::Write;
-use termcolor::{ColorChoice, ColorSpec, StandardStream, WriteColor};
+use libcaliph::acceptance::{Acceptance, Verdict};
+use libcaliph::args::{CalibArgs, ReportArgs};
+use libcaliph::budget::{Budget, BudgetModel, Output, UncertaintySources};
+use libcaliph::buffers::BufferSet;
+use libcaliph::electrode::Electrode;
+use libcaliph::error::Error;
+use libcaliph::fit::{FitReport, OUTLIER_WEIGHT, SIGNIFICANCE};
+use libcaliph::model::{ModelRegistry, PhModel};
+use libcaliph::output::{color_choice, write_calibration, Format};
+use libcaliph::record::{CalibrationRecord, RECORD_FILE};
+use libcaliph::replicate::{replicate_weights, Outlier, OutlierTest, Replicates};
+use libcaliph::routines::{
+    buffer_values_with_policy, ph_calibration_one_point_at, ph_fit_report, ph_fit_report_weighted,
+    ph_fit_robust, Calibration, RangeWarning,
+};
+use libcaliph::temperature::{Temperature, TemperatureUnit};
+use libcaliph::Result;
+use std::io::{self, Write};
+use std::process;
+use termcolor::{Color, ColorSpec, StandardStream, WriteColor};
 
-fn main() -> Result<()> {
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        process::exit(err.exit_code());
+    }
+}
+
+fn run() -> Result<()> {
     let args = CalibArgs::parse();
 
-    let temperature = args.temperature;
-    let ph_measured = [args.ph4, args.ph10];
-    let calibration = ph_calibration(&ph_measured, &temperature);
+    let mut buffer_set = args.buffer_set.clone();
+    if let Some(path) = &args.buffers_file {
+        buffer_set.merge(BufferSet::from_file(path)?);
+    }
+
+    if let Some(interpolation) = args.interpolation {
+        buffer_set = buffer_set.with_interpolation(interpolation);
+    }
+
+    if let Some(report_args) = &args.report {
+        return report(report_args, buffer_set);
+    }
+    if args.one_point {
+        return one_point(&args, buffer_set);
+    }
+
+    let temperature = args.temperature.celsius();
+    let (ph_buffer, range_warnings) = buffer_values_with_policy(
+        &args.buffers,
+        &temperature,
+        &buffer_set,
+        args.temperature_policy,
+    )
+    .map_err(|err| err.in_unit(args.temperature_unit))?;
+    // The readings of each buffer are checked for an outlier, which is left out of the mean if rejected
+    let (outlier_test, reject_outliers) = (args.outlier_test, args.reject_outliers);
+    let outliers: Vec<Option<Outlier>> = args
+        .replicates
+        .iter()
+        .map(|replicates| replicates.outlier(outlier_test))
+        .collect();
+    let replicates: Vec<Replicates> = args
+        .replicates
+        .iter()
+        .zip(outliers.iter())
+        .map(|(replicates, outlier)| match outlier {
+            Some(outlier) if reject_outliers => replicates.without(outlier.index),
+            _ => replicates.clone(),
+        })
+        .collect();
+    let ph_measured: Vec<f64> = replicates.iter().map(Replicates::mean).collect();
+    let robust = match args.robust {
+        Some(method) => Some(ph_fit_robust(&ph_measured, &ph_buffer, method)?),
+        None => None,
+    };
+    // Buffers read in replicate are weighted by the scatter of their readings, unless fitted robustly
+    let weights = match robust {
+        Some(_) => None,
+        None => replicate_weights(&replicates).map(|weights| {
+            let mean = weights.iter().sum::<f64>() / weights.len() as f64;
+            weights
+                .iter()
+                .map(|weight| weight / mean)
+                .collect::<Vec<_>>()
+        }),
+    };
+    let report = match (&robust, &weights) {
+        (Some(robust), _) => FitReport::new(&ph_measured, &ph_buffer, &robust.model)?,
+        (None, Some(weights)) => ph_fit_report_weighted(&ph_measured, &ph_buffer, weights)?,
+        (None, None) => ph_fit_report(&ph_measured, &ph_buffer)?,
+    };
+    let calibration = Calibration::from(&report);
+    let electrode = Electrode::from_calibration(&calibration, args.reading, &temperature);
+    let acceptance = Acceptance::evaluate(&electrode, &args.limits);
+    // The linear calibration is always fitted, as the electrode is described by its slope and offset
+    let model: Option<Box<dyn PhModel>> = if args.model == calibration.name() {
+        None
+    } else {
+        Some(ModelRegistry::default().fit_with(
+            &args.model,
+            &ph_measured,
+            &ph_buffer,
+            &args.fit_options,
+        )?)
+    };
+    // The uncertainty of the least squares line does not describe any other model, nor a robust line
+    let uncertainty = match (&model, &robust) {
+        (None, None) => report.uncertainty(),
+        _ => None,
+    };
+    let record = CalibrationRecord::new(&calibration, temperature, &ph_measured, &ph_buffer)
+        .with_electrode(args.electrode)
+        .with_buffer_set(&buffer_set.name, &args.buffers)
+        .with_interpolation(args.interpolation)
+        .with_replicates(&replicates)
+        .with_reading(args.reading)
+        .with_temperature_policy(args.temperature_policy, &range_warnings)
+        .with_weights(weights.clone().unwrap_or_default())
+        .with_robust(robust.as_ref())
+        .with_model(model.as_ref().map(|model| model.to_record()).transpose()?)
+        .with_uncertainty(uncertainty)
+        .with_acceptance(acceptance.clone());
+
+    if args.format == Format::Table {
+        let mut stdout = StandardStream::stdout(color_choice());
+        writeln!(&mut stdout, "\n-----------------")?;
+        stdout.set_color(ColorSpec::new().set_bold(true))?;
+        writeln!(&mut stdout, "  Calibrating")?;
+        stdout.reset()?;
+        writeln!(&mut stdout, "-----------------")?;
+        write_temperature(&mut stdout, &args.temperature, args.temperature_unit)?;
+        if !range_warnings.is_empty() {
+            write_range_warnings(&mut stdout, &range_warnings, args.temperature_unit)?;
+            writeln!(&mut stdout, "-----------------")?;
+        }
+        if replicates
+            .iter()
+            .any(|replicates| replicates.readings.len() > 1)
+        {
+            write_replicates(
+                &mut stdout,
+                &ph_buffer,
+                &args.replicates,
+                &outliers,
+                args.outlier_test,
+                args.reject_outliers,
+            )?;
+            writeln!(&mut stdout, "-----------------")?;
+        }
+        stdout.set_color(ColorSpec::new().set_bold(true))?;
+        match (report.slope_error, report.intercept_error) {
+            (Some(slope_error), Some(intercept_error)) if robust.is_none() => {
+                writeln!(
+                    &mut stdout,
+                    "Slope\t{:.5} ± {:.5}",
+                    report.slope, slope_error
+                )?;
+                writeln!(
+                    &mut stdout,
+                    "Offset\t{:.5} ± {:.5}",
+                    report.intercept, intercept_error
+                )?;
+            }
+            _ => {
+                writeln!(&mut stdout, "Slope\t{:.5}", report.slope)?;
+                writeln!(&mut stdout, "Offset\t{:.5}", report.intercept)?;
+            }
+        }
+        stdout.reset()?;
+        if report.is_exact() {
+            writeln!(&mut stdout, "Exact fit, no residual information")?;
+        } else {
+            writeln!(&mut stdout, "RMSE\t{:.5}", report.rmse.unwrap_or_default())?;
+            writeln!(&mut stdout, "R²\t{:.5}", report.rsq.unwrap_or_default())?;
+            writeln!(
+                &mut stdout,
+                "Adj. R²\t{:.5}",
+                report.adjusted_rsq.unwrap_or_default()
+            )?;
+            writeln!(&mut stdout, "DoF\t{}", report.dof)?;
+            if let Some(robust) = &robust {
+                writeln!(&mut stdout, "Robust\t{}", robust.method)?;
+            } else if weights.is_some() {
+                writeln!(&mut stdout, "Weights\t1/u² of the replicates")?;
+            }
+            writeln!(&mut stdout, "-----------------")?;
+            let shown = match &robust {
+                Some(robust) => Some(&robust.weights),
+                None => weights.as_ref(),
+            };
+            match shown {
+                Some(_) => writeln!(&mut stdout, "Buffer\tReading\tResidual\tWeight")?,
+                None => writeln!(&mut stdout, "Buffer\tReading\tResidual")?,
+            }
+            for (index, ((buffer, reading), residual)) in ph_buffer
+                .iter()
+                .zip(replicates.iter())
+                .zip(report.residuals.iter())
+                .enumerate()
+            {
+                write!(&mut stdout, "{:.4}\t{}\t", buffer, reading)?;
+                let residual = format!("{:+.4}", residual);
+                match shown {
+                    Some(shown) => {
+                        write!(&mut stdout, "{:<8}", residual)?;
+                        write_weight(&mut stdout, shown[index], robust.is_some())?;
+                    }
+                    None => writeln!(&mut stdout, "{}", residual)?,
+                }
+            }
+        }
+        if let Some(model) = &model {
+            writeln!(&mut stdout, "-----------------")?;
+            writeln!(&mut stdout, "Model\t{}", model.name())?;
+            for line in model.describe().lines() {
+                writeln!(&mut stdout, "{}", line)?;
+            }
+            write_term_tests(&mut stdout, model.as_ref(), &ph_measured, &ph_buffer)?;
+        }
+        writeln!(&mut stdout, "-----------------")?;
+        write!(
+            &mut stdout,
+            "Slope\t{:.2} mV/pH\t{:.1} %\t",
+            electrode.slope, acceptance.slope
+        )?;
+        write_verdict(&mut stdout, acceptance.slope_verdict)?;
+        write!(&mut stdout, "Zero\t{:.1} mV\t\t", acceptance.offset)?;
+        write_verdict(&mut stdout, acceptance.offset_verdict)?;
+        writeln!(&mut stdout, "-----------------")?;
+    } else {
+        for warning in &range_warnings {
+            eprintln!("warning: {}", warning.in_unit(args.temperature_unit));
+        }
+        for (buffer, outlier) in args.buffers.iter().zip(outliers.iter()) {
+            if let Some(outlier) = outlier {
+                eprintln!(
+                    "warning: the reading {} of the pH {} buffer is an outlier by the {} test",
+                    outlier.reading, buffer, args.outlier_test
+                );
+            }
+        }
+        write_calibration(io::stdout().lock(), &record, args.format)?;
+    }
+
+    if args.store {
+        record.write(RECORD_FILE)?;
+        write_saved(args.format);
+    }
+
+    acceptance.check()
+}
 
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+/// Adjusts the offset of the stored calibration to a single buffer, keeping its slope
+fn one_point(args: &CalibArgs, buffer_set: BufferSet) -> Result<()> {
+    let record = CalibrationRecord::read(RECORD_FILE)?;
+    if record.reading != args.reading {
+        return Err(Error::InvalidInput {
+            line: None,
+            message: format!(
+                "the stored calibration is of {} readings, not {}",
+                record.reading, args.reading
+            ),
+        });
+    }
+    if let (Some(stored), Some(electrode)) = (&record.electrode, &args.electrode) {
+        if stored != electrode {
+            return Err(Error::InvalidInput {
+                line: None,
+                message: format!(
+                    "the stored calibration is of electrode {}, not {}",
+                    stored, electrode
+                ),
+            });
+        }
+    }
+
+    // The buffer is interpolated as in the full calibration, unless asked otherwise
+    let buffer_set = match (args.interpolation, record.interpolation) {
+        (None, Some(interpolation)) => buffer_set.with_interpolation(interpolation),
+        _ => buffer_set,
+    };
+    let temperature = args.temperature.celsius();
+    let nominal = args.buffers[0];
+    let (ph_buffer, range_warnings) = buffer_values_with_policy(
+        &[nominal],
+        &temperature,
+        &buffer_set,
+        args.temperature_policy,
+    )
+    .map_err(|err| err.in_unit(args.temperature_unit))?;
+    let (ph_buffer, range_warning) = (ph_buffer[0], range_warnings.first());
+    let replicates = &args.replicates[0];
+    let outlier = replicates.outlier(args.outlier_test);
+    let replicates = match &outlier {
+        Some(outlier) if args.reject_outliers => replicates.without(outlier.index),
+        _ => replicates.clone(),
+    };
+    // The buffer is read at the temperature of the adjustment, and converted by conph compensating from that of the
+    // full calibration, taken as 25˚C for legacy records
+    let calibration_temperature = record.temperature.unwrap_or(25.0);
+    let calibration = ph_calibration_one_point_at(
+        &record.calibration(),
+        record.reading,
+        &replicates.mean(),
+        &ph_buffer,
+        &calibration_temperature,
+        &temperature,
+    )?;
+    // The slope of the electrode is that of the full calibration, at its temperature
+    let electrode =
+        Electrode::from_calibration(&calibration, record.reading, &calibration_temperature);
+    let acceptance = Acceptance::evaluate(&electrode, &args.limits);
+    let adjusted = record
+        .adjusted(
+            &calibration,
+            nominal,
+            ph_buffer,
+            replicates.mean(),
+            temperature,
+            range_warning,
+        )?
+        .with_electrode(args.electrode.clone().or_else(|| record.electrode.clone()))
+        .with_acceptance(acceptance.clone());
+
+    if args.format == Format::Table {
+        let mut stdout = StandardStream::stdout(color_choice());
+        writeln!(&mut stdout, "\n-----------------")?;
+        stdout.set_color(ColorSpec::new().set_bold(true))?;
+        writeln!(&mut stdout, "  Adjusting")?;
+        stdout.reset()?;
+        writeln!(&mut stdout, "-----------------")?;
+        write_temperature(&mut stdout, &args.temperature, args.temperature_unit)?;
+        if !range_warnings.is_empty() {
+            write_range_warnings(&mut stdout, &range_warnings, args.temperature_unit)?;
+            writeln!(&mut stdout, "-----------------")?;
+        }
+        if args.replicates[0].readings.len() > 1 {
+            write_replicates(
+                &mut stdout,
+                &[ph_buffer],
+                &args.replicates,
+                &[outlier],
+                args.outlier_test,
+                args.reject_outliers,
+            )?;
+        } else {
+            writeln!(&mut stdout, "Buffer\tReading")?;
+            writeln!(&mut stdout, "{:.4}\t{}", ph_buffer, replicates)?;
+        }
+        writeln!(&mut stdout, "-----------------")?;
+        stdout.set_color(ColorSpec::new().set_bold(true))?;
+        write!(&mut stdout, "Slope\t{:.5}", calibration.slope)?;
+        stdout.reset()?;
+        match adjusted
+            .adjustment
+            .as_ref()
+            .and_then(|adjustment| adjustment.calibrated)
+        {
+            Some(calibrated) => writeln!(
+                &mut stdout,
+                "\tkept from {}",
+                calibrated.format("%Y-%m-%d %H:%M UTC")
+            )?,
+            None => writeln!(&mut stdout, "\tkept")?,
+        }
+        stdout.set_color(ColorSpec::new().set_bold(true))?;
+        writeln!(
+            &mut stdout,
+            "Offset\t{:.5} → {:.5}",
+            record.offset, calibration.offset
+        )?;
+        stdout.reset()?;
+        writeln!(&mut stdout, "-----------------")?;
+        write!(
+            &mut stdout,
+            "Slope\t{:.2} mV/pH\t{:.1} %\t",
+            electrode.slope, acceptance.slope
+        )?;
+        write_verdict(&mut stdout, acceptance.slope_verdict)?;
+        write!(&mut stdout, "Zero\t{:.1} mV\t\t", acceptance.offset)?;
+        write_verdict(&mut stdout, acceptance.offset_verdict)?;
+        writeln!(&mut stdout, "-----------------")?;
+    } else {
+        if let Some(warning) = range_warning {
+            eprintln!("warning: {}", warning.in_unit(args.temperature_unit));
+        }
+        if let Some(outlier) = &outlier {
+            eprintln!(
+                "warning: the reading {} of the pH {} buffer is an outlier by the {} test",
+                outlier.reading, nominal, args.outlier_test
+            );
+        }
+        write_calibration(io::stdout().lock(), &adjusted, args.format)?;
+    }
+
+    adjusted.write(RECORD_FILE)?;
+    write_saved(args.format);
+
+    acceptance.check()
+}
+
+/// Reports on the stored calibration, with its uncertainty budget if asked
+fn report(args: &ReportArgs, buffer_set: BufferSet) -> Result<()> {
+    let record = CalibrationRecord::read(RECORD_FILE)?;
+    // The buffer set the calibration was made with, if it is one of the built in sets
+    let buffer_set = record
+        .buffer_set
+        .as_deref()
+        .and_then(|name| name.parse::<BufferSet>().ok())
+        .filter(|set| set.name != buffer_set.name)
+        .map(|mut set| {
+            set.merge(buffer_set.clone());
+            set
+        })
+        .unwrap_or(buffer_set);
+    // Interpolated as the calibration was
+    let buffer_set = match record.interpolation {
+        Some(interpolation) => buffer_set.with_interpolation(interpolation),
+        None => buffer_set,
+    };
+
+    let mut stdout = StandardStream::stdout(color_choice());
     writeln!(&mut stdout, "\n-----------------")?;
     stdout.set_color(ColorSpec::new().set_bold(true))?;
-    writeln!(&mut stdout, "  Calibrating")?;
+    writeln!(&mut stdout, "  Calibration")?;
     stdout.reset()?;
     writeln!(&mut stdout, "-----------------")?;
+    if let Some(timestamp) = record.timestamp {
+        writeln!(
+            &mut stdout,
+            "{:<12}{}",
+            "Time",
+            timestamp.format("%Y-%m-%d %H:%M:%S UTC")
+        )?;
+    }
+    if let Some(electrode) = &record.electrode {
+        writeln!(&mut stdout, "{:<12}{}", "Electrode", electrode)?;
+    }
+    let unit = args.temperature_unit;
+    if let Some(temperature) = record.temperature {
+        let temperature = Temperature::from_celsius(temperature).to_unit(unit);
+        writeln!(&mut stdout, "{:<12}{}", "Temperature", temperature)?;
+    }
+    let warnings = record.temperature_warnings.iter().chain(
+        record
+            .adjustment
+            .as_ref()
+            .and_then(|adjustment| adjustment.warning.as_ref()),
+    );
+    for warning in warnings {
+        stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        writeln!(&mut stdout, "{:<12}{}", "Warning", warning)?;
+        stdout.reset()?;
+    }
+    writeln!(&mut stdout, "{:<12}{:.5}", "Slope", record.slope)?;
+    writeln!(&mut stdout, "{:<12}{:.5}", "Offset", record.offset)?;
+    if let Some(adjustment) = &record.adjustment {
+        writeln!(
+            &mut stdout,
+            "{:<12}one point, pH {:.4} read as {:.4} {} at {}",
+            "Adjusted",
+            adjustment.buffer,
+            adjustment.reading,
+            record.reading,
+            Temperature::from_celsius(adjustment.temperature).to_unit(unit)
+        )?;
+        if let Some(calibrated) = adjustment.calibrated {
+            writeln!(
+                &mut stdout,
+                "{:<12}{}",
+                "Calibrated",
+                calibrated.format("%Y-%m-%d %H:%M:%S UTC")
+            )?;
+        }
+    }
+    if !record.readings.is_empty() {
+        writeln!(&mut stdout, "-----------------")?;
+        writeln!(&mut stdout, "{:<12}Reading", "Buffer")?;
+        for (buffer, reading) in record.buffers.iter().zip(record.readings.iter()) {
+            writeln!(
+                &mut stdout,
+                "{:<12.4}{} {}",
+                buffer, reading, record.reading
+            )?;
+        }
+    }
+    if let Some(acceptance) = &record.acceptance {
+        writeln!(&mut stdout, "-----------------")?;
+        write!(&mut stdout, "{:<12}", "Verdict")?;
+        write_verdict(&mut stdout, acceptance.verdict)?;
+    }
+    writeln!(&mut stdout, "-----------------")?;
+    if !args.uncertainty {
+        return Ok(());
+    }
+
+    let model = BudgetModel::from_record(&record, &buffer_set)?;
+    let sources = args
+        .sources
+        .with_defaults(UncertaintySources::typical(record.reading));
+    // Reading of a solution at pH 7 by default, through the model of the calibration
+    let reading = match args.reading {
+        Some(reading) => reading,
+        None => record.model(&ModelRegistry::default())?.inverse(&7.0)?,
+    };
+    let output = Output::Ph(reading);
+    let budget = model.budget(&sources, output)?;
+
     stdout.set_color(ColorSpec::new().set_bold(true))?;
-    writeln!(&mut stdout, "Slope\t{:.5}", calibration.slope)?;
-    println!("Offset\t{:.5}", calibration.offset);
+    writeln!(&mut stdout, "  Uncertainty budget")?;
     stdout.reset()?;
     writeln!(&mut stdout, "-----------------")?;
+    if record.is_partial() {
+        stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        writeln!(
+            &mut stdout,
+            "Of the full calibration, before its one-point adjustment"
+        )?;
+        stdout.reset()?;
+        writeln!(&mut stdout, "-----------------")?;
+    }
+    writeln!(
+        &mut stdout,
+        "{:<28}{:>10}  {:<12}{:>10}{:>10}",
+        "Source", "u(xi)", "Distribution", "ci", "ui(y)"
+    )?;
+    for contribution in &budget.contributions {
+        writeln!(
+            &mut stdout,
+            "{:<28}{:>10.4}  {:<12}{:>10.4}{:>10.5}",
+            contribution.source,
+            contribution.standard_uncertainty,
+            contribution.distribution,
+            contribution.sensitivity,
+            contribution.contribution
+        )?;
+    }
+    writeln!(&mut stdout, "-----------------")?;
+    writeln!(
+        &mut stdout,
+        "{:<12}{:.4} {}",
+        "Sample", reading, record.reading
+    )?;
+    write_budget(&mut stdout, &budget, "pH", 4)?;
+    write_budget(
+        &mut stdout,
+        &model.budget(&sources, Output::Slope)?,
+        "Slope",
+        5,
+    )?;
+    write_budget(
+        &mut stdout,
+        &model.budget(&sources, Output::Offset)?,
+        "Offset",
+        5,
+    )?;
+    writeln!(&mut stdout, "-----------------")?;
 
-    if args.store {
-        let mut file = File::create("calibration.ph")?;
-        write!(file, "{}\t{}", calibration.slope, calibration.offset)?;
-        println!("\nSaved to calibration.ph\n");
+    if let Some(samples) = args.monte_carlo {
+        let monte_carlo = model.monte_carlo(&sources, output, samples, args.seed)?;
+        writeln!(
+            &mut stdout,
+            "{:<12}{} samples, seed {}",
+            "Monte Carlo", monte_carlo.samples, monte_carlo.seed
+        )?;
+        writeln!(
+            &mut stdout,
+            "{:<12}{:.4} ± {:.4}",
+            "pH", monte_carlo.mean, monte_carlo.standard_uncertainty
+        )?;
+        writeln!(
+            &mut stdout,
+            "{:<12}{:.4} – {:.4}",
+            "95 %", monte_carlo.interval.0, monte_carlo.interval.1
+        )?;
+        writeln!(&mut stdout, "-----------------")?;
+    }
+    Ok(())
+}
+
+/// Writes the value of an output with its combined and expanded uncertainty
+fn write_budget(
+    stdout: &mut StandardStream,
+    budget: &Budget,
+    name: &str,
+    precision: usize,
+) -> Result<()> {
+    writeln!(
+        stdout,
+        "{:<12}{:<10.*}u = {:<10.*}U = {:.*} (k = {})",
+        name,
+        precision,
+        budget.value,
+        precision,
+        budget.combined,
+        precision,
+        budget.expanded,
+        budget.coverage_factor
+    )?;
+    Ok(())
+}
+
+/// Writes the goodness of fit of a model which is not a line, and whether each of the terms it adds to the line is
+/// justified
+fn write_term_tests(
+    stdout: &mut StandardStream,
+    model: &dyn PhModel,
+    ph_measured: &[f64],
+    ph_buffer: &[f64],
+) -> Result<()> {
+    let report = match model.fit_report(ph_measured, ph_buffer)? {
+        Some(report) => report,
+        None => return Ok(()),
+    };
+    if report.is_exact() {
+        writeln!(stdout, "Exact fit, no residual information")?;
+        return Ok(());
+    }
+    writeln!(stdout, "RMSE\t{:.5}", report.rmse.unwrap_or_default())?;
+    writeln!(
+        stdout,
+        "Adj. R²\t{:.5}",
+        report.adjusted_rsq.unwrap_or_default()
+    )?;
+    writeln!(stdout, "DoF\t{}", report.dof)?;
+
+    let tests = model.term_tests(ph_measured, ph_buffer)?;
+    if tests.is_empty() {
+        return Ok(());
+    }
+    writeln!(stdout, "-----------------")?;
+    writeln!(stdout, "Degree\tF\tp")?;
+    for test in tests.iter() {
+        write!(
+            stdout,
+            "{}\t{:.2}\t{:.4}\t",
+            test.degree, test.f, test.p_value
+        )?;
+        if test.is_significant(SIGNIFICANCE) {
+            writeln!(stdout, "justified")?;
+        } else {
+            stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+            writeln!(stdout, "not justified")?;
+            stdout.reset()?;
+        }
     }
+    Ok(())
+}
+
+/// Writes the mean and standard deviation of the replicate readings of each buffer, flagging any outlier
+fn write_replicates(
+    stdout: &mut StandardStream,
+    ph_buffer: &[f64],
+    replicates: &[Replicates],
+    outliers: &[Option<Outlier>],
+    test: OutlierTest,
+    reject: bool,
+) -> Result<()> {
+    writeln!(stdout, "Buffer\tMean\tSD\tn")?;
+    for ((buffer, replicates), outlier) in
+        ph_buffer.iter().zip(replicates.iter()).zip(outliers.iter())
+    {
+        // The statistics are of the readings kept
+        let kept = match outlier {
+            Some(outlier) if reject => replicates.without(outlier.index),
+            _ => replicates.clone(),
+        };
+        let deviation = match kept.standard_deviation() {
+            Some(deviation) => format!("{:.4}", deviation),
+            None => "-".to_string(),
+        };
+        write!(
+            stdout,
+            "{:.4}\t{}\t{}\t{}",
+            buffer,
+            kept,
+            deviation,
+            kept.readings.len()
+        )?;
+        if let Some(outlier) = outlier {
+            let statistic = match test {
+                OutlierTest::Grubbs => "G",
+                OutlierTest::Dixon => "Q",
+            };
+            stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+            write!(
+                stdout,
+                "\toutlier {} ({} = {:.2} > {:.2})",
+                outlier.reading, statistic, outlier.statistic, outlier.critical
+            )?;
+            if reject {
+                write!(stdout, " rejected")?;
+            }
+            stdout.reset()?;
+        }
+        writeln!(stdout)?;
+    }
+    Ok(())
+}
 
+/// Writes the temperature of measurement when it is shown in a unit other than ˚C, with the value in ˚C the buffers
+/// are corrected to
+fn write_temperature(
+    stdout: &mut StandardStream,
+    temperature: &Temperature,
+    unit: TemperatureUnit,
+) -> Result<()> {
+    if unit != TemperatureUnit::Celsius {
+        writeln!(
+            stdout,
+            "Temp.\t{} ({})",
+            temperature.to_unit(unit),
+            temperature.to_unit(TemperatureUnit::Celsius)
+        )?;
+        writeln!(stdout, "-----------------")?;
+    }
+    Ok(())
+}
+
+/// Writes the buffers used beyond their tables, and how their pH was found, with the temperatures in `unit`
+fn write_range_warnings(
+    stdout: &mut StandardStream,
+    warnings: &[RangeWarning],
+    unit: TemperatureUnit,
+) -> Result<()> {
+    stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+    for warning in warnings {
+        writeln!(stdout, "warning: {}", warning.in_unit(unit))?;
+    }
+    stdout.reset()?;
     Ok(())
 }
+
+/// Writes the weight of a buffer on the end of its line, flagging the outliers of a robust fit
+fn write_weight(stdout: &mut StandardStream, weight: f64, robust: bool) -> Result<()> {
+    write!(stdout, "\t{:.2}", weight)?;
+    if robust && weight < OUTLIER_WEIGHT {
+        stdout.set_color(ColorSpec::new().set_fg(Some(Color::Yellow)))?;
+        let flag = if weight == 0.0 {
+            "rejected"
+        } else {
+            "down-weighted"
+        };
+        write!(stdout, "\t{}", flag)?;
+        stdout.reset()?;
+    }
+    writeln!(stdout)?;
+    Ok(())
+}
+
+/// Writes a verdict on its own line, coloured by how good it is
+fn write_verdict(stdout: &mut StandardStream, verdict: Verdict) -> Result<()> {
+    let color = match verdict {
+        Verdict::Pass => Color::Green,
+        Verdict::Warn => Color::Yellow,
+        Verdict::Fail => Color::Red,
+    };
+    stdout.set_color(ColorSpec::new().set_fg(Some(color)).set_bold(true))?;
+    write!(stdout, "{}", verdict)?;
+    stdout.reset()?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+/// Tells where the calibration was saved, under the table or on stderr when stdout carries JSON or CSV
+fn write_saved(format: Format) {
+    if format == Format::Table {
+        println!("\nSaved to {}\n", RECORD_FILE);
+    } else {
+        eprintln!("Saved to {}", RECORD_FILE);
+    }
+}
@@ -4,7 +4,7 @@ file, You can obtain one at https://mozilla.org/MPL/2.0/.
 Copyright 2021 Peter Dunne */
 //! Command line tool to correct a pH measurement using a calibration model.
 //!
-//!Assuming the `calibration.ph` file exists:
+//!Assuming the `calibration.ph` file exists (legacy files holding only the slope and offset can also be read):
 //!
 //!```console
 //!$ conph 3.5
@@ -12,8 +12,9 @@ Copyright 2021 Peter Dunne */
 //!---------------
 //!  Converting
 //!---------------
-//!Input   3.5
+//!Input   3.5 pH
 //!Output  3.5495
+//!Calibrated      2021-11-02 10:41:07 UTC
 //!---------------
 //!
 //!```
@@ -28,45 +29,204 @@ Copyright 2021 Peter Dunne */
 //!---------------
 //!  Converting
 //!---------------
-//!Input   3.5
+//!Input   3.5 pH
 //!Output  3.8700
 //!---------------
+//!```
+//!
+//!When the sample is measured at a different temperature to the calibration, `-t` compensates the slope of the
+//!electrode for the change in Nernstian slope around the isopotential point at pH 7. The calibration temperature is read
+//!from `calibration.ph`, or given with `--calibration-temperature` for custom calibrations (25˚C if unknown):
+//!
+//!```console
+//!$ conph 3.5 -c -s 1.1 -o 0.02 -t 40
+//!
+//!---------------
+//!  Converting
+//!---------------
+//!Input   3.5 pH
+//!Output  4.0544
+//!Temp.   40 ˚C (calibrated at 25 ˚C)
+//!---------------
+//!```
+//!
+//!`-t` and `--calibration-temperature` take a unit in the same way as for `caliph`, e.g. `-t 104F`, and the temperatures
+//!are shown in the unit given with `--temperature-unit`, or else in that of the temperature:
+//!
+//!```console
+//!$ conph 3.5 -c -s 1.1 -o 0.02 -t 104F
+//!
+//!---------------
+//!  Converting
+//!---------------
+//!Input   3.5 pH
+//!Output  4.0544
+//!Temp.   104 ˚F (calibrated at 77 ˚F)
+//!---------------
+//!```
+//!
+//!With a calibration of more than two buffers, `-u` gives the interval of each converted pH from the uncertainty of
+//!the fit, at the confidence level given with `--level` (95 % by default). The prediction interval of the single sample
+//!measured is given by default, or the confidence interval of the mean pH with `--interval confidence`:
+//!
+//!```console
+//!$ caliph 1.5 3.8 6.6 8.9 9.8 -b 1.68,4.01,6.86,9.18,10.01 --buffer-set nist -s > /dev/null
+//!$ conph 7 -u
+//!
+//!---------------
+//!  Converting
+//!---------------
+//!Input   7 pH
+//!Output  7.2352 ± 0.1315 (95 %)
+//!Calibrated      2021-11-02 10:41:07 UTC
+//!---------------
+//!```
+//!
+//!Without a pH value, the measurements are read one per line from stdin, or from a file given with `-i`:
+//!
+//!```console
+//!$ printf '3.5\n4.0\n' | conph -c -s 1.1 -o 0.02
+//!3.8700
+//!4.4200
+//!```
+//!
+//!For CSV files, `--column` selects the column of measurements by name, or by position counting from 1.
+//!All columns are passed through, with the converted values appended as a new column:
 //!
+//!```console
+//!$ conph -c -s 1.1 -o 0.02 -i samples.csv --column ph
+//!sample,ph,ph_calibrated
+//!A,3.5,3.8700
+//!B,4.0,4.4200
 //!```
 //!
-use anyhow::Result;
-use termcolor::{ColorChoice, ColorSpec, StandardStream, WriteColor};
+use termcolor::{ColorSpec, StandardStream, WriteColor};
 
+use libcaliph::acceptance::Verdict;
 use libcaliph::args::ConvArgs;
-use libcaliph::routines::ph_convert;
+use libcaliph::batch::{convert_csv, read_lines};
+use libcaliph::model::{compensate, ModelRegistry, PhModel};
+use libcaliph::output::{color_choice, write_conversions, Conversion, Format};
+use libcaliph::record::{CalibrationRecord, RECORD_FILE};
+use libcaliph::routines::{ph_convert, ph_convert_interval, Calibration};
+use libcaliph::temperature::Temperature;
+use libcaliph::{Error, Result};
 use std::fs::File;
 use std::io::prelude::*;
-use std::io::BufReader;
+use std::io::{self, BufReader};
+use std::process;
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("error: {}", err);
+        process::exit(err.exit_code());
+    }
+}
 
-fn main() -> Result<()> {
+fn run() -> Result<()> {
     // Parse CLI
     let args = ConvArgs::parse();
 
-    let ph_measured = args.ph;
-    let calibration = if !args.custom {
-        let file = File::open("calibration.ph")?;
-        let mut buf_reader = BufReader::new(file);
-        let mut contents = String::new();
-        buf_reader.read_to_string(&mut contents)?;
+    let (model, reading, record): (Box<dyn PhModel>, _, _) = match args.calibration {
+        Some([slope, offset]) => (
+            Box::new(Calibration::new(slope, offset, None, None)),
+            args.reading,
+            None,
+        ),
+        None => {
+            let record = CalibrationRecord::read(RECORD_FILE)?;
+            if let Some(acceptance) = &record.acceptance {
+                if acceptance.verdict == Verdict::Fail {
+                    eprintln!(
+                        "warning: the calibration in {} failed its acceptance criteria",
+                        RECORD_FILE
+                    );
+                }
+            }
+            let model = record.model(&ModelRegistry::default())?;
+            (model, record.reading, Some(record))
+        }
+    };
 
-        // Fragile, assumes two floats separated by a tab/whitespace
-        let cal_vec = contents
-            .split_whitespace()
-            .filter_map(|s| s.parse::<f64>().ok())
-            .collect::<Vec<_>>();
-        [cal_vec[0], cal_vec[1]]
+    // The interval is that of the calibration at its own temperature
+    let uncertainty = if args.uncertainty {
+        let uncertainty = record.as_ref().and_then(|record| record.uncertainty);
+        Some(uncertainty.ok_or(Error::NoUncertainty)?)
     } else {
-        args.calibration.unwrap()
+        None
+    };
+
+    // Legacy calibration files do not record their temperature, and were made at 25˚C by default
+    let calibration_temperature = match &record {
+        Some(record) => record.temperature.unwrap_or(25.0),
+        None => args
+            .calibration_temperature
+            .map(|temperature| temperature.celsius())
+            .unwrap_or(25.0),
+    };
+    let temperature = args
+        .temperature
+        .map(|temperature| temperature.celsius())
+        .unwrap_or(calibration_temperature);
+
+    let model = compensate(
+        model.as_ref(),
+        reading,
+        &calibration_temperature,
+        &temperature,
+    );
+
+    let ph_measured = match args.ph {
+        Some(ph) => vec![ph],
+        None => {
+            let reader: Box<dyn BufRead> = if args.input == "-" {
+                Box::new(io::stdin().lock())
+            } else {
+                let file = File::open(&args.input).map_err(|source| Error::File {
+                    path: args.input.clone().into(),
+                    source,
+                })?;
+                Box::new(BufReader::new(file))
+            };
+            if let Some(column) = &args.column {
+                convert_csv(
+                    reader,
+                    io::stdout().lock(),
+                    column,
+                    !args.no_header,
+                    args.delimiter,
+                    model.as_ref(),
+                )?;
+                return Ok(());
+            }
+            read_lines(reader)?
+        }
     };
 
-    let ph_correct = ph_convert(&ph_measured, &calibration);
+    let level = args.level / 100.0;
+    let conversions = ph_measured
+        .iter()
+        .map(|ph| match &uncertainty {
+            Some(uncertainty) => {
+                let interval = ph_convert_interval(ph, model.as_ref(), uncertainty, &level)?;
+                Ok(Conversion::new(*ph, interval.ph)
+                    .with_uncertainty(interval.half_width(args.interval), args.level))
+            }
+            None => Ok(Conversion::new(*ph, ph_convert(ph, model.as_ref()))),
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    let mut stdout = StandardStream::stdout(ColorChoice::Always);
+    let format = args.format.unwrap_or(if args.ph.is_some() {
+        Format::Table
+    } else {
+        Format::Plain
+    });
+    if format != Format::Table {
+        write_conversions(io::stdout().lock(), &conversions, format)?;
+        return Ok(());
+    }
+
+    let mut stdout = StandardStream::stdout(color_choice());
     writeln!(&mut stdout, "\n---------------")?;
     stdout.set_color(ColorSpec::new().set_bold(true))?;
     writeln!(&mut stdout, "  Converting")?;
@@ -74,10 +234,41 @@ fn main() -> Result<()> {
     writeln!(&mut stdout, "---------------")?;
 
     stdout.set_color(ColorSpec::new().set_bold(true))?;
-    writeln!(&mut stdout, "Input\t{}", ph_measured)?;
-    writeln!(&mut stdout, "Output\t{:.4}", ph_correct)?;
-    stdout.reset()?;
+    if let [conversion] = conversions.as_slice() {
+        writeln!(&mut stdout, "Input\t{} {}", conversion.input, reading)?;
+        writeln!(&mut stdout, "Output\t{}", output(conversion))?;
+        stdout.reset()?;
+    } else {
+        writeln!(&mut stdout, "Input\tOutput")?;
+        stdout.reset()?;
+        for conversion in conversions.iter() {
+            writeln!(&mut stdout, "{}\t{}", conversion.input, output(conversion))?;
+        }
+    }
+    if args.temperature.is_some() {
+        let unit = args.temperature_unit;
+        writeln!(
+            &mut stdout,
+            "Temp.\t{} (calibrated at {})",
+            Temperature::from_celsius(temperature).to_unit(unit),
+            Temperature::from_celsius(calibration_temperature).to_unit(unit)
+        )?;
+    }
+    if let Some(timestamp) = record.as_ref().and_then(|record| record.timestamp) {
+        writeln!(&mut stdout, "Calibrated\t{}", timestamp.format("%F %T UTC"))?;
+    }
     writeln!(&mut stdout, "---------------\n")?;
 
     Ok(())
 }
+
+/// Converted pH value, with its interval if known
+fn output(conversion: &Conversion) -> String {
+    match (conversion.uncertainty, conversion.level) {
+        (Some(uncertainty), Some(level)) => format!(
+            "{:.4} ± {:.4} ({} %)",
+            conversion.output, uncertainty, level
+        ),
+        _ => format!("{:.4}", conversion.output),
+    }
+}
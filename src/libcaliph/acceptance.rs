@@ -0,0 +1,238 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Acceptance Module
+//! Provides the acceptance criteria of a calibration, following good laboratory practice.
+//!
+//! The calibration is judged on the electrode it describes, see [`crate::electrode`]:
+//!
+//! - its slope, in % of the Nernstian slope $`S_N = R T \ln 10 / F`$ at the calibration temperature,
+//! - its zero point offset, the asymmetry potential in mV of the electrode in a solution of pH 7.
+//!
+//! Within the pass limits the calibration passes, within the warn limits it passes with a warning
+//! that the electrode needs attention, and beyond them it fails.
+
+use super::electrode::Electrode;
+use super::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Verdict of the acceptance criteria, ordered from best to worst
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Pass,
+    /// Acceptable, but the electrode should be cleaned or replaced soon
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Verdict::Pass => "pass",
+            Verdict::Warn => "warn",
+            Verdict::Fail => "fail",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Limits of the acceptance criteria
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AcceptanceLimits {
+    /// Range of the slope which passes, in % of the Nernstian slope
+    pub slope_pass: (f64, f64),
+    /// Range of the slope which passes with a warning, in % of the Nernstian slope
+    pub slope_warn: (f64, f64),
+    /// Largest magnitude of the zero point offset which passes, in mV
+    pub offset_pass: f64,
+    /// Largest magnitude of the zero point offset which passes with a warning, in mV
+    pub offset_warn: f64,
+}
+
+impl Default for AcceptanceLimits {
+    /// Slope within 95 – 105 % and offset within ±15 mV pass, slope within 90 – 110 % and
+    /// offset within ±30 mV pass with a warning
+    fn default() -> Self {
+        AcceptanceLimits {
+            slope_pass: (95.0, 105.0),
+            slope_warn: (90.0, 110.0),
+            offset_pass: 15.0,
+            offset_warn: 30.0,
+        }
+    }
+}
+
+impl AcceptanceLimits {
+    /// Checks that the limits are finite, and that the pass limits lie within the warn limits
+    pub fn validate(&self) -> Result<()> {
+        let limits = [
+            self.slope_pass.0,
+            self.slope_pass.1,
+            self.slope_warn.0,
+            self.slope_warn.1,
+            self.offset_pass,
+            self.offset_warn,
+        ];
+        if limits.iter().any(|limit| !limit.is_finite()) {
+            return Err(Error::NonFinite("acceptance limits".to_string()));
+        }
+        if self.slope_pass.0 > self.slope_pass.1 {
+            return Err(Error::InvalidInput {
+                line: None,
+                message: "the lower slope limit is above the upper one".to_string(),
+            });
+        }
+        if self.slope_warn.0 > self.slope_pass.0 || self.slope_warn.1 < self.slope_pass.1 {
+            return Err(Error::InvalidInput {
+                line: None,
+                message: "the slope pass limits must lie within the warn limits".to_string(),
+            });
+        }
+        if self.offset_pass < 0.0 || self.offset_warn < self.offset_pass {
+            return Err(Error::InvalidInput {
+                line: None,
+                message: "the offset pass limit must lie between 0 and the warn limit".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Acceptance criteria evaluated for a calibration
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Acceptance {
+    /// Slope of the electrode, in % of the Nernstian slope
+    pub slope: f64,
+    pub slope_verdict: Verdict,
+    /// Zero point offset of the electrode, in mV
+    pub offset: f64,
+    pub offset_verdict: Verdict,
+    /// Worst of the slope and offset verdicts
+    pub verdict: Verdict,
+    /// Limits the calibration was judged against
+    pub limits: AcceptanceLimits,
+}
+
+impl Acceptance {
+    /// Evaluates the acceptance criteria of the electrode described by a calibration
+    pub fn evaluate(electrode: &Electrode, limits: &AcceptanceLimits) -> Acceptance {
+        let slope = electrode.slope_percent();
+        let offset = electrode.asymmetry_potential();
+
+        let slope_verdict = if slope >= limits.slope_pass.0 && slope <= limits.slope_pass.1 {
+            Verdict::Pass
+        } else if slope >= limits.slope_warn.0 && slope <= limits.slope_warn.1 {
+            Verdict::Warn
+        } else {
+            Verdict::Fail
+        };
+        let offset_verdict = if offset.abs() <= limits.offset_pass {
+            Verdict::Pass
+        } else if offset.abs() <= limits.offset_warn {
+            Verdict::Warn
+        } else {
+            Verdict::Fail
+        };
+
+        Acceptance {
+            slope,
+            slope_verdict,
+            offset,
+            offset_verdict,
+            verdict: slope_verdict.max(offset_verdict),
+            limits: *limits,
+        }
+    }
+
+    /// Returns an error describing the failed criteria if the calibration fails
+    pub fn check(&self) -> Result<()> {
+        if self.verdict != Verdict::Fail {
+            return Ok(());
+        }
+        let mut reasons = Vec::new();
+        if self.slope_verdict == Verdict::Fail {
+            reasons.push(format!(
+                "slope {:.1} % is outside {} – {} %",
+                self.slope, self.limits.slope_warn.0, self.limits.slope_warn.1
+            ));
+        }
+        if self.offset_verdict == Verdict::Fail {
+            reasons.push(format!(
+                "offset {:.1} mV is outside ±{} mV",
+                self.offset, self.limits.offset_warn
+            ));
+        }
+        Err(Error::CalibrationRejected(reasons.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{Acceptance, AcceptanceLimits, Verdict};
+    use crate::electrode::{Electrode, Reading};
+    use crate::routines::Calibration;
+
+    fn evaluate(calibration: &Calibration<f64>, limits: &AcceptanceLimits) -> Acceptance {
+        let electrode = Electrode::from_calibration(calibration, Reading::Ph, &25.0);
+        Acceptance::evaluate(&electrode, limits)
+    }
+
+    #[test]
+    fn test_acceptance_pass() {
+        let calibration = Calibration::default().with_slope(1.0).with_offset(0.0);
+        let acceptance = evaluate(&calibration, &AcceptanceLimits::default());
+
+        assert!(approx_eq!(f64, acceptance.slope, 100.0));
+        assert!(approx_eq!(f64, acceptance.offset, 0.0));
+        assert_eq!(acceptance.verdict, Verdict::Pass);
+        assert!(acceptance.check().is_ok());
+    }
+
+    #[test]
+    fn test_acceptance_verdicts() {
+        let limits = AcceptanceLimits::default();
+
+        // 93 % of the Nernstian slope
+        let calibration = Calibration::default().with_slope(1.0 / 0.93);
+        let acceptance = evaluate(&calibration, &limits);
+        assert_eq!(acceptance.slope_verdict, Verdict::Warn);
+        assert_eq!(acceptance.verdict, Verdict::Warn);
+
+        // Reads 7.5 at pH 7, about -29.6 mV
+        let calibration = Calibration::default().with_slope(1.0).with_offset(-0.5);
+        let acceptance = evaluate(&calibration, &limits);
+        assert!(approx_eq!(f64, acceptance.offset, -29.58, epsilon = 0.01));
+        assert_eq!(acceptance.offset_verdict, Verdict::Warn);
+
+        let calibration = Calibration::default()
+            .with_slope(1.0 / 0.85)
+            .with_offset(1.0);
+        let acceptance = evaluate(&calibration, &limits);
+        assert_eq!(acceptance.slope_verdict, Verdict::Fail);
+        assert_eq!(acceptance.offset_verdict, Verdict::Fail);
+        assert_eq!(acceptance.verdict, Verdict::Fail);
+        assert!(acceptance.check().is_err());
+    }
+
+    #[test]
+    fn test_acceptance_limits_validate() {
+        assert!(AcceptanceLimits::default().validate().is_ok());
+
+        let limits = AcceptanceLimits {
+            slope_warn: (96.0, 110.0),
+            ..AcceptanceLimits::default()
+        };
+        assert!(limits.validate().is_err());
+
+        let limits = AcceptanceLimits {
+            offset_warn: 10.0,
+            ..AcceptanceLimits::default()
+        };
+        assert!(limits.validate().is_err());
+    }
+}
@@ -8,5 +8,45 @@ Copyright 2021 Peter Dunne */
 mod args_caliph;
 mod args_conph;
 
-pub use args_caliph::CalibArgs;
+pub use args_caliph::{CalibArgs, PartialSources, ReportArgs};
 pub use args_conph::ConvArgs;
+
+use crate::temperature::TEMPERATURE_UNIT_NAMES;
+use clap::{ArgMatches, PossibleValue};
+
+/// Parses a finite floating point value, used to validate the command line arguments
+pub(crate) fn finite(value: &str) -> Result<f64, String> {
+    match value.trim().parse::<f64>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => Err(format!("{:?} is not a finite number", value)),
+    }
+}
+
+/// Parses a finite value which is not negative, used to validate uncertainties
+pub(crate) fn non_negative(value: &str) -> Result<f64, String> {
+    match finite(value)? {
+        number if number >= 0.0 => Ok(number),
+        _ => Err(format!("{:?} is negative", value)),
+    }
+}
+
+/// Names of the temperature units, each also given by its initial, e.g. `F`
+pub(crate) fn temperature_units() -> impl Iterator<Item = PossibleValue<'static>> {
+    TEMPERATURE_UNIT_NAMES
+        .iter()
+        .map(|name| PossibleValue::new(name).alias(&name[..1]))
+}
+
+/// Value of an argument validated with `finite`
+pub(crate) fn value_of_finite(matches: &ArgMatches, name: &str) -> Option<f64> {
+    matches.value_of(name).and_then(|value| finite(value).ok())
+}
+
+/// Values of an argument validated with `finite`
+pub(crate) fn values_of_finite(matches: &ArgMatches, name: &str) -> Vec<f64> {
+    matches
+        .values_of(name)
+        .unwrap_or_default()
+        .filter_map(|value| finite(value).ok())
+        .collect()
+}
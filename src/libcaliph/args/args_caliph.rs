@@ -2,86 +2,541 @@
 License, v. 2.0. If a copy of the MPL was not distributed with this
 file, You can obtain one at https://mozilla.org/MPL/2.0/.
 Copyright 2021 Peter Dunne */
+//! Read in command line arguments for `caliph` using clap
 
-///! Read in command line arguments for `caliph` using clap
-use clap::{App, Arg};
+use super::{finite, non_negative, temperature_units, value_of_finite, values_of_finite};
+use crate::acceptance::AcceptanceLimits;
+use crate::budget::UncertaintySources;
+use crate::buffers::{BufferSet, InterpolationMethod, BUFFER_SET_NAMES, INTERPOLATION_NAMES};
+use crate::electrode::Reading;
+use crate::fit::{Robust, ROBUST_NAMES};
+use crate::model::{FitOptions, ModelRegistry, MODEL_NAMES};
+use crate::output::{Format, FORMAT_NAMES};
+use crate::replicate::{OutlierTest, Replicates, OUTLIER_TEST_NAMES};
+use crate::routines::{TemperaturePolicy, TEMPERATURE_POLICY_NAMES};
+use crate::temperature::{Temperature, TemperatureUnit};
+use clap::{App, AppSettings, Arg, ErrorKind};
 
 /// Command line arguments struct, infile, outfile, and silent (i.e. emit to stdout)
 pub struct CalibArgs {
-    /// pH measured for pH 4.01 buffer solution
-    pub ph4: f64,
-    /// pH measured for pH 10.01 buffer solution
-    pub ph10: f64,
+    /// pH, or potential in mV, measured for each buffer solution, the mean of its replicates
+    pub ph_measured: Vec<f64>,
+    /// Replicate readings of each buffer solution
+    pub replicates: Vec<Replicates>,
+    /// Test for an outlier among the replicate readings of each buffer
+    pub outlier_test: OutlierTest,
+    /// Leave the outliers out of the mean readings
+    pub reject_outliers: bool,
+    /// Quantity read from the meter
+    pub reading: Reading,
+    /// Name of the calibration model, see [`crate::model`]
+    pub model: String,
+    /// Options of the model fit, such as the degree of a polynomial
+    pub fit_options: FitOptions,
+    /// Robust estimator of the linear calibration, `None` for least squares
+    pub robust: Option<Robust>,
+    /// Nominal pH of each buffer solution, in the same order as `ph_measured`
+    pub buffers: Vec<f64>,
+    /// Buffer set providing the temperature dependence of the buffers
+    pub buffer_set: BufferSet,
+    /// TOML file of custom buffer solutions, added to the buffer set
+    pub buffers_file: Option<String>,
+    /// Interpolation of every buffer table, `None` for the interpolation of each buffer
+    pub interpolation: Option<InterpolationMethod>,
     /// temperature of measurement
-    pub temperature: f64,
-    /// Store calibration to file calib.ph
+    pub temperature: Temperature,
+    /// Unit temperatures are shown in
+    pub temperature_unit: TemperatureUnit,
+    /// Handling of a temperature outside the table of a buffer
+    pub temperature_policy: TemperaturePolicy,
+    /// Store calibration to file calibration.ph
     pub store: bool,
+    /// Identifier of the electrode, stored with the calibration
+    pub electrode: Option<String>,
+    /// Output format
+    pub format: Format,
+    /// Acceptance limits of the calibration
+    pub limits: AcceptanceLimits,
+    /// Adjust the offset of the stored calibration to a single buffer, keeping its slope
+    pub one_point: bool,
+    /// Report on the stored calibration instead of calibrating
+    pub report: Option<ReportArgs>,
+}
+
+/// Arguments of `caliph report`
+pub struct ReportArgs {
+    /// Give the uncertainty budget of the stored calibration
+    pub uncertainty: bool,
+    /// Reading converted to pH by the budget, `None` for the reading at pH 7
+    pub reading: Option<f64>,
+    /// Standard uncertainties of the sources, `None` for the typical values of the stored readings
+    pub sources: PartialSources,
+    /// Number of Monte Carlo samples, if the budget is also evaluated by the Monte Carlo method
+    pub monte_carlo: Option<usize>,
+    /// Seed of the Monte Carlo random number generator
+    pub seed: u64,
+    /// Unit temperatures are shown in
+    pub temperature_unit: TemperatureUnit,
+}
+
+/// Standard uncertainties given on the command line, each completing the typical sources
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PartialSources {
+    pub buffer: Option<f64>,
+    pub temperature: Option<f64>,
+    pub resolution: Option<f64>,
+    pub repeatability: Option<f64>,
+}
+
+impl PartialSources {
+    /// Sources given, the others taken from `typical`
+    pub fn with_defaults(&self, typical: UncertaintySources) -> UncertaintySources {
+        UncertaintySources {
+            buffer: self.buffer.unwrap_or(typical.buffer),
+            temperature: self.temperature.unwrap_or(typical.temperature),
+            resolution: self.resolution.unwrap_or(typical.resolution),
+            repeatability: self.repeatability.unwrap_or(typical.repeatability),
+        }
+    }
 }
 
 impl CalibArgs {
     /// Parse command line arguments
     pub fn parse() -> Self {
-        let matches = App::new("caliph")
+        let mut app = App::new("caliph")
             .author("Peter Dunne")
             .version("0.1.5")
-            .about("Calculates corrections from 2 point pH calibration")
+            .about("Calculates corrections from a multi-point pH calibration")
+            .setting(AppSettings::AllowNegativeNumbers)
+            .setting(AppSettings::SubcommandsNegateReqs)
             .arg(
-                Arg::new("ph4")
-                    .help("pH measured for pH 4.01 buffer solution")
+                Arg::new("ph_measured")
+                    .help("pH, or potential in mV with --mv, measured for each buffer solution, with any replicate readings separated by /")
                     .index(1)
                     .required(true)
-                    .takes_value(true),
+                    .takes_value(true)
+                    .multiple_values(true)
+                    .min_values(1)
+                    .validator(|value| value.parse::<Replicates>().map(|_| ())),
             )
             .arg(
-                Arg::new("ph10")
-                    .help("pH measured for pH 10.01 buffer solution")
-                    .index(2)
-                    .required(true)
+                Arg::new("outlier_test")
+                    .help("test for an outlier among the replicate readings of each buffer")
+                    .long("outlier-test")
+                    .takes_value(true)
+                    .possible_values(OUTLIER_TEST_NAMES)
+                    .ignore_case(true)
+                    .default_value("grubbs"),
+            )
+            .arg(
+                Arg::new("reject_outliers")
+                    .long("reject-outliers")
+                    .help("leave the outliers found by the test out of the mean readings"),
+            )
+            .arg(
+                Arg::new("mv")
+                    .long("mv")
+                    .help("Readings are electrode potentials in mV"),
+            )
+            .arg(
+                Arg::new("model")
+                    .help("calibration model fitted to the buffers")
+                    .long("model")
+                    .takes_value(true)
+                    .possible_values(MODEL_NAMES)
+                    .ignore_case(true)
+                    .default_value("linear"),
+            )
+            .arg(
+                Arg::new("degree")
+                    .help("degree of the polynomial model, below the number of buffers")
+                    .long("degree")
+                    .takes_value(true)
+                    .default_value("2")
+                    .validator(|value| match value.parse::<usize>() {
+                        Ok(degree) if degree >= 1 => Ok(()),
+                        _ => Err("expected a degree of at least 1".to_string()),
+                    }),
+            )
+            .arg(
+                Arg::new("robust")
+                    .help("fit the line robustly, flagging outlying buffers [default: huber]")
+                    .long("robust")
+                    .takes_value(true)
+                    .value_name("ESTIMATOR")
+                    .min_values(0)
+                    .max_values(1)
+                    .require_equals(true)
+                    .default_missing_value("huber")
+                    .possible_values(ROBUST_NAMES)
+                    .ignore_case(true),
+            )
+            .arg(
+                Arg::new("buffers")
+                    .help("comma separated nominal pH of each buffer solution")
+                    .short('b')
+                    .long("buffers")
+                    .takes_value(true)
+                    .use_delimiter(true)
+                    .require_delimiter(true)
+                    .default_value("4.01,10.01")
+                    .validator(finite),
+            )
+            .arg(
+                Arg::new("buffer_set")
+                    .help("set of buffer solutions used")
+                    .long("buffer-set")
+                    .takes_value(true)
+                    .possible_values(BUFFER_SET_NAMES)
+                    .ignore_case(true)
+                    .default_value("technical"),
+            )
+            .arg(
+                Arg::new("buffers_file")
+                    .help("TOML file of custom buffer solutions, added to the buffer set")
+                    .long("buffers-file")
                     .takes_value(true),
             )
+            .arg(
+                Arg::new("interpolation")
+                    .help("interpolation of the buffer tables in temperature [default: that of each buffer, linear for the built in sets]")
+                    .long("interpolation")
+                    .takes_value(true)
+                    .possible_values(INTERPOLATION_NAMES)
+                    .ignore_case(true),
+            )
             .arg(
                 Arg::new("temperature")
-                    .help("temperature of measurement")
+                    .help("temperature of measurement, in the unit of --temperature-unit unless followed by one, e.g. 77F [default: 25]")
                     .short('t')
                     .long("temperature")
-                    .takes_value(true),
+                    .takes_value(true)
+                    .allow_hyphen_values(true),
+            )
+            .arg(
+                Arg::new("temperature_unit")
+                    .help("unit of the temperatures given and shown [default: that of --temperature, or celsius]")
+                    .long("temperature-unit")
+                    .takes_value(true)
+                    .possible_values(temperature_units())
+                    .ignore_case(true)
+                    .global(true),
+            )
+            .arg(
+                Arg::new("temperature_policy")
+                    .help("handling of a temperature outside the table of a buffer, refusing to calibrate, or using the nearest end of the table or extending it with a warning")
+                    .long("temperature-policy")
+                    .takes_value(true)
+                    .possible_values(TEMPERATURE_POLICY_NAMES)
+                    .ignore_case(true)
+                    .default_value("error"),
             )
             .arg(
                 Arg::new("store")
                     .short('s')
                     .long("store")
-                    .help("Store calibration to file calib.ph"),
+                    .help("Store calibration to file calibration.ph"),
+            )
+            .arg(
+                Arg::new("one_point")
+                    .long("one-point")
+                    .help("Adjust the offset of the calibration stored in calibration.ph to a single buffer, keeping its slope")
+                    .conflicts_with_all(&["model", "degree", "robust"]),
             )
-            .get_matches();
+            .arg(
+                Arg::new("electrode")
+                    .short('e')
+                    .long("electrode")
+                    .help("identifier of the electrode, stored with the calibration")
+                    .takes_value(true),
+            )
+            .arg(
+                Arg::new("format")
+                    .short('f')
+                    .long("format")
+                    .help("output format")
+                    .takes_value(true)
+                    .possible_values(FORMAT_NAMES)
+                    .ignore_case(true)
+                    .default_value("table"),
+            )
+            .arg(
+                Arg::new("slope_limits")
+                    .help("range of the electrode slope which passes, in % of the Nernstian slope")
+                    .long("slope-limits")
+                    .takes_value(true)
+                    .value_names(&["MIN", "MAX"])
+                    .use_delimiter(true)
+                    .require_delimiter(true)
+                    .number_of_values(2)
+                    .default_value("95,105")
+                    .validator(finite),
+            )
+            .arg(
+                Arg::new("slope_warn_limits")
+                    .help("range of the electrode slope which passes with a warning, in % of the Nernstian slope")
+                    .long("slope-warn-limits")
+                    .takes_value(true)
+                    .value_names(&["MIN", "MAX"])
+                    .use_delimiter(true)
+                    .require_delimiter(true)
+                    .number_of_values(2)
+                    .default_value("90,110")
+                    .validator(finite),
+            )
+            .arg(
+                Arg::new("offset_limit")
+                    .help("largest zero point offset of the electrode which passes, in mV")
+                    .long("offset-limit")
+                    .takes_value(true)
+                    .default_value("15")
+                    .validator(finite),
+            )
+            .arg(
+                Arg::new("offset_warn_limit")
+                    .help("largest zero point offset of the electrode which passes with a warning, in mV")
+                    .long("offset-warn-limit")
+                    .takes_value(true)
+                    .default_value("30")
+                    .validator(finite),
+            )
+            .subcommand(
+                App::new("report")
+                    .about("Reports on the calibration stored in calibration.ph")
+                    .setting(AppSettings::AllowNegativeNumbers)
+                    .arg(
+                        Arg::new("uncertainty")
+                            .short('u')
+                            .long("uncertainty")
+                            .help("Give the uncertainty budget of the calibration, listing each contribution"),
+                    )
+                    .arg(
+                        Arg::new("reading")
+                            .long("reading")
+                            .help("Reading converted to pH by the budget [default: the reading at pH 7]")
+                            .takes_value(true)
+                            .requires("uncertainty")
+                            .validator(finite),
+                    )
+                    .arg(
+                        Arg::new("u_buffer")
+                            .long("u-buffer")
+                            .help("Standard uncertainty of the certified pH of each buffer [default: 0.01]")
+                            .takes_value(true)
+                            .requires("uncertainty")
+                            .validator(non_negative),
+                    )
+                    .arg(
+                        Arg::new("u_temperature")
+                            .long("u-temperature")
+                            .help("Standard uncertainty of the temperature, in ˚C [default: 0.1]")
+                            .takes_value(true)
+                            .requires("uncertainty")
+                            .validator(non_negative),
+                    )
+                    .arg(
+                        Arg::new("resolution")
+                            .long("resolution")
+                            .help("Resolution of the meter [default: 0.01, or 0.1 for mV]")
+                            .takes_value(true)
+                            .requires("uncertainty")
+                            .validator(non_negative),
+                    )
+                    .arg(
+                        Arg::new("repeatability")
+                            .long("repeatability")
+                            .help("Standard deviation of repeated readings [default: 0.01, or 0.5 for mV]")
+                            .takes_value(true)
+                            .requires("uncertainty")
+                            .validator(non_negative),
+                    )
+                    .arg(
+                        Arg::new("monte_carlo")
+                            .long("monte-carlo")
+                            .help("Also evaluate the budget by the Monte Carlo method, with this many samples")
+                            .takes_value(true)
+                            .value_name("SAMPLES")
+                            .requires("uncertainty")
+                            .validator(|value| match value.parse::<usize>() {
+                                Ok(samples) if samples >= 2 => Ok(()),
+                                _ => Err("expected at least 2 samples".to_string()),
+                            }),
+                    )
+                    .arg(
+                        Arg::new("seed")
+                            .long("seed")
+                            .help("Seed of the Monte Carlo random number generator [default: 1]")
+                            .takes_value(true)
+                            .requires("monte_carlo")
+                            .validator(|value| value.parse::<u64>().map(|_| ())),
+                    ),
+            );
+        let matches = app.get_matches_mut();
 
-        let ph4 = matches
-            .value_of("ph4")
-            .unwrap_or_default()
-            .parse::<f64>()
-            .unwrap();
-        let ph10 = matches
-            .value_of("ph10")
+        let replicates: Vec<Replicates> = matches
+            .values_of("ph_measured")
             .unwrap_or_default()
-            .parse::<f64>()
-            .unwrap();
-
-        let temperature = if matches.is_present("temperature") {
-            matches
-                .value_of("temperature")
-                .unwrap_or_default()
-                .parse::<f64>()
-                .unwrap()
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        let ph_measured: Vec<f64> = replicates.iter().map(Replicates::mean).collect();
+        let buffers = values_of_finite(&matches, "buffers");
+        let reading = if matches.is_present("mv") {
+            Reading::Millivolt
         } else {
-            25.0_f64
+            Reading::Ph
+        };
+
+        let unit = matches
+            .value_of("temperature_unit")
+            .and_then(|value| value.parse::<TemperatureUnit>().ok());
+        let temperature = match matches.value_of("temperature") {
+            Some(value) => Temperature::parse_in(value, unit.unwrap_or_default())
+                .unwrap_or_else(|err| app.error(ErrorKind::InvalidValue, err).exit()),
+            None => Temperature::default().to_unit(unit.unwrap_or_default()),
         };
+        let temperature_unit = unit.unwrap_or(temperature.unit);
+
+        let report = matches
+            .subcommand_matches("report")
+            .map(|report| ReportArgs {
+                uncertainty: report.is_present("uncertainty"),
+                reading: value_of_finite(report, "reading"),
+                sources: PartialSources {
+                    buffer: value_of_finite(report, "u_buffer"),
+                    temperature: value_of_finite(report, "u_temperature"),
+                    resolution: value_of_finite(report, "resolution"),
+                    repeatability: value_of_finite(report, "repeatability"),
+                },
+                monte_carlo: report
+                    .value_of("monte_carlo")
+                    .and_then(|value| value.parse().ok()),
+                seed: report
+                    .value_of("seed")
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(1),
+                temperature_unit: report
+                    .value_of("temperature_unit")
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or_default(),
+            });
+
+        let one_point = matches.is_present("one_point");
+        if report.is_none() && one_point && ph_measured.len() != 1 {
+            app.error(
+                ErrorKind::WrongNumberOfValues,
+                format!(
+                    "--one-point adjusts the offset to a single buffer, but {} pH values were measured",
+                    ph_measured.len()
+                ),
+            )
+            .exit();
+        }
+        if report.is_none() && !one_point && ph_measured.len() < 2 {
+            app.error(
+                ErrorKind::TooFewValues,
+                "a calibration needs at least 2 buffers, or --one-point to adjust the offset to one",
+            )
+            .exit();
+        }
+        if report.is_none() && ph_measured.len() != buffers.len() {
+            app.error(
+                ErrorKind::WrongNumberOfValues,
+                format!(
+                    "{} pH values were measured, but {} buffers were given",
+                    ph_measured.len(),
+                    buffers.len()
+                ),
+            )
+            .exit();
+        }
+
+        let buffer_set = matches
+            .value_of("buffer_set")
+            .and_then(|value| value.parse::<BufferSet>().ok())
+            .unwrap_or_default();
+        let buffers_file = matches.value_of("buffers_file").map(String::from);
 
         let store = matches.is_present("store");
+        let electrode = matches.value_of("electrode").map(String::from);
+        let format = matches
+            .value_of("format")
+            .and_then(|value| value.parse::<Format>().ok())
+            .unwrap_or(Format::Table);
+
+        let slope_limits = values_of_finite(&matches, "slope_limits");
+        let slope_warn_limits = values_of_finite(&matches, "slope_warn_limits");
+        let defaults = AcceptanceLimits::default();
+        let limits = AcceptanceLimits {
+            slope_pass: (slope_limits[0], slope_limits[1]),
+            slope_warn: (slope_warn_limits[0], slope_warn_limits[1]),
+            offset_pass: value_of_finite(&matches, "offset_limit").unwrap_or(defaults.offset_pass),
+            offset_warn: value_of_finite(&matches, "offset_warn_limit")
+                .unwrap_or(defaults.offset_warn),
+        };
+        if let Err(err) = limits.validate() {
+            app.error(ErrorKind::InvalidValue, err.to_string()).exit();
+        }
+
+        let model = matches.value_of("model").unwrap_or("linear").to_lowercase();
+        let fit_options = FitOptions {
+            degree: match matches.occurrences_of("degree") {
+                0 => None,
+                _ => matches
+                    .value_of("degree")
+                    .and_then(|value| value.parse().ok()),
+            },
+        };
+        if !ModelRegistry::default()
+            .takes(&model, &fit_options)
+            .unwrap_or(false)
+        {
+            app.error(
+                ErrorKind::ArgumentConflict,
+                format!("--degree is not used by --model {}", model),
+            )
+            .exit();
+        }
+        let robust = matches
+            .value_of("robust")
+            .and_then(|value| value.parse::<Robust>().ok());
+        if robust.is_some() && model != "linear" {
+            app.error(
+                ErrorKind::ArgumentConflict,
+                "--robust only fits the linear model",
+            )
+            .exit();
+        }
 
         Self {
-            ph4,
-            ph10,
+            ph_measured,
+            replicates,
+            outlier_test: matches
+                .value_of("outlier_test")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(OutlierTest::Grubbs),
+            reject_outliers: matches.is_present("reject_outliers"),
+            reading,
+            model,
+            fit_options,
+            robust,
+            buffers,
+            buffer_set,
+            buffers_file,
+            interpolation: matches
+                .value_of("interpolation")
+                .and_then(|value| value.parse().ok()),
             temperature,
+            temperature_unit,
+            temperature_policy: matches
+                .value_of("temperature_policy")
+                .and_then(|value| value.parse().ok())
+                .unwrap_or_default(),
             store,
+            electrode,
+            format,
+            limits,
+            one_point,
+            report,
         }
     }
 }
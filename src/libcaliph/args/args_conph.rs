@@ -2,40 +2,109 @@
 License, v. 2.0. If a copy of the MPL was not distributed with this
 file, You can obtain one at https://mozilla.org/MPL/2.0/.
 Copyright 2021 Peter Dunne */
+//! Read in command line arguments for `conph` using clap
 
-///! Read in command line arguments for `conph` using clap
-use clap::{App, Arg};
+use super::{finite, temperature_units, value_of_finite};
+use crate::batch::Column;
+use crate::electrode::Reading;
+use crate::output::{Format, FORMAT_NAMES};
+use crate::routines::Interval;
+use crate::temperature::{Temperature, TemperatureUnit};
+use clap::{App, AppSettings, Arg, ErrorKind};
 
 /// Command line arguments struct, infile, outfile, and silent (i.e. emit to stdout)
 pub struct ConvArgs {
-    /// pH measured
-    pub ph: f64,
+    /// pH measured, if `None` the pH values are read from `input`
+    pub ph: Option<f64>,
+    /// File to read measured pH values from, `-` for stdin
+    pub input: String,
+    /// Column of a CSV input holding the measured pH values
+    pub column: Option<Column>,
+    /// CSV input has no header row
+    pub no_header: bool,
+    /// Delimiter of the CSV input
+    pub delimiter: u8,
     /// Give custom calibration values insted of reading calibration.ph
     pub custom: bool,
+    /// Quantity read from the meter by the custom calibration
+    pub reading: Reading,
 
     pub calibration: Option<[f64; 2]>,
+    /// Temperature of measurement
+    pub temperature: Option<Temperature>,
+    /// Temperature of the custom calibration
+    pub calibration_temperature: Option<Temperature>,
+    /// Unit temperatures are shown in
+    pub temperature_unit: TemperatureUnit,
+    /// Output format, if `None` a table for a single pH value and plain otherwise
+    pub format: Option<Format>,
+    /// Give the interval of each converted pH value
+    pub uncertainty: bool,
+    /// Kind of interval given
+    pub interval: Interval,
+    /// Confidence level of the interval, in %
+    pub level: f64,
 }
 
 impl ConvArgs {
     /// Parse command line arguments
     pub fn parse() -> Self {
-        let matches = App::new("conph")
+        let mut app = App::new("conph")
             .author("Peter Dunne")
             .version("0.1.5")
             .about("Corrects pH measurement with calibration")
+            .setting(AppSettings::AllowNegativeNumbers)
             .arg(
                 Arg::new("ph")
-                    .help("pH measured")
+                    .help("pH, or potential in mV, measured, if absent the values are read from the input")
                     .index(1)
-                    .required(true)
-                    .takes_value(true),
+                    .takes_value(true)
+                    .validator(finite),
+            )
+            .arg(
+                Arg::new("input")
+                    .short('i')
+                    .long("input")
+                    .help("File of measured pH values, one per line or CSV, - for stdin [default: -]")
+                    .takes_value(true)
+                    .conflicts_with("ph"),
+            )
+            .arg(
+                Arg::new("column")
+                    .long("column")
+                    .help("Name, or position counting from 1, of the CSV column of measured pH values")
+                    .takes_value(true)
+                    .conflicts_with("ph"),
+            )
+            .arg(
+                Arg::new("no_header")
+                    .long("no-header")
+                    .help("CSV input has no header row")
+                    .requires("column"),
+            )
+            .arg(
+                Arg::new("delimiter")
+                    .short('d')
+                    .long("delimiter")
+                    .help("Delimiter of the CSV input, a single character or 'tab' [default: ,]")
+                    .takes_value(true)
+                    .requires("column"),
             )
             .arg(
                 Arg::new("temperature")
-                    .help("Temperature of measurement")
+                    .help("Temperature of measurement, in the unit of --temperature-unit unless followed by one, e.g. 77F")
                     .short('t')
                     .long("temperature")
-                    .takes_value(true),
+                    .takes_value(true)
+                    .allow_hyphen_values(true),
+            )
+            .arg(
+                Arg::new("temperature_unit")
+                    .long("temperature-unit")
+                    .help("Unit of the temperatures given and shown [default: that of --temperature, or celsius]")
+                    .takes_value(true)
+                    .possible_values(temperature_units())
+                    .ignore_case(true),
             )
             .arg(
                 Arg::new("custom")
@@ -50,6 +119,7 @@ impl ConvArgs {
                     .long("slope")
                     .help("Slope")
                     .takes_value(true)
+                    .validator(finite)
                     .requires_all(&["custom", "offset"]),
             )
             .arg(
@@ -58,40 +128,149 @@ impl ConvArgs {
                     .long("offset")
                     .help("Offset")
                     .takes_value(true)
+                    .validator(finite)
                     .requires_all(&["custom", "slope"]),
             )
-            .get_matches();
+            .arg(
+                Arg::new("mv")
+                    .long("mv")
+                    .help("Custom calibration converts electrode potentials in mV")
+                    .requires("custom"),
+            )
+            .arg(
+                Arg::new("calibration_temperature")
+                    .long("calibration-temperature")
+                    .help("Temperature of the custom calibration [default: 25 ˚C]")
+                    .takes_value(true)
+                    .allow_hyphen_values(true)
+                    .requires("custom"),
+            )
+            .arg(
+                Arg::new("format")
+                    .short('f')
+                    .long("format")
+                    .help("Output format [default: table for a single pH value, plain otherwise]")
+                    .takes_value(true)
+                    .possible_values(FORMAT_NAMES)
+                    .ignore_case(true)
+                    .conflicts_with("column"),
+            )
+            .arg(
+                Arg::new("uncertainty")
+                    .short('u')
+                    .long("uncertainty")
+                    .help("Give the interval of each converted pH value, from a calibration of more than two buffers")
+                    .conflicts_with("column"),
+            )
+            .arg(
+                Arg::new("interval")
+                    .long("interval")
+                    .help("Kind of interval, of the mean pH or of the single sample [default: prediction]")
+                    .takes_value(true)
+                    .possible_values(["confidence", "prediction"])
+                    .ignore_case(true)
+                    .requires("uncertainty"),
+            )
+            .arg(
+                Arg::new("level")
+                    .long("level")
+                    .help("Confidence level of the interval, in % [default: 95]")
+                    .takes_value(true)
+                    .validator(finite)
+                    .requires("uncertainty"),
+            );
+        let matches = app.get_matches_mut();
 
-        let ph = matches
-            .value_of("ph")
-            .unwrap_or_default()
-            .parse::<f64>()
-            .unwrap();
+        let ph = value_of_finite(&matches, "ph");
 
-        let custom = matches.is_present("custom");
+        let input = matches.value_of("input").unwrap_or("-").to_string();
+
+        let column = match matches.value_of("column").map(str::parse::<Column>) {
+            Some(Ok(column)) => Some(column),
+            Some(Err(err)) => app.error(ErrorKind::InvalidValue, err).exit(),
+            None => None,
+        };
 
-        let calibration = if custom {
-            let slope = matches
-                .value_of("slope")
-                .unwrap_or_default()
-                .parse::<f64>()
-                .unwrap();
+        let no_header = matches.is_present("no_header");
 
-            let offset = matches
-                .value_of("offset")
-                .unwrap_or_default()
-                .parse::<f64>()
-                .unwrap();
+        let delimiter = match matches.value_of("delimiter").unwrap_or(",") {
+            "tab" | "\\t" => b'\t',
+            value if value.len() == 1 => value.as_bytes()[0],
+            value => app
+                .error(
+                    ErrorKind::InvalidValue,
+                    format!("Delimiter must be a single character, not {:?}", value),
+                )
+                .exit(),
+        };
 
-            Some([slope, offset])
+        let custom = matches.is_present("custom");
+        let reading = if matches.is_present("mv") {
+            Reading::Millivolt
         } else {
-            None
+            Reading::Ph
         };
 
+        let calibration = match (
+            value_of_finite(&matches, "slope"),
+            value_of_finite(&matches, "offset"),
+        ) {
+            (Some(slope), Some(offset)) if custom => Some([slope, offset]),
+            _ => None,
+        };
+
+        let unit = matches
+            .value_of("temperature_unit")
+            .and_then(|value| value.parse::<TemperatureUnit>().ok());
+        let mut temperature_of = |name: &str| {
+            matches.value_of(name).map(|value| {
+                Temperature::parse_in(value, unit.unwrap_or_default())
+                    .unwrap_or_else(|err| app.error(ErrorKind::InvalidValue, err).exit())
+            })
+        };
+        let temperature = temperature_of("temperature");
+        let calibration_temperature = temperature_of("calibration_temperature");
+        let temperature_unit = unit
+            .or_else(|| temperature.or(calibration_temperature).map(|t| t.unit))
+            .unwrap_or_default();
+
+        let format = matches
+            .value_of("format")
+            .and_then(|value| value.parse::<Format>().ok());
+
+        let uncertainty = matches.is_present("uncertainty");
+        let interval = matches
+            .value_of("interval")
+            .and_then(|value| value.parse::<Interval>().ok())
+            .unwrap_or_default();
+        let level = value_of_finite(&matches, "level").unwrap_or(95.0);
+        if !(level > 0.0 && level < 100.0) {
+            app.error(
+                ErrorKind::InvalidValue,
+                format!(
+                    "The confidence level must lie between 0 and 100 %, not {}",
+                    level
+                ),
+            )
+            .exit();
+        }
+
         Self {
             ph,
+            input,
+            column,
+            no_header,
+            delimiter,
             custom,
+            reading,
             calibration,
+            temperature,
+            calibration_temperature,
+            temperature_unit,
+            format,
+            uncertainty,
+            interval,
+            level,
         }
     }
 }
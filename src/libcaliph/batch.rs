@@ -0,0 +1,261 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Batch Module
+//! Provides the conversion of many measured pH values at once, read either as one value per line,
+//! or from a column of a CSV file.
+
+use super::error::{Error, Result};
+use super::model::PhModel;
+use super::routines::ph_convert_all;
+use std::io::{BufRead, Read, Write};
+use std::str::FromStr;
+
+/// Column of a CSV file holding the measured pH values
+#[derive(Clone, Debug, PartialEq)]
+pub enum Column {
+    /// Column with this name in the header
+    Name(String),
+    /// Column at this position, counting from 0
+    Index(usize),
+}
+
+/// Parses a column from its name, or from its position counting from 1
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(0) => Err("Columns are counted from 1".to_string()),
+            Ok(position) => Ok(Column::Index(position - 1)),
+            Err(_) => Ok(Column::Name(s.to_string())),
+        }
+    }
+}
+
+/// Reads measured pH values given one per line, skipping blank lines
+pub fn read_lines<R: BufRead>(reader: R) -> Result<Vec<f64>> {
+    let mut ph_measured = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        ph_measured.push(parse_value(line, Some(number + 1))?);
+    }
+    Ok(ph_measured)
+}
+
+/// Converts measured pH values given one per line, writing each converted value on its own line.
+///
+/// Blank lines are skipped. Returns the number of values converted.
+pub fn convert_lines<R: BufRead, W: Write, M: PhModel + ?Sized>(
+    reader: R,
+    mut writer: W,
+    calibration: &M,
+) -> Result<usize> {
+    let ph_measured = read_lines(reader)?;
+
+    let ph_correct = ph_convert_all(&ph_measured, calibration);
+    for ph in ph_correct.iter() {
+        writeln!(writer, "{:.4}", ph)?;
+    }
+
+    Ok(ph_correct.len())
+}
+
+/// Parses a finite measured pH value
+fn parse_value(value: &str, line: Option<usize>) -> Result<f64> {
+    match value.trim().parse::<f64>() {
+        Ok(ph) if ph.is_finite() => Ok(ph),
+        _ => Err(Error::InvalidInput {
+            line,
+            message: format!("{:?} is not a finite number", value),
+        }),
+    }
+}
+
+/// Converts the measured pH values in a column of a CSV file.
+///
+/// All the columns are written back out untouched, with the converted values appended as a new
+/// last column, named `<column>_calibrated` if the file has a header. Returns the number of values converted.
+pub fn convert_csv<R: Read, W: Write, M: PhModel + ?Sized>(
+    reader: R,
+    writer: W,
+    column: &Column,
+    has_headers: bool,
+    delimiter: u8,
+    calibration: &M,
+) -> Result<usize> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .has_headers(has_headers)
+        .delimiter(delimiter)
+        .from_reader(reader);
+    let mut csv_writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(writer);
+
+    let headers = if has_headers {
+        Some(csv_reader.headers()?.clone())
+    } else {
+        None
+    };
+
+    let index = match (column, &headers) {
+        (Column::Index(index), _) => *index,
+        (Column::Name(name), Some(headers)) => headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| Error::InvalidInput {
+                line: Some(1),
+                message: format!("no column named {:?}", name),
+            })?,
+        (Column::Name(name), None) => {
+            return Err(Error::InvalidInput {
+                line: None,
+                message: format!(
+                    "column {:?} can only be found in a file with a header",
+                    name
+                ),
+            })
+        }
+    };
+
+    let records = csv_reader
+        .records()
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let ph_measured = records
+        .iter()
+        .map(|record| {
+            let line = record.position().map(|position| position.line() as usize);
+            let cell = record.get(index).ok_or_else(|| Error::InvalidInput {
+                line,
+                message: format!("no column {}", index + 1),
+            })?;
+            parse_value(cell, line)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let ph_correct = ph_convert_all(&ph_measured, calibration);
+
+    if let Some(headers) = headers {
+        let name = format!("{}_calibrated", &headers[index]);
+        csv_writer.write_record(headers.iter().chain(std::iter::once(name.as_str())))?;
+    }
+    for (record, ph) in records.iter().zip(ph_correct.iter()) {
+        let value = format!("{:.4}", ph);
+        csv_writer.write_record(record.iter().chain(std::iter::once(value.as_str())))?;
+    }
+    csv_writer.flush()?;
+
+    Ok(ph_correct.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert_csv, convert_lines, Column};
+    use crate::error::Error;
+    use crate::routines::Calibration;
+
+    #[test]
+    fn test_column_from_str() {
+        assert_eq!("2".parse::<Column>().unwrap(), Column::Index(1));
+        assert_eq!(
+            "ph".parse::<Column>().unwrap(),
+            Column::Name("ph".to_string())
+        );
+        assert!("0".parse::<Column>().is_err());
+    }
+
+    #[test]
+    fn test_convert_lines() {
+        let input = "3.5\n\n4.0\n";
+        let mut output = Vec::new();
+        let count = convert_lines(
+            input.as_bytes(),
+            &mut output,
+            &Calibration::new(2.0, 0.5, None, None),
+        )
+        .unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(String::from_utf8(output).unwrap(), "7.5000\n8.5000\n");
+    }
+
+    #[test]
+    fn test_convert_lines_invalid() {
+        let input = "3.5\nseven\n";
+        let err = convert_lines(
+            input.as_bytes(),
+            Vec::new(),
+            &Calibration::new(2.0, 0.5, None, None),
+        )
+        .unwrap_err();
+
+        assert!(matches!(err, Error::InvalidInput { line: Some(2), .. }));
+
+        let input = "3.5\nNaN\n";
+        assert!(convert_lines(
+            input.as_bytes(),
+            Vec::new(),
+            &Calibration::new(2.0, 0.5, None, None)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_convert_csv() {
+        let input = "sample,ph,note\nA,3.5,\"first, filtered\"\nB,4.0,\n";
+        let mut output = Vec::new();
+        let column = Column::Name("ph".to_string());
+        let count = convert_csv(
+            input.as_bytes(),
+            &mut output,
+            &column,
+            true,
+            b',',
+            &Calibration::new(2.0, 0.5, None, None),
+        )
+        .unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "sample,ph,note,ph_calibrated\nA,3.5,\"first, filtered\",7.5000\nB,4.0,,8.5000\n"
+        );
+    }
+
+    #[test]
+    fn test_convert_csv_no_header() {
+        let input = "A\t3.5\nB\t4.0\n";
+        let mut output = Vec::new();
+        let column = Column::Index(1);
+        convert_csv(
+            input.as_bytes(),
+            &mut output,
+            &column,
+            false,
+            b'\t',
+            &Calibration::new(2.0, 0.5, None, None),
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "A\t3.5\t7.5000\nB\t4.0\t8.5000\n"
+        );
+
+        let column = Column::Name("ph".to_string());
+        assert!(convert_csv(
+            input.as_bytes(),
+            Vec::new(),
+            &column,
+            false,
+            b'\t',
+            &Calibration::new(2.0, 0.5, None, None)
+        )
+        .is_err());
+    }
+}
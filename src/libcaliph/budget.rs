@@ -0,0 +1,658 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Budget Module
+//! Provides the measurement uncertainty budget of a calibration, following the GUM
+//! (JCGM 100, Guide to the expression of uncertainty in measurement).
+//!
+//! The calibration, and a pH converted with it, depend on these uncorrelated input quantities:
+//!
+//! | Source        | Input                                           | Distribution |
+//! |---------------|-------------------------------------------------|--------------|
+//! | buffer        | certified pH of each buffer                     | normal       |
+//! | temperature   | temperature of the buffers, through their table | normal       |
+//! | resolution    | each reading, to the last digit of the meter    | rectangular  |
+//! | repeatability | each reading, as a repeated reading scatters    | normal       |
+//!
+//! The analytic budget propagates the standard uncertainty $`u(x_i)`$ of each input with its sensitivity
+//! coefficient $`c_i = \partial y / \partial x_i`$, giving the combined standard uncertainty
+//! $`u_c(y) = \sqrt{\sum_i c_i^2 u^2(x_i)}`$, and the expanded uncertainty $`U = k u_c(y)`$ with $`k = 2`$.
+//! The Monte Carlo method (JCGM 101) instead propagates the distributions themselves, from a seeded random
+//! number generator so that each result can be reproduced.
+//!
+//! Each evaluation fits the calibration again to the perturbed inputs as it was made: the line by least squares,
+//! weighted or robustly, and the pH through its model if other than the line.
+
+use super::buffers::BufferSet;
+use super::electrode::Reading;
+use super::error::{Error, Result};
+use super::fit::{self, Robust};
+use super::model::{FitOptions, ModelRegistry};
+use super::record::CalibrationRecord;
+use super::routines::{buffer_values, interp_buffer, ph_convert, Calibration};
+use std::fmt;
+
+/// Coverage factor of the expanded uncertainty, for a coverage probability of about 95 %
+pub const COVERAGE_FACTOR: f64 = 2.0;
+
+/// Standard uncertainties of the sources of a budget
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UncertaintySources {
+    /// Standard uncertainty of the certified pH of each buffer
+    pub buffer: f64,
+    /// Standard uncertainty of the temperature, in ˚C
+    pub temperature: f64,
+    /// Resolution of the meter, the smallest step of a reading
+    pub resolution: f64,
+    /// Repeatability of the electrode, the standard deviation of repeated readings
+    pub repeatability: f64,
+}
+
+impl UncertaintySources {
+    /// Typical sources for readings of the given quantity
+    ///
+    /// Buffers certified to ±0.02 pH (k = 2) and a thermometer to ±0.2˚C, with a meter reading to 0.01 pH
+    /// or 0.1 mV, repeatable to 0.01 pH or 0.5 mV.
+    pub fn typical(reading: Reading) -> UncertaintySources {
+        let (resolution, repeatability) = match reading {
+            Reading::Ph => (0.01, 0.01),
+            Reading::Millivolt => (0.1, 0.5),
+        };
+        UncertaintySources {
+            buffer: 0.01,
+            temperature: 0.1,
+            resolution,
+            repeatability,
+        }
+    }
+}
+
+impl Default for UncertaintySources {
+    fn default() -> Self {
+        UncertaintySources::typical(Reading::Ph)
+    }
+}
+
+/// Probability distribution of an input quantity
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distribution {
+    Normal,
+    /// Uniform over an interval of half width $`\sqrt{3} u`$
+    Rectangular,
+}
+
+impl fmt::Display for Distribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Distribution::Normal => "normal",
+            Distribution::Rectangular => "rectangular",
+        };
+        f.pad(name)
+    }
+}
+
+/// Quantity whose uncertainty is evaluated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    /// Slope of the calibration
+    Slope,
+    /// Offset of the calibration
+    Offset,
+    /// pH converted from the given reading
+    Ph(f64),
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Slope => write!(f, "slope"),
+            Output::Offset => write!(f, "offset"),
+            Output::Ph(reading) => write!(f, "pH converted from {}", reading),
+        }
+    }
+}
+
+/// Input quantity perturbed by a source
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Target {
+    Buffer(usize),
+    Temperature,
+    Reading(usize),
+    /// Reading converted to pH
+    Sample,
+}
+
+/// Contribution of an input quantity to the uncertainty of the output
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contribution {
+    /// Name of the input quantity
+    pub source: String,
+    pub distribution: Distribution,
+    /// Standard uncertainty of the input, $`u(x_i)`$
+    pub standard_uncertainty: f64,
+    /// Sensitivity coefficient, $`c_i`$
+    pub sensitivity: f64,
+    /// Contribution to the standard uncertainty of the output, $`|c_i| u(x_i)`$
+    pub contribution: f64,
+}
+
+/// Analytic uncertainty budget of an output quantity
+#[derive(Clone, Debug, PartialEq)]
+pub struct Budget {
+    pub output: Output,
+    /// Value of the output
+    pub value: f64,
+    pub contributions: Vec<Contribution>,
+    /// Combined standard uncertainty, $`u_c(y)`$
+    pub combined: f64,
+    /// Coverage factor, $`k`$
+    pub coverage_factor: f64,
+    /// Expanded uncertainty, $`U = k u_c(y)`$
+    pub expanded: f64,
+}
+
+/// Monte Carlo evaluation of the uncertainty of an output quantity
+#[derive(Clone, Debug, PartialEq)]
+pub struct MonteCarlo {
+    pub output: Output,
+    /// Number of samples drawn
+    pub samples: usize,
+    /// Seed of the random number generator
+    pub seed: u64,
+    /// Mean of the output
+    pub mean: f64,
+    /// Standard deviation of the output, its standard uncertainty
+    pub standard_uncertainty: f64,
+    /// Probabilistically symmetric 95 % coverage interval of the output
+    pub interval: (f64, f64),
+}
+
+/// Fit of the line of a calibration
+#[derive(Clone, Debug, PartialEq)]
+pub enum LineFit {
+    /// Least squares
+    LeastSquares,
+    /// Weighted least squares, keeping the weight of each reading
+    Weighted(Vec<f64>),
+    /// Robust estimator, weighting the readings again at each evaluation
+    Robust(Robust),
+}
+
+/// Calibration whose uncertainty is evaluated
+#[derive(Clone, Debug, PartialEq)]
+pub struct BudgetModel {
+    /// Reading for each buffer
+    pub readings: Vec<f64>,
+    /// pH of each buffer at the calibration temperature
+    pub buffers: Vec<f64>,
+    /// Change of the pH of each buffer with temperature, in pH per ˚C
+    pub temperature_coefficients: Vec<f64>,
+    /// Temperature of the calibration, in ˚C
+    pub temperature: f64,
+    /// Fit of the line giving the slope and offset
+    pub line: LineFit,
+    /// Name of the model converting readings to pH, see [`crate::model`], the line if absent
+    pub model: Option<String>,
+    /// Options the model is fitted with
+    pub fit_options: FitOptions,
+}
+
+impl BudgetModel {
+    /// Model of a calibration of the readings of buffers with the given nominal pH, at `temperature`.
+    ///
+    /// Returns an error for a buffer not found in `buffer_set`.
+    pub fn new(
+        readings: &[f64],
+        ph_nominal: &[f64],
+        temperature: &f64,
+        buffer_set: &BufferSet,
+    ) -> Result<BudgetModel> {
+        if readings.len() != ph_nominal.len() {
+            return Err(Error::LengthMismatch {
+                expected: readings.len(),
+                found: ph_nominal.len(),
+            });
+        }
+        let buffers = buffer_values(ph_nominal, temperature, buffer_set)?;
+        let temperature_coefficients = ph_nominal
+            .iter()
+            .map(|nominal| temperature_coefficient(buffer_set, nominal, temperature))
+            .collect();
+        Ok(BudgetModel {
+            readings: readings.to_vec(),
+            buffers,
+            temperature_coefficients,
+            temperature: *temperature,
+            line: LineFit::LeastSquares,
+            model: None,
+            fit_options: FitOptions::default(),
+        })
+    }
+
+    /// Model of a stored calibration, with the temperature dependence of its buffers taken from `buffer_set`.
+    ///
+    /// The calibration is fitted as it was made, with its weights, robust estimator and model.
+    pub fn from_record(record: &CalibrationRecord, buffer_set: &BufferSet) -> Result<BudgetModel> {
+        let temperature = record.temperature.unwrap_or(25.0);
+        if record.readings.len() != record.buffers.len() || record.readings.len() < 2 {
+            return Err(Error::MalformedCalibration(
+                "the readings and buffers of the calibration are not recorded".to_string(),
+            ));
+        }
+        let temperature_coefficients = record
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| {
+                let nominal = record.nominal.get(index).unwrap_or(buffer);
+                temperature_coefficient(buffer_set, nominal, &temperature)
+            })
+            .collect();
+        let line = match (record.robust, record.weights.is_empty()) {
+            (Some(method), _) => LineFit::Robust(method),
+            (None, false) => LineFit::Weighted(record.weights.clone()),
+            (None, true) => LineFit::LeastSquares,
+        };
+        let model = match &record.model {
+            Some(model) => Some(ModelRegistry::default().load(model)?),
+            None => None,
+        };
+        Ok(BudgetModel {
+            readings: record.readings.clone(),
+            buffers: record.buffers.clone(),
+            temperature_coefficients,
+            temperature,
+            line,
+            fit_options: model
+                .as_ref()
+                .map(|model| model.options())
+                .unwrap_or_default(),
+            model: model.map(|model| model.name().to_string()),
+        })
+    }
+
+    /// Analytic uncertainty budget of the output
+    pub fn budget(&self, sources: &UncertaintySources, output: Output) -> Result<Budget> {
+        let inputs = self.inputs(sources, output);
+        let value = self.evaluate(&[], output)?;
+
+        let mut perturbation = Vec::with_capacity(inputs.len());
+        let contributions = inputs
+            .iter()
+            .map(|input| {
+                // Central difference over the standard uncertainty of the input
+                let step = input.standard_uncertainty;
+                let sensitivity = if step > 0.0 {
+                    perturbation.clear();
+                    perturbation.push((input.target, step));
+                    let above = self.evaluate(&perturbation, output)?;
+                    perturbation[0].1 = -step;
+                    let below = self.evaluate(&perturbation, output)?;
+                    (above - below) / (2.0 * step)
+                } else {
+                    0.0
+                };
+                Ok(Contribution {
+                    source: input.source.clone(),
+                    distribution: input.distribution,
+                    standard_uncertainty: input.standard_uncertainty,
+                    sensitivity,
+                    contribution: (sensitivity * input.standard_uncertainty).abs(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let combined = contributions
+            .iter()
+            .map(|contribution| contribution.contribution.powi(2))
+            .sum::<f64>()
+            .sqrt();
+        Ok(Budget {
+            output,
+            value,
+            contributions,
+            combined,
+            coverage_factor: COVERAGE_FACTOR,
+            expanded: COVERAGE_FACTOR * combined,
+        })
+    }
+
+    /// Monte Carlo evaluation of the uncertainty of the output, from `samples` draws of the inputs
+    pub fn monte_carlo(
+        &self,
+        sources: &UncertaintySources,
+        output: Output,
+        samples: usize,
+        seed: u64,
+    ) -> Result<MonteCarlo> {
+        if samples < 2 {
+            return Err(Error::InvalidInput {
+                line: None,
+                message: "the Monte Carlo method needs at least 2 samples".to_string(),
+            });
+        }
+        let inputs = self.inputs(sources, output);
+        let mut rng = SplitMix64::new(seed);
+
+        let mut perturbation = Vec::with_capacity(inputs.len());
+        let mut values = Vec::with_capacity(samples);
+        for _ in 0..samples {
+            perturbation.clear();
+            perturbation.extend(inputs.iter().map(|input| {
+                let draw = match input.distribution {
+                    Distribution::Normal => rng.next_normal(),
+                    Distribution::Rectangular => (2.0 * rng.next_f64() - 1.0) * 3f64.sqrt(),
+                };
+                (input.target, draw * input.standard_uncertainty)
+            }));
+            values.push(self.evaluate(&perturbation, output)?);
+        }
+
+        let mean = values.iter().sum::<f64>() / samples as f64;
+        let variance = values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / (samples - 1) as f64;
+        values.sort_by(|a, b| a.total_cmp(b));
+        let quantile = |p: f64| values[((p * samples as f64) as usize).min(samples - 1)];
+        Ok(MonteCarlo {
+            output,
+            samples,
+            seed,
+            mean,
+            standard_uncertainty: variance.sqrt(),
+            interval: (quantile(0.025), quantile(0.975)),
+        })
+    }
+
+    /// Input quantities of the output, with their standard uncertainties
+    fn inputs(&self, sources: &UncertaintySources, output: Output) -> Vec<Input> {
+        let resolution = sources.resolution / (2.0 * 3f64.sqrt());
+        let mut inputs: Vec<Input> = self
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(index, buffer)| Input {
+                source: format!("buffer {:.2}", buffer),
+                target: Target::Buffer(index),
+                distribution: Distribution::Normal,
+                standard_uncertainty: sources.buffer,
+            })
+            .collect();
+        inputs.push(Input {
+            source: "temperature".to_string(),
+            target: Target::Temperature,
+            distribution: Distribution::Normal,
+            standard_uncertainty: sources.temperature,
+        });
+
+        let mut readings: Vec<(String, Target)> = self
+            .readings
+            .iter()
+            .enumerate()
+            .map(|(index, reading)| (format!("reading {}", reading), Target::Reading(index)))
+            .collect();
+        if let Output::Ph(_) = output {
+            readings.push(("sample".to_string(), Target::Sample));
+        }
+        for (name, target) in readings {
+            inputs.push(Input {
+                source: format!("{} resolution", name),
+                target,
+                distribution: Distribution::Rectangular,
+                standard_uncertainty: resolution,
+            });
+            inputs.push(Input {
+                source: format!("{} repeatability", name),
+                target,
+                distribution: Distribution::Normal,
+                standard_uncertainty: sources.repeatability,
+            });
+        }
+        inputs
+    }
+
+    /// Value of the output with the inputs perturbed
+    fn evaluate(&self, perturbation: &[(Target, f64)], output: Output) -> Result<f64> {
+        let mut readings = self.readings.clone();
+        let mut buffers = self.buffers.clone();
+        let mut sample = match output {
+            Output::Ph(reading) => reading,
+            _ => 0.0,
+        };
+        for (target, delta) in perturbation {
+            match target {
+                Target::Buffer(index) => buffers[*index] += delta,
+                Target::Temperature => {
+                    for (buffer, coefficient) in
+                        buffers.iter_mut().zip(self.temperature_coefficients.iter())
+                    {
+                        *buffer += coefficient * delta;
+                    }
+                }
+                Target::Reading(index) => readings[*index] += delta,
+                Target::Sample => sample += delta,
+            }
+        }
+
+        if let (Output::Ph(_), Some(model)) = (output, &self.model) {
+            let model =
+                ModelRegistry::default().fit_with(model, &readings, &buffers, &self.fit_options)?;
+            return Ok(model.convert(&sample));
+        }
+        let line = match &self.line {
+            LineFit::LeastSquares => fit::fit(&readings, &buffers)?,
+            LineFit::Weighted(weights) => fit::fit_weighted(&readings, &buffers, weights)?,
+            LineFit::Robust(method) => fit::robust_fit(&readings, &buffers, *method)?.model,
+        };
+        Ok(match output {
+            Output::Slope => line[0],
+            Output::Offset => line[1],
+            Output::Ph(_) => ph_convert(&sample, &Calibration::new(line[0], line[1], None, None)),
+        })
+    }
+}
+
+/// Input quantity of a budget
+struct Input {
+    source: String,
+    target: Target,
+    distribution: Distribution,
+    standard_uncertainty: f64,
+}
+
+/// Change of the pH of a buffer with temperature, in pH per ˚C, zero if the buffer is not in the set
+fn temperature_coefficient(buffer_set: &BufferSet, nominal: &f64, temperature: &f64) -> f64 {
+    const STEP: f64 = 0.5;
+    let buffer = match buffer_set.find(nominal) {
+        Some(buffer) => buffer,
+        None => return 0.0,
+    };
+    // One sided at the ends of the table
+    let (low, high) = buffer.temperature_range();
+    let below = (temperature - STEP).max(low);
+    let above = (temperature + STEP).min(high);
+    match (interp_buffer(buffer, &below), interp_buffer(buffer, &above)) {
+        (Some(ph_below), Some(ph_above)) if above > below => {
+            (ph_above - ph_below) / (above - below)
+        }
+        _ => 0.0,
+    }
+}
+
+/// SplitMix64 generator, small and fully reproducible from its seed
+struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Standard normal, by the Box-Muller transform
+    fn next_normal(&mut self) -> f64 {
+        let u1 = 1.0 - self.next_f64();
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{BudgetModel, LineFit, Output, UncertaintySources};
+    use crate::buffers::BufferSet;
+    use crate::fit::{self, Robust};
+    use crate::model::{PhModel, PolynomialCalibration};
+    use crate::record::CalibrationRecord;
+    use crate::routines::Calibration;
+
+    fn model() -> BudgetModel {
+        BudgetModel::new(
+            &[3.97, 10.2],
+            &[4.01, 10.01],
+            &22.3,
+            &BufferSet::technical(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_budget_buffers_only() {
+        let sources = UncertaintySources {
+            buffer: 0.01,
+            temperature: 0.0,
+            resolution: 0.0,
+            repeatability: 0.0,
+        };
+        let budget = model().budget(&sources, Output::Ph(7.0)).unwrap();
+
+        // Each buffer contributes in proportion to how close the sample is to it
+        let contributions: Vec<f64> = budget
+            .contributions
+            .iter()
+            .map(|c| c.contribution)
+            .collect();
+        assert!(approx_eq!(
+            f64,
+            contributions[0] + contributions[1],
+            0.01,
+            epsilon = 1e-9
+        ));
+        assert!(approx_eq!(
+            f64,
+            budget.combined,
+            (contributions[0].powi(2) + contributions[1].powi(2)).sqrt(),
+            epsilon = 1e-12
+        ));
+        assert!(approx_eq!(f64, budget.expanded, 2.0 * budget.combined));
+    }
+
+    #[test]
+    fn test_budget_sources() {
+        let budget = model()
+            .budget(&UncertaintySources::default(), Output::Ph(7.0))
+            .unwrap();
+
+        // 2 buffers, temperature, and resolution and repeatability of 2 readings and the sample
+        assert_eq!(budget.contributions.len(), 9);
+        // The 4.01 buffer changes with temperature
+        let temperature = &budget.contributions[2];
+        assert!(temperature.sensitivity.abs() > 0.0);
+
+        let slope = model()
+            .budget(&UncertaintySources::default(), Output::Slope)
+            .unwrap();
+        assert_eq!(slope.contributions.len(), 7);
+    }
+
+    #[test]
+    fn test_monte_carlo() {
+        let sources = UncertaintySources::default();
+        let budget = model().budget(&sources, Output::Ph(7.0)).unwrap();
+        let monte_carlo = model()
+            .monte_carlo(&sources, Output::Ph(7.0), 20_000, 42)
+            .unwrap();
+
+        assert!(approx_eq!(
+            f64,
+            monte_carlo.mean,
+            budget.value,
+            epsilon = 1e-3
+        ));
+        assert!(approx_eq!(
+            f64,
+            monte_carlo.standard_uncertainty,
+            budget.combined,
+            epsilon = 0.05 * budget.combined
+        ));
+        assert!(monte_carlo.interval.0 < budget.value && budget.value < monte_carlo.interval.1);
+
+        // Reproducible from the seed
+        let again = model()
+            .monte_carlo(&sources, Output::Ph(7.0), 20_000, 42)
+            .unwrap();
+        assert_eq!(monte_carlo, again);
+    }
+
+    #[test]
+    fn test_budget_from_record() {
+        let readings = [1.5, 3.8, 6.6, 8.9, 9.8];
+        let buffers = [1.679, 4.005, 6.865, 9.18, 10.012];
+        let weights = [1.0, 2.0, 1.0, 0.5, 0.5];
+        let calibration = Calibration::new(1.0, 0.0, None, None);
+        let record = CalibrationRecord::new(&calibration, 25.0, &readings, &buffers);
+        let nist = BufferSet::nist();
+        let sources = UncertaintySources::default();
+
+        // The slope of a weighted calibration is that of the weighted line
+        let weighted =
+            BudgetModel::from_record(&record.with_weights(weights.to_vec()), &nist).unwrap();
+        assert_eq!(weighted.line, LineFit::Weighted(weights.to_vec()));
+        let slope = weighted.budget(&sources, Output::Slope).unwrap();
+        let line = fit::fit_weighted(&readings, &buffers, &weights).unwrap();
+        assert!(approx_eq!(f64, slope.value, line[0], epsilon = 1e-12));
+
+        // A robust calibration is fitted robustly
+        let robust = fit::robust_fit(&readings, &buffers, Robust::TheilSen).unwrap();
+        let robust_model =
+            BudgetModel::from_record(&record.with_robust(Some(&robust)), &nist).unwrap();
+        let offset = robust_model.budget(&sources, Output::Offset).unwrap();
+        assert!(approx_eq!(
+            f64,
+            offset.value,
+            robust.model[1],
+            epsilon = 1e-12
+        ));
+
+        // The pH is converted through the stored model, fitted again with its degree
+        let cubic = PolynomialCalibration::fit_degree(&readings, &buffers, 3).unwrap();
+        let record = record.with_model(Some(cubic.to_record().unwrap()));
+        let polynomial = BudgetModel::from_record(&record, &nist).unwrap();
+        let ph = polynomial.budget(&sources, Output::Ph(5.0)).unwrap();
+        assert!(approx_eq!(
+            f64,
+            ph.value,
+            cubic.convert(&5.0),
+            epsilon = 1e-9
+        ));
+        assert!(ph.combined > 0.0);
+    }
+}
@@ -0,0 +1,993 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Buffers Module
+//! Provides the library of pH buffer solutions, and their temperature dependence.
+//!
+//! Three buffer sets are built in:
+//!
+//! - `technical`, the common 4.01, 7.00 and 10.01 technical buffers
+//! - `nist`, the DIN 19266 / NIST primary standards 1.68, 4.01, 6.86, 9.18, 10.01 and 12.45
+//! - `nist-equations`, the same standards described by equations fitted to their tables, within 0.002 pH
+//!
+//! The pH of a buffer at a temperature is given by its [`BufferCurve`], either a table interpolated between its points
+//! or a closed form equation, $`pH = A/T + B + C T + D T^2`$ with $`T`$ in kelvin.
+//!
+//! Custom buffers, e.g. from lot specific certificates, can be loaded from a TOML file:
+//!
+//! ```toml
+//! name = "lot 2104"
+//!
+//! [[buffer]]
+//! name = "phthalate"
+//! nominal = 4.01
+//! interpolation = "linear"
+//! temperature = [10.0, 20.0, 25.0, 30.0, 40.0]
+//! ph = [3.998, 4.001, 4.006, 4.012, 4.028]
+//! ```
+//!
+//! A buffer can instead be described by the coefficients of its equation, and the temperatures in ˚C it is valid for:
+//!
+//! ```toml
+//! [[buffer]]
+//! name = "phosphate"
+//! nominal = 6.86
+//! equation = { a = 3453.25, b = -20.9993, c = 0.0731207, d = -6.20826e-5, range = [0.0, 95.0] }
+//! ```
+//!
+//! The `interpolation` key is optional, and may be `linear` (the default), `cosine`, `catmull-rom`, `monotone-cubic`
+//! or `akima`. The cubic interpolations follow a curved table more closely than straight lines between its points,
+//! e.g. where the pH 10.01 buffer turns around near 80˚C. Of those, `monotone-cubic` never overshoots the table, and
+//! `akima` is least disturbed by a single odd point.
+
+use super::error::{Error, Result};
+use super::routines::ZERO_CELSIUS;
+use super::{
+    PH1001_DIN_EQUATION, PH1245_DIN_EQUATION, PH168_DIN_EQUATION, PH401_DIN_EQUATION,
+    PH686_DIN_EQUATION, PH918_DIN_EQUATION,
+};
+use super::{
+    PH1001_DIN_STATIC, PH1245_DIN_STATIC, PH168_DIN_STATIC, PH401_DIN_STATIC, PH686_DIN_STATIC,
+    PH918_DIN_STATIC, TEMP_DIN_STATIC,
+};
+use super::{PH10_STATIC, PH4_STATIC, PH7_STATIC, TEMP_STATIC};
+use float_cmp::{ApproxEq, F64Margin};
+use serde::{Deserialize, Serialize};
+use splines::{Interpolation, Key, Spline};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Names of the built in buffer sets
+pub const BUFFER_SET_NAMES: [&str; 4] = ["technical", "nist", "din19266", "nist-equations"];
+
+/// Names of the interpolations of buffer tables
+pub const INTERPOLATION_NAMES: [&str; 5] =
+    ["linear", "cosine", "catmull-rom", "monotone-cubic", "akima"];
+
+/// Interpolation used between the points of a buffer table
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum InterpolationMethod {
+    #[default]
+    Linear,
+    Cosine,
+    /// Catmull-Rom spline, straight in the first and last interval of a table, which have no outer neighbour
+    CatmullRom,
+    /// Piecewise cubic Hermite spline with Fritsch-Carlson tangents, which is monotone between the table points
+    MonotoneCubic,
+    /// Akima spline, whose tangents are weighted away from the steeper neighbouring intervals
+    Akima,
+}
+
+impl InterpolationMethod {
+    /// All interpolations, in the order of [`INTERPOLATION_NAMES`]
+    pub const ALL: [InterpolationMethod; 5] = [
+        InterpolationMethod::Linear,
+        InterpolationMethod::Cosine,
+        InterpolationMethod::CatmullRom,
+        InterpolationMethod::MonotoneCubic,
+        InterpolationMethod::Akima,
+    ];
+
+    /// Fewest table points the interpolation can be sampled with
+    pub fn min_points(&self) -> usize {
+        match self {
+            InterpolationMethod::Linear
+            | InterpolationMethod::Cosine
+            | InterpolationMethod::MonotoneCubic => 2,
+            InterpolationMethod::Akima => 3,
+            InterpolationMethod::CatmullRom => 4,
+        }
+    }
+
+    /// Spline through the points of a table.
+    ///
+    /// The cubic Hermite splines are built from `splines` Bézier keys, whose control points lie a third of the
+    /// way along the tangent at each point to the neighbouring points.
+    pub fn spline(&self, temperature: &[f64], ph: &[f64]) -> Spline<f64, f64> {
+        let tangents = match self {
+            InterpolationMethod::Linear => {
+                return uniform_spline(temperature, ph, Interpolation::Linear)
+            }
+            InterpolationMethod::Cosine => {
+                return uniform_spline(temperature, ph, Interpolation::Cosine)
+            }
+            InterpolationMethod::CatmullRom => return catmull_rom_spline(temperature, ph),
+            InterpolationMethod::MonotoneCubic => monotone_tangents(temperature, ph),
+            InterpolationMethod::Akima => akima_tangents(temperature, ph),
+        };
+        let last = temperature.len().saturating_sub(1);
+        let keys = (0..temperature.len())
+            .map(|index| {
+                let step_in = if index > 0 {
+                    temperature[index] - temperature[index - 1]
+                } else {
+                    0.0
+                };
+                let step_out = if index < last {
+                    temperature[index + 1] - temperature[index]
+                } else {
+                    0.0
+                };
+                let input = ph[index] - tangents[index] * step_in / 3.0;
+                let output = ph[index] + tangents[index] * step_out / 3.0;
+                Key::new(
+                    temperature[index],
+                    ph[index],
+                    Interpolation::StrokeBezier(input, output),
+                )
+            })
+            .collect();
+        Spline::from_vec(keys)
+    }
+}
+
+impl fmt::Display for InterpolationMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            InterpolationMethod::Linear => "linear",
+            InterpolationMethod::Cosine => "cosine",
+            InterpolationMethod::CatmullRom => "catmull-rom",
+            InterpolationMethod::MonotoneCubic => "monotone-cubic",
+            InterpolationMethod::Akima => "akima",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for InterpolationMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "linear" => Ok(InterpolationMethod::Linear),
+            "cosine" => Ok(InterpolationMethod::Cosine),
+            "catmull-rom" => Ok(InterpolationMethod::CatmullRom),
+            "monotone-cubic" => Ok(InterpolationMethod::MonotoneCubic),
+            "akima" => Ok(InterpolationMethod::Akima),
+            _ => Err(format!("Unknown interpolation {}", s)),
+        }
+    }
+}
+
+/// Spline with the same interpolation at every point of a table
+fn uniform_spline(
+    temperature: &[f64],
+    ph: &[f64],
+    interpolation: Interpolation<f64, f64>,
+) -> Spline<f64, f64> {
+    let keys = temperature
+        .iter()
+        .zip(ph.iter())
+        .map(|(x, y)| Key::new(*x, *y, interpolation))
+        .collect();
+    Spline::from_vec(keys)
+}
+
+/// Catmull-Rom spline through a table, with linear keys starting the first and last intervals.
+///
+/// A Catmull-Rom interval needs a point on either side of it, so `splines` cannot sample the end intervals of a
+/// table by Catmull-Rom.
+fn catmull_rom_spline(temperature: &[f64], ph: &[f64]) -> Spline<f64, f64> {
+    let last_interval = temperature.len().saturating_sub(2);
+    let keys = temperature
+        .iter()
+        .zip(ph.iter())
+        .enumerate()
+        .map(|(index, (x, y))| {
+            let interpolation = if index == 0 || index >= last_interval {
+                Interpolation::Linear
+            } else {
+                Interpolation::CatmullRom
+            };
+            Key::new(*x, *y, interpolation)
+        })
+        .collect();
+    Spline::from_vec(keys)
+}
+
+/// Slope of each interval of a table
+fn secants(x: &[f64], y: &[f64]) -> Vec<f64> {
+    x.windows(2)
+        .zip(y.windows(2))
+        .map(|(x, y)| (y[1] - y[0]) / (x[1] - x[0]))
+        .collect()
+}
+
+/// Fritsch-Carlson tangents of a monotone cubic, the weighted harmonic mean of the neighbouring secants, or zero at
+/// a turning point of the table
+fn monotone_tangents(x: &[f64], y: &[f64]) -> Vec<f64> {
+    let secants = secants(x, y);
+    let steps: Vec<f64> = x.windows(2).map(|x| x[1] - x[0]).collect();
+    if secants.len() < 2 {
+        return vec![secants.first().copied().unwrap_or(0.0); x.len()];
+    }
+    // One sided three point estimate at each end, kept to the sign and within three times the end secant
+    let end = |h0: f64, h1: f64, d0: f64, d1: f64| {
+        let tangent = ((2.0 * h0 + h1) * d0 - h0 * d1) / (h0 + h1);
+        if tangent * d0 <= 0.0 {
+            0.0
+        } else if d0 * d1 < 0.0 && tangent.abs() > 3.0 * d0.abs() {
+            3.0 * d0
+        } else {
+            tangent
+        }
+    };
+    let last = secants.len() - 1;
+    let mut tangents = Vec::with_capacity(x.len());
+    tangents.push(end(steps[0], steps[1], secants[0], secants[1]));
+    for index in 1..x.len() - 1 {
+        let (d0, d1) = (secants[index - 1], secants[index]);
+        if d0 * d1 <= 0.0 {
+            tangents.push(0.0);
+        } else {
+            let (h0, h1) = (steps[index - 1], steps[index]);
+            let (w0, w1) = (2.0 * h1 + h0, h1 + 2.0 * h0);
+            tangents.push((w0 + w1) / (w0 / d0 + w1 / d1));
+        }
+    }
+    tangents.push(end(
+        steps[last],
+        steps[last - 1],
+        secants[last],
+        secants[last - 1],
+    ));
+    tangents
+}
+
+/// Akima tangents, the mean of the neighbouring secants weighted by the change in slope on the far side of each
+fn akima_tangents(x: &[f64], y: &[f64]) -> Vec<f64> {
+    let secants = secants(x, y);
+    if secants.len() < 2 {
+        return vec![secants.first().copied().unwrap_or(0.0); x.len()];
+    }
+    // Two more secants extrapolated beyond each end of the table
+    let count = secants.len();
+    let mut extended = Vec::with_capacity(count + 4);
+    extended.push(3.0 * secants[0] - 2.0 * secants[1]);
+    extended.push(2.0 * secants[0] - secants[1]);
+    extended.extend_from_slice(&secants);
+    extended.push(2.0 * secants[count - 1] - secants[count - 2]);
+    extended.push(3.0 * secants[count - 1] - 2.0 * secants[count - 2]);
+    extended
+        .windows(4)
+        .map(|m| {
+            let (w0, w1) = ((m[3] - m[2]).abs(), (m[1] - m[0]).abs());
+            if w0 + w1 == 0.0 {
+                (m[1] + m[2]) / 2.0
+            } else {
+                (w0 * m[1] + w1 * m[2]) / (w0 + w1)
+            }
+        })
+        .collect()
+}
+
+/// Closed form temperature dependence of a buffer solution, $`pH = A/T + B + C T + D T^2`$ with $`T`$ in kelvin
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct BufferEquation {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    /// Lowest and highest temperatures the equation is valid for, in ˚C
+    pub range: (f64, f64),
+}
+
+impl BufferEquation {
+    /// Creates an equation from its coefficients $`A, B, C, D`$, valid over a range of temperatures in ˚C
+    pub fn new(coefficients: [f64; 4], range: (f64, f64)) -> BufferEquation {
+        let [a, b, c, d] = coefficients;
+        BufferEquation { a, b, c, d, range }
+    }
+
+    /// pH at the temperature in ˚C, within the range of the equation or not
+    pub fn evaluate(&self, temperature: &f64) -> f64 {
+        let kelvin = temperature + ZERO_CELSIUS;
+        self.a / kelvin + self.b + self.c * kelvin + self.d * kelvin * kelvin
+    }
+}
+
+/// pH of a buffer solution tabulated at temperature points in ˚C, interpolated between them.
+///
+/// The spline through the points is built once, when the table is created, so sampling it many times does not
+/// allocate.
+#[derive(Clone, Debug)]
+pub struct BufferTable {
+    temperature: Vec<f64>,
+    ph: Vec<f64>,
+    interpolation: InterpolationMethod,
+    spline: Spline<f64, f64>,
+}
+
+impl BufferTable {
+    /// Creates a table and builds its interpolation. Points beyond the shorter of `temperature` and `ph` are left out
+    /// of the spline, see [`Buffer::validate`].
+    pub fn new(
+        temperature: Vec<f64>,
+        ph: Vec<f64>,
+        interpolation: InterpolationMethod,
+    ) -> BufferTable {
+        let points = temperature.len().min(ph.len());
+        let spline = interpolation.spline(&temperature[..points], &ph[..points]);
+        BufferTable {
+            temperature,
+            ph,
+            interpolation,
+            spline,
+        }
+    }
+
+    /// Temperature points of the table, in ˚C
+    pub fn temperature(&self) -> &[f64] {
+        &self.temperature
+    }
+
+    /// pH at each temperature point of the table
+    pub fn ph(&self) -> &[f64] {
+        &self.ph
+    }
+
+    /// Interpolation between the points of the table
+    pub fn interpolation(&self) -> InterpolationMethod {
+        self.interpolation
+    }
+
+    /// pH at the temperature, `None` outside the table or where its interpolation cannot be sampled
+    pub fn sample(&self, temperature: &f64) -> Option<f64> {
+        // The spline can not be sampled at its last key, so that point is taken from the table
+        self.spline.sample(*temperature).or_else(|| {
+            self.temperature
+                .last()
+                .zip(self.ph.last())
+                .filter(|(last, _)| *last == temperature)
+                .map(|(_, ph)| *ph)
+        })
+    }
+}
+
+impl PartialEq for BufferTable {
+    /// Tables are equal if their points and interpolation are, the spline follows from those
+    fn eq(&self, other: &Self) -> bool {
+        self.temperature == other.temperature
+            && self.ph == other.ph
+            && self.interpolation == other.interpolation
+    }
+}
+
+/// Temperature dependence of the pH of a buffer solution, by a table or an equation.
+///
+/// A curve is built once and can be sampled any number of times without allocating, e.g. with [`BufferCurve::ph_all`]
+/// over the temperatures of a log.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BufferCurve {
+    /// pH at temperature points in ˚C, interpolated between them
+    Table(BufferTable),
+    /// Closed form equation, evaluated at any temperature in its range
+    Equation(BufferEquation),
+}
+
+impl BufferCurve {
+    /// Table of pH values at temperature points, interpolated linearly
+    pub fn table(temperature: &[f64], ph: &[f64]) -> BufferCurve {
+        BufferCurve::Table(BufferTable::new(
+            temperature.to_vec(),
+            ph.to_vec(),
+            InterpolationMethod::default(),
+        ))
+    }
+
+    /// Lowest and highest temperatures the curve is defined for
+    pub fn temperature_range(&self) -> (f64, f64) {
+        match self {
+            BufferCurve::Table(table) => (
+                table.temperature.first().copied().unwrap_or(f64::NAN),
+                table.temperature.last().copied().unwrap_or(f64::NAN),
+            ),
+            BufferCurve::Equation(equation) => equation.range,
+        }
+    }
+
+    /// pH at each of the temperatures, see [`BufferCurve::ph`]
+    pub fn ph_all(&self, temperatures: &[f64]) -> Vec<Option<f64>> {
+        self.ph_iter(temperatures.iter().copied()).collect()
+    }
+
+    /// Lazily gives the pH at each temperature of an iterator, see [`BufferCurve::ph`]
+    pub fn ph_iter<'a, I>(&'a self, temperatures: I) -> impl Iterator<Item = Option<f64>> + 'a
+    where
+        I: IntoIterator<Item = f64>,
+        I::IntoIter: 'a,
+    {
+        temperatures
+            .into_iter()
+            .map(move |temperature| self.ph(&temperature))
+    }
+
+    /// pH at the temperature, `None` outside the range of the curve, or where its interpolation cannot be sampled
+    pub fn ph(&self, temperature: &f64) -> Option<f64> {
+        match self {
+            BufferCurve::Table(table) => table.sample(temperature),
+            BufferCurve::Equation(equation) => {
+                let (low, high) = equation.range;
+                if (low..=high).contains(temperature) {
+                    Some(equation.evaluate(temperature))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// pH at the temperature, or at the end of the range nearest it if outside the range
+    pub fn clamped(&self, temperature: &f64) -> Option<f64> {
+        let (low, high) = self.temperature_range();
+        let temperature = temperature.max(low).min(high);
+        match self {
+            BufferCurve::Table(table) => table.sample(&temperature),
+            BufferCurve::Equation(equation) => Some(equation.evaluate(&temperature)),
+        }
+    }
+
+    /// pH beyond the range, on the line through the two table points nearest the temperature, or by the equation
+    pub fn extrapolated(&self, temperature: &f64) -> Option<f64> {
+        match self {
+            BufferCurve::Table(BufferTable {
+                temperature: temperature_points,
+                ph,
+                ..
+            }) => {
+                let count = temperature_points.len();
+                let index = if *temperature < self.temperature_range().0 {
+                    0
+                } else {
+                    count.checked_sub(2)?
+                };
+                let (t0, t1) = (
+                    temperature_points.get(index)?,
+                    temperature_points.get(index + 1)?,
+                );
+                let (ph0, ph1) = (ph.get(index)?, ph.get(index + 1)?);
+                Some(ph0 + (ph1 - ph0) * (temperature - t0) / (t1 - t0))
+            }
+            BufferCurve::Equation(equation) => Some(equation.evaluate(temperature)),
+        }
+    }
+
+    /// Modifies the interpolation of a table, an equation is kept as it is
+    pub fn with_interpolation(&self, interpolation: InterpolationMethod) -> BufferCurve {
+        match self {
+            BufferCurve::Table(table) => BufferCurve::Table(BufferTable::new(
+                table.temperature.clone(),
+                table.ph.clone(),
+                interpolation,
+            )),
+            BufferCurve::Equation(_) => self.clone(),
+        }
+    }
+}
+
+/// A pH buffer solution, with its temperature dependence
+#[derive(Clone, Debug, PartialEq)]
+pub struct Buffer {
+    /// Name of the buffer solution
+    pub name: String,
+    /// Nominal pH the buffer is labelled with
+    pub nominal: f64,
+    /// Temperature dependence of the pH
+    pub curve: BufferCurve,
+}
+
+impl Buffer {
+    /// Creates a buffer tabulated at temperature points, interpolated linearly
+    pub fn new(name: &str, nominal: f64, temperature: &[f64], ph: &[f64]) -> Buffer {
+        Buffer {
+            name: name.to_string(),
+            nominal,
+            curve: BufferCurve::table(temperature, ph),
+        }
+    }
+
+    /// Creates a buffer described by an equation
+    pub fn from_equation(name: &str, nominal: f64, equation: BufferEquation) -> Buffer {
+        Buffer {
+            name: name.to_string(),
+            nominal,
+            curve: BufferCurve::Equation(equation),
+        }
+    }
+
+    /// Modifies the interpolation of a tabulated buffer
+    pub fn with_interpolation(&self, interpolation: InterpolationMethod) -> Buffer {
+        Buffer {
+            curve: self.curve.with_interpolation(interpolation),
+            ..self.clone()
+        }
+    }
+
+    /// Checks the curve can be evaluated, i.e. that a table has enough points, and that
+    /// the temperatures are strictly increasing, or that an equation is finite over an increasing range above 0 K.
+    pub fn validate(&self) -> Result<()> {
+        let (temperature, ph, interpolation) = match &self.curve {
+            BufferCurve::Table(table) => (&table.temperature, &table.ph, &table.interpolation),
+            BufferCurve::Equation(equation) => {
+                let (low, high) = equation.range;
+                if ![equation.a, equation.b, equation.c, equation.d, low, high]
+                    .iter()
+                    .all(|value| value.is_finite())
+                {
+                    return Err(Error::InvalidBuffer(format!(
+                        "buffer {} contains non-finite values",
+                        self.name
+                    )));
+                }
+                if low >= high || low <= -ZERO_CELSIUS {
+                    return Err(Error::InvalidBuffer(format!(
+                        "buffer {} has an invalid range from {} to {} ˚C",
+                        self.name, low, high
+                    )));
+                }
+                return Ok(());
+            }
+        };
+        if temperature.len() != ph.len() {
+            return Err(Error::InvalidBuffer(format!(
+                "buffer {} has {} temperature points but {} pH values",
+                self.name,
+                temperature.len(),
+                ph.len()
+            )));
+        }
+        if temperature.len() < interpolation.min_points() {
+            return Err(Error::InvalidBuffer(format!(
+                "buffer {} needs at least {} points for {:?} interpolation",
+                self.name,
+                interpolation.min_points(),
+                interpolation
+            )));
+        }
+        if !temperature
+            .iter()
+            .chain(ph.iter())
+            .all(|value| value.is_finite())
+        {
+            return Err(Error::InvalidBuffer(format!(
+                "buffer {} contains non-finite values",
+                self.name
+            )));
+        }
+        if !temperature.windows(2).all(|pair| pair[0] < pair[1]) {
+            return Err(Error::InvalidBuffer(format!(
+                "buffer {} temperatures are not strictly increasing",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks if the buffer is labelled with the nominal pH value
+    pub fn is_nominal(&self, nominal: &f64) -> bool {
+        self.nominal.approx_eq(*nominal, F64Margin::default())
+    }
+
+    /// Lowest and highest temperatures the buffer is defined for
+    pub fn temperature_range(&self) -> (f64, f64) {
+        self.curve.temperature_range()
+    }
+}
+
+/// A named collection of buffer solutions
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferSet {
+    /// Name of the set
+    pub name: String,
+    /// Buffers in the set
+    pub buffers: Vec<Buffer>,
+}
+
+impl BufferSet {
+    /// The common 4.01, 7.00 and 10.01 technical buffers, tabulated from 0 to 95˚C
+    pub fn technical() -> BufferSet {
+        BufferSet {
+            name: "technical".to_string(),
+            buffers: vec![
+                Buffer::new("technical 4.01", 4.01, &TEMP_STATIC, &PH4_STATIC),
+                Buffer::new("technical 7.00", 7.0, &TEMP_STATIC, &PH7_STATIC),
+                Buffer::new("technical 10.01", 10.01, &TEMP_STATIC, &PH10_STATIC),
+            ],
+        }
+    }
+
+    /// The DIN 19266 / NIST primary standards.
+    ///
+    /// The carbonate (10.01) and calcium hydroxide (12.45) standards are only tabulated up to 50˚C and 60˚C respectively.
+    pub fn nist() -> BufferSet {
+        BufferSet {
+            name: "nist".to_string(),
+            buffers: vec![
+                Buffer::new("tetroxalate", 1.68, &TEMP_DIN_STATIC, &PH168_DIN_STATIC),
+                Buffer::new("phthalate", 4.01, &TEMP_DIN_STATIC, &PH401_DIN_STATIC),
+                Buffer::new("phosphate", 6.86, &TEMP_DIN_STATIC, &PH686_DIN_STATIC),
+                Buffer::new("borax", 9.18, &TEMP_DIN_STATIC, &PH918_DIN_STATIC),
+                Buffer::new(
+                    "carbonate",
+                    10.01,
+                    &TEMP_DIN_STATIC[..11],
+                    &PH1001_DIN_STATIC,
+                ),
+                Buffer::new(
+                    "calcium hydroxide",
+                    12.45,
+                    &TEMP_DIN_STATIC[..12],
+                    &PH1245_DIN_STATIC,
+                ),
+            ],
+        }
+    }
+
+    /// The DIN 19266 / NIST primary standards, described by equations rather than tables.
+    ///
+    /// The equations are least squares fits to the tables of [`BufferSet::nist`], within 0.002 pH of their points, and
+    /// are valid over the same temperatures.
+    pub fn nist_equations() -> BufferSet {
+        let equation = |coefficients, high| BufferEquation::new(coefficients, (0.0, high));
+        BufferSet {
+            name: "nist-equations".to_string(),
+            buffers: vec![
+                Buffer::from_equation("tetroxalate", 1.68, equation(PH168_DIN_EQUATION, 95.0)),
+                Buffer::from_equation("phthalate", 4.01, equation(PH401_DIN_EQUATION, 95.0)),
+                Buffer::from_equation("phosphate", 6.86, equation(PH686_DIN_EQUATION, 95.0)),
+                Buffer::from_equation("borax", 9.18, equation(PH918_DIN_EQUATION, 95.0)),
+                Buffer::from_equation("carbonate", 10.01, equation(PH1001_DIN_EQUATION, 50.0)),
+                Buffer::from_equation(
+                    "calcium hydroxide",
+                    12.45,
+                    equation(PH1245_DIN_EQUATION, 60.0),
+                ),
+            ],
+        }
+    }
+
+    /// Modifies the interpolation of every buffer in the set
+    pub fn with_interpolation(&self, interpolation: InterpolationMethod) -> BufferSet {
+        BufferSet {
+            name: self.name.clone(),
+            buffers: self
+                .buffers
+                .iter()
+                .map(|buffer| buffer.with_interpolation(interpolation))
+                .collect(),
+        }
+    }
+
+    /// Finds the buffer labelled with the nominal pH value
+    pub fn find(&self, nominal: &f64) -> Option<&Buffer> {
+        self.buffers
+            .iter()
+            .find(|buffer| buffer.is_nominal(nominal))
+    }
+
+    /// Parses a set of buffers from the contents of a TOML buffer file, see the module documentation for the format.
+    ///
+    /// Every buffer is validated, so tables that are too short or not strictly increasing in temperature are refused.
+    pub fn from_toml(contents: &str) -> Result<BufferSet> {
+        let file: BufferFile =
+            toml::from_str(contents).map_err(|err| Error::InvalidBuffer(err.to_string()))?;
+        if file.buffers.is_empty() {
+            return Err(Error::InvalidBuffer("no buffers defined".to_string()));
+        }
+
+        let buffers = file
+            .buffers
+            .into_iter()
+            .map(|entry| {
+                let buffer = entry.into_buffer()?;
+                buffer.validate()?;
+                Ok(buffer)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(BufferSet {
+            name: file.name.unwrap_or_else(|| "custom".to_string()),
+            buffers,
+        })
+    }
+
+    /// Reads a set of buffers from a TOML buffer file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<BufferSet> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        BufferSet::from_toml(&contents).map_err(|err| match err {
+            Error::InvalidBuffer(message) => {
+                Error::InvalidBuffer(format!("{}: {}", path.display(), message))
+            }
+            err => err,
+        })
+    }
+
+    /// Adds the buffers of `other` to the set, replacing any buffers with the same nominal pH
+    pub fn merge(&mut self, other: BufferSet) {
+        for buffer in other.buffers {
+            match self
+                .buffers
+                .iter_mut()
+                .find(|b| b.is_nominal(&buffer.nominal))
+            {
+                Some(existing) => *existing = buffer,
+                None => self.buffers.push(buffer),
+            }
+        }
+        self.name = format!("{}+{}", self.name, other.name);
+    }
+}
+
+/// Layout of a TOML buffer file
+#[derive(Deserialize)]
+struct BufferFile {
+    name: Option<String>,
+    #[serde(rename = "buffer")]
+    buffers: Vec<BufferEntry>,
+}
+
+/// Layout of a buffer in a TOML buffer file, defined by either a table or an equation
+#[derive(Deserialize)]
+struct BufferEntry {
+    #[serde(default)]
+    name: String,
+    nominal: f64,
+    temperature: Option<Vec<f64>>,
+    ph: Option<Vec<f64>>,
+    #[serde(default)]
+    interpolation: InterpolationMethod,
+    equation: Option<BufferEquation>,
+}
+
+impl BufferEntry {
+    /// The buffer, named after its nominal pH if unnamed
+    fn into_buffer(self) -> Result<Buffer> {
+        let name = if self.name.is_empty() {
+            format!("{}", self.nominal)
+        } else {
+            self.name
+        };
+        let curve = match (self.temperature, self.ph, self.equation) {
+            (Some(temperature), Some(ph), None) => {
+                BufferCurve::Table(BufferTable::new(temperature, ph, self.interpolation))
+            }
+            (None, None, Some(equation)) => BufferCurve::Equation(equation),
+            _ => {
+                return Err(Error::InvalidBuffer(format!(
+                    "buffer {} needs either a table of temperature and ph, or an equation",
+                    name
+                )))
+            }
+        };
+        Ok(Buffer {
+            name,
+            nominal: self.nominal,
+            curve,
+        })
+    }
+}
+
+impl Default for BufferSet {
+    fn default() -> Self {
+        BufferSet::technical()
+    }
+}
+
+impl fmt::Display for BufferSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl FromStr for BufferSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "technical" => Ok(BufferSet::technical()),
+            "nist" | "din19266" => Ok(BufferSet::nist()),
+            "nist-equations" => Ok(BufferSet::nist_equations()),
+            _ => Err(format!("Unknown buffer set {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferCurve, BufferEquation, BufferSet, BufferTable, InterpolationMethod};
+    use float_cmp::approx_eq;
+
+    #[test]
+    fn test_buffer_set_find() {
+        let technical = BufferSet::technical();
+        assert!(technical.find(&7.0).is_some());
+        assert!(technical.find(&6.86).is_none());
+
+        let nist = BufferSet::nist();
+        assert!(nist.find(&6.86).is_some());
+        assert!(nist.find(&7.0).is_none());
+    }
+
+    #[test]
+    fn test_buffer_tables() {
+        for set in [BufferSet::technical(), BufferSet::nist()] {
+            for buffer in set.buffers {
+                assert!(buffer.validate().is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_buffer_curve_clamped() {
+        let nist = BufferSet::nist();
+        let equations = BufferSet::nist_equations();
+        for curve in [&nist, &equations].map(|set| &set.find(&4.01).unwrap().curve) {
+            // Within the range the pH is that of the curve, beyond it that at the nearest end
+            let (low, high) = curve.temperature_range();
+            assert_eq!(curve.clamped(&22.3), curve.ph(&22.3));
+            assert_eq!(curve.clamped(&(low - 5.0)), curve.ph(&low));
+            assert_eq!(curve.clamped(&(high + 5.0)), curve.ph(&high));
+        }
+    }
+
+    #[test]
+    fn test_buffer_set_from_str() {
+        assert_eq!("DIN19266".parse::<BufferSet>().unwrap(), BufferSet::nist());
+        assert!("acetate".parse::<BufferSet>().is_err());
+    }
+
+    #[test]
+    fn test_buffer_set_from_toml() {
+        let contents = r#"
+            name = "lot 2104"
+
+            [[buffer]]
+            name = "phthalate"
+            nominal = 4.01
+            temperature = [10.0, 20.0, 25.0, 30.0]
+            ph = [3.998, 4.001, 4.006, 4.012]
+
+            [[buffer]]
+            nominal = 7.0
+            interpolation = "catmull-rom"
+            temperature = [10.0, 20.0, 25.0, 30.0]
+            ph = [7.06, 7.02, 7.0, 6.99]
+        "#;
+        let set = BufferSet::from_toml(contents).unwrap();
+
+        assert_eq!(set.name, "lot 2104");
+        assert_eq!(set.buffers.len(), 2);
+        assert_eq!(set.buffers[1].name, "7");
+        assert_eq!(
+            set.buffers[1].curve,
+            BufferCurve::Table(BufferTable::new(
+                vec![10.0, 20.0, 25.0, 30.0],
+                vec![7.06, 7.02, 7.0, 6.99],
+                InterpolationMethod::CatmullRom,
+            ))
+        );
+    }
+
+    #[test]
+    fn test_buffer_set_from_toml_invalid() {
+        let non_monotonic = r#"
+            [[buffer]]
+            nominal = 4.01
+            temperature = [10.0, 30.0, 20.0]
+            ph = [3.998, 4.012, 4.001]
+        "#;
+        assert!(BufferSet::from_toml(non_monotonic).is_err());
+
+        let too_short = r#"
+            [[buffer]]
+            nominal = 4.01
+            interpolation = "catmull-rom"
+            temperature = [10.0, 20.0, 30.0]
+            ph = [3.998, 4.001, 4.012]
+        "#;
+        assert!(BufferSet::from_toml(too_short).is_err());
+
+        let mismatched = r#"
+            [[buffer]]
+            nominal = 4.01
+            temperature = [10.0, 20.0, 30.0]
+            ph = [3.998, 4.001]
+        "#;
+        assert!(BufferSet::from_toml(mismatched).is_err());
+    }
+
+    #[test]
+    fn test_buffer_equation() {
+        let tables = BufferSet::nist();
+        let equations = BufferSet::nist_equations();
+        for (table, equation) in tables.buffers.iter().zip(equations.buffers.iter()) {
+            assert_eq!(table.temperature_range(), equation.temperature_range());
+            if let BufferCurve::Table(table) = &table.curve {
+                for (t, ph) in table.temperature().iter().zip(table.ph().iter()) {
+                    assert!((equation.curve.ph(t).unwrap() - ph).abs() < 0.002);
+                }
+            }
+        }
+        let carbonate = equations.find(&10.01).unwrap();
+        assert!(carbonate.curve.ph(&60.0).is_none());
+        assert!(carbonate.curve.extrapolated(&60.0).unwrap() < 9.828);
+
+        let contents = r#"
+            [[buffer]]
+            name = "phosphate"
+            nominal = 6.86
+            equation = { a = 3453.25, b = -20.9993, c = 0.0731207, d = -6.20826e-5, range = [0.0, 95.0] }
+        "#;
+        let set = BufferSet::from_toml(contents).unwrap();
+        let phosphate = set.find(&6.86).unwrap();
+        assert_eq!(
+            phosphate.curve,
+            BufferCurve::Equation(BufferEquation::new(
+                [3453.25, -20.9993, 0.0731207, -6.20826e-5],
+                (0.0, 95.0)
+            ))
+        );
+        assert!(approx_eq!(
+            f64,
+            phosphate.curve.ph(&25.0).unwrap(),
+            6.865,
+            epsilon = 0.001
+        ));
+
+        let both = r#"
+            [[buffer]]
+            nominal = 6.86
+            temperature = [10.0, 20.0]
+            ph = [6.92, 6.88]
+            equation = { a = 3453.25, b = -20.9993, c = 0.0731207, d = -6.20826e-5, range = [0.0, 95.0] }
+        "#;
+        assert!(BufferSet::from_toml(both).is_err());
+
+        let reversed = r#"
+            [[buffer]]
+            nominal = 6.86
+            equation = { a = 3453.25, b = -20.9993, c = 0.0731207, d = -6.20826e-5, range = [95.0, 0.0] }
+        "#;
+        assert!(BufferSet::from_toml(reversed).is_err());
+    }
+
+    #[test]
+    fn test_buffer_set_merge() {
+        let mut set = BufferSet::technical();
+        let mut custom = BufferSet::nist();
+        custom.buffers.truncate(2);
+        set.merge(custom);
+
+        assert_eq!(set.name, "technical+nist");
+        assert_eq!(set.buffers.len(), 4);
+        assert_eq!(set.find(&4.01).unwrap().name, "phthalate");
+    }
+}
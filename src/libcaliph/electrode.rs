@@ -0,0 +1,206 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Electrode Module
+//! Provides the Nernstian model of a pH electrode, $`E = E_0 - S \cdot pH`$.
+//!
+//! Readings are either the pH displayed by a meter, or the potential of the electrode in mV. A calibration
+//! $`pH = m x + c`$ of either describes the electrode, which is compared to the theoretical slope
+//! $`S_N = R T \ln 10 / F`$ at the calibration temperature.
+
+use super::routines::{nernst_slope, Calibration, ISOPOTENTIAL_PH, ZERO_CELSIUS};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Quantity read from the meter
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Reading {
+    /// pH displayed by the meter
+    #[default]
+    #[serde(rename = "ph")]
+    Ph,
+    /// Potential of the electrode, in mV
+    #[serde(rename = "mv")]
+    Millivolt,
+}
+
+impl fmt::Display for Reading {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Reading::Ph => "pH",
+            Reading::Millivolt => "mV",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Reading {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ph" => Ok(Reading::Ph),
+            "mv" => Ok(Reading::Millivolt),
+            _ => Err(format!("Unknown reading {}", s)),
+        }
+    }
+}
+
+/// Nernstian model of a pH electrode at a temperature
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Electrode {
+    /// Slope $`S`$, in mV per pH unit
+    pub slope: f64,
+    /// Standard potential $`E_0`$ at pH 0, in mV
+    pub standard_potential: f64,
+    /// Temperature of the electrode, in ˚C
+    pub temperature: f64,
+}
+
+impl Electrode {
+    /// Electrode described by a calibration of readings made at `temperature` in ˚C.
+    ///
+    /// A meter displaying the pH assumes a Nernstian electrode with zero potential at pH 7,
+    /// i.e. it reads $`x = 7 - E/S_N`$.
+    pub fn from_calibration(
+        calibration: &Calibration<f64>,
+        reading: Reading,
+        temperature: &f64,
+    ) -> Electrode {
+        // Both readings give pH = a - E/S
+        let (slope, intercept) = match reading {
+            Reading::Ph => (
+                nernst_slope(temperature) / calibration.slope,
+                ISOPOTENTIAL_PH * calibration.slope + calibration.offset,
+            ),
+            Reading::Millivolt => (-1.0 / calibration.slope, calibration.offset),
+        };
+        Electrode {
+            slope,
+            standard_potential: slope * intercept,
+            temperature: *temperature,
+        }
+    }
+
+    /// Theoretical (Nernstian) slope at the temperature of the electrode, in mV per pH unit
+    pub fn nernst_slope(&self) -> f64 {
+        nernst_slope(&self.temperature)
+    }
+
+    /// Slope in % of the Nernstian slope
+    pub fn slope_percent(&self) -> f64 {
+        100.0 * self.slope / self.nernst_slope()
+    }
+
+    /// Asymmetry potential, the potential at pH 7, in mV
+    pub fn asymmetry_potential(&self) -> f64 {
+        self.potential(&ISOPOTENTIAL_PH)
+    }
+
+    /// Potential of the electrode in a solution of the given pH, in mV
+    pub fn potential(&self, ph: &f64) -> f64 {
+        self.standard_potential - self.slope * ph
+    }
+
+    /// pH of a solution giving the potential in mV
+    pub fn ph(&self, potential: &f64) -> f64 {
+        (self.standard_potential - potential) / self.slope
+    }
+
+    /// Electrode at another temperature, with the slope scaled by the absolute temperature
+    /// around the isopotential point at pH 7
+    pub fn at_temperature(&self, temperature: &f64) -> Electrode {
+        let slope = self.slope * (temperature + ZERO_CELSIUS) / (self.temperature + ZERO_CELSIUS);
+        Electrode {
+            slope,
+            standard_potential: self.asymmetry_potential() + slope * ISOPOTENTIAL_PH,
+            temperature: *temperature,
+        }
+    }
+
+    /// Calibration converting potentials in mV to pH, $`pH = E_0/S - E/S`$
+    pub fn millivolt_calibration(&self) -> [f64; 2] {
+        [-1.0 / self.slope, self.standard_potential / self.slope]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{Electrode, Reading};
+    use crate::routines::{nernst_slope, ph_calibration_points, Calibration};
+
+    #[test]
+    fn test_electrode_from_millivolt() {
+        // 57 mV/pH with 10 mV at pH 7
+        let ph_buffer = [4.01, 7.0, 10.01];
+        let potential: Vec<f64> = ph_buffer
+            .iter()
+            .map(|ph| 10.0 - 57.0 * (ph - 7.0))
+            .collect();
+        let calibration = ph_calibration_points(&potential, &ph_buffer).unwrap();
+        let electrode = Electrode::from_calibration(&calibration, Reading::Millivolt, &25.0);
+
+        assert!(approx_eq!(f64, electrode.slope, 57.0, epsilon = 1e-9));
+        assert!(approx_eq!(
+            f64,
+            electrode.asymmetry_potential(),
+            10.0,
+            epsilon = 1e-9
+        ));
+        assert!(approx_eq!(
+            f64,
+            electrode.slope_percent(),
+            96.35,
+            epsilon = 0.01
+        ));
+        assert!(approx_eq!(f64, electrode.ph(&10.0), 7.0, epsilon = 1e-9));
+    }
+
+    #[test]
+    fn test_electrode_from_ph() {
+        let calibration = Calibration::default().with_slope(1.0).with_offset(0.0);
+        let electrode = Electrode::from_calibration(&calibration, Reading::Ph, &25.0);
+
+        assert!(approx_eq!(f64, electrode.slope, nernst_slope(&25.0)));
+        assert!(approx_eq!(
+            f64,
+            electrode.asymmetry_potential(),
+            0.0,
+            epsilon = 1e-9
+        ));
+    }
+
+    #[test]
+    fn test_electrode_at_temperature() {
+        let electrode = Electrode {
+            slope: 59.0,
+            standard_potential: 420.0,
+            temperature: 25.0,
+        };
+        let warm = electrode.at_temperature(&50.0);
+
+        assert!(approx_eq!(
+            f64,
+            warm.slope_percent(),
+            electrode.slope_percent(),
+            epsilon = 1e-9
+        ));
+        assert!(approx_eq!(
+            f64,
+            warm.asymmetry_potential(),
+            electrode.asymmetry_potential(),
+            epsilon = 1e-9
+        ));
+        let [slope, offset] = warm.millivolt_calibration();
+        assert!(approx_eq!(
+            f64,
+            slope * 100.0 + offset,
+            warm.ph(&100.0),
+            epsilon = 1e-9
+        ));
+    }
+}
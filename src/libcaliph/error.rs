@@ -0,0 +1,179 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Error Module
+//! Provides the error type returned by the public functions of the library.
+
+use super::temperature::{Temperature, TemperatureUnit};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Result type of the library
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned by the library
+#[derive(Debug)]
+pub enum Error {
+    /// Two sets of values which should be paired have different lengths
+    LengthMismatch { expected: usize, found: usize },
+    /// The fit is undetermined, as the values have zero variance
+    DegenerateFit,
+    /// The temperature is outside the table or equation of a buffer solution, with the temperatures in ˚C and the
+    /// unit they are written in
+    TemperatureOutOfRange {
+        nominal: f64,
+        temperature: f64,
+        range: (f64, f64),
+        unit: TemperatureUnit,
+    },
+    /// An input value is NaN or infinite
+    NonFinite(String),
+    /// A calibration file could not be parsed
+    MalformedCalibration(String),
+    /// A buffer definition is invalid, or a buffer is not in the buffer set
+    InvalidBuffer(String),
+    /// Measured values could not be read, at the line counting from 1 if known
+    InvalidInput {
+        line: Option<usize>,
+        message: String,
+    },
+    /// The calibration fails its acceptance criteria, for the reasons given
+    CalibrationRejected(String),
+    /// The calibration has no uncertainty, as it was not fitted to more than two points
+    NoUncertainty,
+    /// A polynomial has at least as many coefficients as there are points to fit
+    TooFewPoints { degree: usize, found: usize },
+    /// A file could not be read or written
+    File { path: PathBuf, source: io::Error },
+    /// Reading or writing a stream failed
+    Io(io::Error),
+}
+
+impl Error {
+    /// Exit code used by the command line tools for the error.
+    ///
+    /// Code 1 is left for other failures, and 2 is used for invalid command line arguments.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::LengthMismatch { .. } => 3,
+            Error::DegenerateFit => 4,
+            Error::TemperatureOutOfRange { .. } => 5,
+            Error::NonFinite(_) => 6,
+            Error::MalformedCalibration(_) => 7,
+            Error::InvalidBuffer(_) => 8,
+            Error::InvalidInput { .. } => 9,
+            Error::File { .. } | Error::Io(_) => 10,
+            Error::CalibrationRejected(_) => 11,
+            Error::NoUncertainty => 12,
+            Error::TooFewPoints { .. } => 13,
+        }
+    }
+
+    /// Writes the temperatures of the error in `unit`, as given by the user, rather than in ˚C
+    pub fn in_unit(self, unit: TemperatureUnit) -> Error {
+        match self {
+            Error::TemperatureOutOfRange {
+                nominal,
+                temperature,
+                range,
+                ..
+            } => Error::TemperatureOutOfRange {
+                nominal,
+                temperature,
+                range,
+                unit,
+            },
+            err => err,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::LengthMismatch { expected, found } => write!(
+                f,
+                "expected {} values to pair with, but found {}",
+                expected, found
+            ),
+            Error::DegenerateFit => write!(f, "the fit is undetermined, as the values do not vary"),
+            Error::TemperatureOutOfRange {
+                nominal,
+                temperature,
+                range,
+                unit,
+            } => {
+                let in_unit = |celsius: f64| Temperature::from_celsius(celsius).to_unit(*unit);
+                write!(
+                    f,
+                    "{} is outside the range of the pH {} buffer, from {} to {}",
+                    in_unit(*temperature).with_celsius(),
+                    nominal,
+                    in_unit(range.0),
+                    in_unit(range.1)
+                )
+            }
+            Error::NonFinite(what) => write!(f, "{} must be finite", what),
+            Error::MalformedCalibration(message) => {
+                write!(f, "malformed calibration file: {}", message)
+            }
+            Error::InvalidBuffer(message) => write!(f, "invalid buffer: {}", message),
+            Error::InvalidInput {
+                line: Some(line),
+                message,
+            } => write!(f, "line {}: {}", line, message),
+            Error::InvalidInput {
+                line: None,
+                message,
+            } => write!(f, "{}", message),
+            Error::CalibrationRejected(reasons) => write!(f, "calibration rejected: {}", reasons),
+            Error::NoUncertainty => write!(
+                f,
+                "the calibration has no uncertainty, which needs a fit of more than two buffers"
+            ),
+            Error::TooFewPoints { degree, found } => write!(
+                f,
+                "a polynomial of degree {} needs at least {} points, but found {}",
+                degree,
+                degree + 1,
+                found
+            ),
+            Error::File { path, source } => write!(f, "{}: {}", path.display(), source),
+            Error::Io(source) => write!(f, "{}", source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::File { source, .. } | Error::Io(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Self {
+        let line = err.position().map(|position| position.line() as usize);
+        let message = err.to_string();
+        match err.into_kind() {
+            csv::ErrorKind::Io(err) => Error::Io(err),
+            _ => Error::InvalidInput { line, message },
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Io(err.into())
+    }
+}
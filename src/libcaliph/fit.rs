@@ -3,8 +3,12 @@ License, v. 2.0. If a copy of the MPL was not distributed with this
 file, You can obtain one at https://mozilla.org/MPL/2.0/.
 Copyright 2021 Peter Dunne */
 
-//! Provides methods to performs linear fit of two input arrays, x and y
+//! Provides methods to performs linear and polynomial fits of two input arrays, x and y
+use super::error::{Error, Result};
 use super::stats;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 /// Linear regression of x,y data
 /// Returns an array of [slope, offset]
@@ -14,16 +18,80 @@ use super::stats;
 /// let x = [1.0, 2.0, 3.0];
 /// let y = [3.0, 5.0, 7.0];
 ///
-/// let result = fit(&x, &y);
+/// let result = fit(&x, &y).unwrap();
 /// let comparison = [2.0, 1.0];
 ///
 /// assert!(approx_eq!(f64, result[0], comparison[0]) &&  approx_eq!(f64, result[0], comparison[0]) );
 /// ```
 ///
-pub fn fit(x: &[f64], y: &[f64]) -> [f64; 2] {
-    let slope = stats::covariance(x, y) / stats::variance(x);
+/// Returns an error if x and y differ in length, contain non-finite values, or if x has zero variance.
+pub fn fit(x: &[f64], y: &[f64]) -> Result<[f64; 2]> {
+    check_finite(x, "x")?;
+    check_finite(y, "y")?;
+    let variance = stats::variance(x);
+    if variance == 0.0 {
+        return Err(Error::DegenerateFit);
+    }
+    let slope = stats::covariance(x, y)? / variance;
     let intercept = stats::mean(y) - slope * stats::mean(x);
-    [slope, intercept]
+    Ok([slope, intercept])
+}
+
+/// Weighted linear regression of x,y data, each point counting in proportion to its weight.
+/// Returns an array of [slope, offset]
+/// ```
+/// use crate::libcaliph::fit::{fit_weighted, weights_from_deviations};
+/// use float_cmp::approx_eq;
+/// let x = [1.0, 2.0, 3.0, 4.0];
+/// let y = [3.0, 5.0, 7.0, 10.0];
+///
+/// // The last point is ten times less certain than the others
+/// let weights = weights_from_deviations(&[0.1, 0.1, 0.1, 1.0]).unwrap();
+/// let result = fit_weighted(&x, &y, &weights).unwrap();
+///
+/// assert!(approx_eq!(f64, result[0], 2.0, epsilon = 0.02) && approx_eq!(f64, result[1], 1.0, epsilon = 0.05));
+/// ```
+///
+/// Returns an error if x, y and the weights differ in length, contain non-finite values, if a weight is
+/// negative or all are zero, or if x has zero weighted variance.
+pub fn fit_weighted(x: &[f64], y: &[f64], weights: &[f64]) -> Result<[f64; 2]> {
+    check_finite(x, "x")?;
+    check_finite(y, "y")?;
+    let variance = stats::weighted_variance(x, weights)?;
+    if variance == 0.0 {
+        return Err(Error::DegenerateFit);
+    }
+    let slope = stats::weighted_covariance(x, y, weights)? / variance;
+    let intercept = stats::weighted_mean(y, weights)? - slope * stats::weighted_mean(x, weights)?;
+    Ok([slope, intercept])
+}
+
+/// Weights of points with the given standard deviations, $`w_i = 1/\sigma_i^2`$
+///
+/// Returns an error unless every standard deviation is positive and finite.
+pub fn weights_from_deviations(deviations: &[f64]) -> Result<Vec<f64>> {
+    deviations
+        .iter()
+        .map(|deviation| {
+            if deviation.is_finite() && *deviation > 0.0 {
+                Ok(deviation.powi(-2))
+            } else {
+                Err(Error::InvalidInput {
+                    line: None,
+                    message: format!("standard deviation {} must be positive", deviation),
+                })
+            }
+        })
+        .collect()
+}
+
+/// Checks all values are finite
+fn check_finite(values: &[f64], name: &str) -> Result<()> {
+    if values.iter().all(|value| value.is_finite()) {
+        Ok(())
+    } else {
+        Err(Error::NonFinite(format!("all values of {}", name)))
+    }
 }
 
 /// Gives predicted value using `model` for a given x
@@ -41,42 +109,819 @@ pub fn predict(x: &f64, model: &[f64; 2]) -> f64 {
     x * model[0] + model[1]
 }
 
-/// Calculates RMS for a model
-fn root_mean_squared_error(actual: &[f64], predicted: &[f64]) -> f64 {
-    let length = actual.len();
+/// Names of the robust estimators
+pub const ROBUST_NAMES: [&str; 2] = ["huber", "theil-sen"];
 
-    let sum_error_iter: f64 = predicted
-        .iter()
-        .zip(actual.iter())
-        .map(|(x, y)| (x - y).powi(2))
-        .sum();
+/// Tuning constant of the Huber estimator, giving 95 % of the efficiency of least squares for normal errors
+pub const HUBER_K: f64 = 1.345;
+
+/// Residual, in robust standard deviations, beyond which a point is rejected by the Theil–Sen estimator
+pub const REJECTION_CUTOFF: f64 = 2.5;
+
+/// Weight below which a point of a robust fit is flagged as an outlier
+pub const OUTLIER_WEIGHT: f64 = 0.5;
+
+/// Robust estimator of a line, tolerating outlying points
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Robust {
+    /// Huber M-estimator, by iteratively reweighted least squares, down-weighting points with large residuals
+    Huber,
+    /// Theil–Sen estimator, the median of the slopes between all pairs of points
+    TheilSen,
+}
+
+impl fmt::Display for Robust {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Robust::Huber => "huber",
+            Robust::TheilSen => "theil-sen",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Robust {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "huber" => Ok(Robust::Huber),
+            "theil-sen" | "theilsen" => Ok(Robust::TheilSen),
+            _ => Err(format!("Unknown robust estimator {}", s)),
+        }
+    }
+}
+
+/// Robust fit of a line to x,y data
+#[derive(Clone, Debug, PartialEq)]
+pub struct RobustFit {
+    pub method: Robust,
+    /// Slope and offset of the line
+    pub model: [f64; 2],
+    /// Weight of each point, from 1 for a point fully trusted down to 0 for a rejected one
+    pub weights: Vec<f64>,
+    /// Robust standard deviation of the residuals, from their median absolute value
+    pub scale: f64,
+}
+
+impl RobustFit {
+    /// Indices of the points flagged as outliers, weighted below [`OUTLIER_WEIGHT`]
+    pub fn outliers(&self) -> Vec<usize> {
+        self.weights
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight < OUTLIER_WEIGHT)
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
 
-    (sum_error_iter / length as f64).sqrt()
+/// Robust linear regression of x,y data, with the weight each point is given
+/// ```
+/// use crate::libcaliph::fit::{robust_fit, Robust};
+/// use float_cmp::approx_eq;
+/// let x = [1.0, 2.0, 3.0, 4.0, 5.0];
+/// let y = [3.0, 5.0, 9.5, 9.0, 11.0];
+///
+/// let result = robust_fit(&x, &y, Robust::TheilSen).unwrap();
+///
+/// assert!(approx_eq!(f64, result.model[0], 2.0) && approx_eq!(f64, result.model[1], 1.0));
+/// assert_eq!(result.outliers(), vec![2]);
+/// ```
+///
+/// Returns an error if x and y differ in length, contain non-finite values, or if x has zero variance.
+pub fn robust_fit(x: &[f64], y: &[f64], method: Robust) -> Result<RobustFit> {
+    check_finite(x, "x")?;
+    check_finite(y, "y")?;
+    if x.len() != y.len() {
+        return Err(Error::LengthMismatch {
+            expected: x.len(),
+            found: y.len(),
+        });
+    }
+    // The scale is that of the Theil–Sen line, kept fixed while the Huber line is reweighted
+    let mut model = theil_sen(x, y)?;
+    let scale = residual_scale(x, y, &model);
+    let weights = match method {
+        Robust::TheilSen => x
+            .iter()
+            .zip(y.iter())
+            .map(|(x, y)| {
+                let residual = y - predict(x, &model);
+                if residual.abs() > REJECTION_CUTOFF * scale {
+                    0.0
+                } else {
+                    1.0
+                }
+            })
+            .collect(),
+        Robust::Huber => {
+            let mut weights = vec![1.0; x.len()];
+            for _ in 0..100 {
+                weights = x
+                    .iter()
+                    .zip(y.iter())
+                    .map(|(x, y)| {
+                        let residual = (y - predict(x, &model)).abs();
+                        if residual <= HUBER_K * scale {
+                            1.0
+                        } else {
+                            HUBER_K * scale / residual
+                        }
+                    })
+                    .collect();
+                let next = fit_weighted(x, y, &weights)?;
+                let converged = (next[0] - model[0]).abs() <= 1e-12 * next[0].abs().max(1.0)
+                    && (next[1] - model[1]).abs() <= 1e-12 * next[1].abs().max(1.0);
+                model = next;
+                if converged {
+                    break;
+                }
+            }
+            weights
+        }
+    };
+    Ok(RobustFit {
+        method,
+        model,
+        weights,
+        scale,
+    })
 }
 
-fn rsquared(y: &[f64], rms: &f64) -> f64 {
-    1.0 - (rms / stats::variance(y))
+/// Theil–Sen line, through the median of the slopes between all pairs of points with distinct x
+fn theil_sen(x: &[f64], y: &[f64]) -> Result<[f64; 2]> {
+    let mut slopes = Vec::new();
+    for i in 0..x.len() {
+        for j in i + 1..x.len() {
+            if x[i] != x[j] {
+                slopes.push((y[j] - y[i]) / (x[j] - x[i]));
+            }
+        }
+    }
+    if slopes.is_empty() {
+        return Err(Error::DegenerateFit);
+    }
+    let slope = stats::median(&slopes);
+    let intercepts: Vec<f64> = x.iter().zip(y.iter()).map(|(x, y)| y - slope * x).collect();
+    Ok([slope, stats::median(&intercepts)])
 }
 
-/// Evaluates all data in a model, returning the root mean squared error (RMSE), and the R-Squared goodness of fit
+/// Robust standard deviation of the residuals, $`1.4826 \, \mathrm{median}|r_i|`$, kept above rounding error.
 ///
+/// A line can pass through any two points, so the two smallest residuals are left out of the median, which
+/// would otherwise collapse for the few points of a calibration.
+fn residual_scale(x: &[f64], y: &[f64], model: &[f64; 2]) -> f64 {
+    let mut residuals: Vec<f64> = x
+        .iter()
+        .zip(y.iter())
+        .map(|(x, y)| (y - predict(x, model)).abs())
+        .collect();
+    residuals.sort_by(|a, b| a.total_cmp(b));
+    let residuals = &residuals[residuals.len().saturating_sub(1).min(2)..];
+    let largest = y.iter().fold(1.0_f64, |max, value| max.max(value.abs()));
+    (1.4826 * stats::median(residuals)).max(1e-9 * largest)
+}
+
+/// Least squares polynomial fit of x,y data.
+/// Returns the coefficients in increasing powers of x, $`y = a_0 + a_1 x + \dots + a_d x^d`$
 /// ```
-/// use crate::libcaliph::fit::{fit,evaluate};
+/// use crate::libcaliph::fit::{polyfit, polyval};
 /// use float_cmp::approx_eq;
+/// let x = [0.0, 1.0, 2.0, 3.0];
+/// let y = [1.0, 2.0, 5.0, 10.0];
 ///
-/// let x = [1.05, 1.992, 3.03];
-/// let y = [2.993, 4.92, 6.99];
+/// let coefficients = polyfit(&x, &y, 2).unwrap();
 ///
-/// let model = fit(&x, &y);
-/// let result = evaluate(&x, &y, &model);
+/// assert!(approx_eq!(f64, polyval(&4.0, &coefficients), 17.0, epsilon = 1e-9));
+/// ```
 ///
-/// let comparison = [1.19675583971723e-2, 0.99550];
-/// println!("{:.e}", result[0]);
-/// println!("{}", result[1]);
-/// assert!(approx_eq!(f64, result[0], comparison[0]) &&  approx_eq!(f64, result[0], comparison[0]) );
-///```
-pub fn evaluate(x: &[f64], y: &[f64], model: &[f64; 2]) -> [f64; 2] {
-    let y_predicted: Vec<f64> = x.iter().map(|y| predict(y, model)).collect();
-    let rms = root_mean_squared_error(y, &y_predicted);
-    [rms, rsquared(y, &rms)]
+/// Returns an error if x and y differ in length, contain non-finite values, if there are no more points than
+/// the degree, or if there are not enough distinct x values to determine the coefficients.
+pub fn polyfit(x: &[f64], y: &[f64], degree: usize) -> Result<Vec<f64>> {
+    check_finite(x, "x")?;
+    check_finite(y, "y")?;
+    if x.len() != y.len() {
+        return Err(Error::LengthMismatch {
+            expected: x.len(),
+            found: y.len(),
+        });
+    }
+    if x.len() <= degree {
+        return Err(Error::TooFewPoints {
+            degree,
+            found: x.len(),
+        });
+    }
+
+    // Columns scaled to the largest |x|, to keep the Vandermonde matrix well conditioned
+    let scale = x.iter().fold(0.0_f64, |max, value| max.max(value.abs()));
+    let scale = if scale > 0.0 { scale } else { 1.0 };
+    let columns = degree + 1;
+    let mut matrix: Vec<Vec<f64>> = x
+        .iter()
+        .map(|value| {
+            (0..columns)
+                .map(|power| (value / scale).powi(power as i32))
+                .collect()
+        })
+        .collect();
+    let mut rhs = y.to_vec();
+
+    let scaled = least_squares(&mut matrix, &mut rhs)?;
+    Ok(scaled
+        .iter()
+        .enumerate()
+        .map(|(power, coefficient)| coefficient / scale.powi(power as i32))
+        .collect())
+}
+
+/// Solves the least squares problem by Householder QR decomposition, overwriting its arguments
+fn least_squares(matrix: &mut [Vec<f64>], rhs: &mut [f64]) -> Result<Vec<f64>> {
+    let rows = matrix.len();
+    let columns = matrix.first().map(Vec::len).unwrap_or(0);
+    let mut diagonal = vec![0.0; columns];
+
+    for column in 0..columns {
+        let norm = (column..rows)
+            .map(|row| matrix[row][column].powi(2))
+            .sum::<f64>()
+            .sqrt();
+        if norm <= f64::EPSILON * rows as f64 {
+            return Err(Error::DegenerateFit);
+        }
+        let alpha = if matrix[column][column] > 0.0 {
+            -norm
+        } else {
+            norm
+        };
+        // Householder vector v = a - alpha e, stored in place of the column
+        matrix[column][column] -= alpha;
+        let v_norm = (column..rows)
+            .map(|row| matrix[row][column].powi(2))
+            .sum::<f64>();
+        for other in column + 1..columns {
+            let dot: f64 = (column..rows)
+                .map(|row| matrix[row][column] * matrix[row][other])
+                .sum();
+            for row in matrix[column..].iter_mut() {
+                row[other] -= 2.0 * dot / v_norm * row[column];
+            }
+        }
+        let dot: f64 = (column..rows)
+            .map(|row| matrix[row][column] * rhs[row])
+            .sum();
+        for (value, row) in rhs[column..].iter_mut().zip(matrix[column..].iter()) {
+            *value -= 2.0 * dot / v_norm * row[column];
+        }
+        diagonal[column] = alpha;
+    }
+
+    // Back substitution of R a = Q^T y
+    let largest = diagonal
+        .iter()
+        .fold(0.0_f64, |max, value| max.max(value.abs()));
+    let mut coefficients = vec![0.0; columns];
+    for column in (0..columns).rev() {
+        if diagonal[column].abs() <= 1e-12 * largest {
+            return Err(Error::DegenerateFit);
+        }
+        let sum: f64 = (column + 1..columns)
+            .map(|other| matrix[column][other] * coefficients[other])
+            .sum();
+        coefficients[column] = (rhs[column] - sum) / diagonal[column];
+    }
+    Ok(coefficients)
+}
+
+/// Gives the value of the polynomial with `coefficients` in increasing powers, for a given x
+pub fn polyval(x: &f64, coefficients: &[f64]) -> f64 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |value, coefficient| value * x + coefficient)
+}
+
+/// Goodness of fit of a linear model to x,y data
+///
+/// With two points the line passes exactly through both, so there is no residual information:
+/// the degrees of freedom are 0, and all the statistics derived from the residuals are `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FitReport {
+    /// Slope of the model
+    pub slope: f64,
+    /// Intercept of the model
+    pub intercept: f64,
+    /// Residual $`y_i - \hat{y}_i`$ of each point
+    pub residuals: Vec<f64>,
+    /// Sum of squared residuals, each weighted if the model was fitted with weights
+    pub sse: f64,
+    /// Degrees of freedom of the residuals, $`n - 2`$
+    pub dof: usize,
+    /// Root mean squared error, $`\sqrt{SSE/n}`$
+    pub rmse: Option<f64>,
+    /// Standard error of the regression, $`s = \sqrt{SSE/(n - 2)}`$
+    pub standard_error: Option<f64>,
+    /// Coefficient of determination, $`R^2 = 1 - SSE/SST`$
+    pub rsq: Option<f64>,
+    /// $`R^2`$ adjusted for the number of points, $`1 - (1 - R^2)(n - 1)/(n - 2)`$
+    pub adjusted_rsq: Option<f64>,
+    /// Standard error of the slope, $`s / \sqrt{S_{xx}}`$
+    pub slope_error: Option<f64>,
+    /// Standard error of the intercept, $`s \sqrt{1/n + \bar{x}^2/S_{xx}}`$
+    pub intercept_error: Option<f64>,
+    /// Covariance matrix of the slope and intercept
+    pub covariance: Option<[[f64; 2]; 2]>,
+}
+
+impl FitReport {
+    /// Evaluates a model `[slope, intercept]` over all the x,y data
+    ///
+    /// ```
+    /// use crate::libcaliph::fit::{fit, FitReport};
+    /// use float_cmp::approx_eq;
+    ///
+    /// let x = [1.05, 1.992, 3.03];
+    /// let y = [2.993, 4.92, 6.99];
+    ///
+    /// let model = fit(&x, &y).unwrap();
+    /// let report = FitReport::new(&x, &y, &model).unwrap();
+    ///
+    /// assert_eq!(report.dof, 1);
+    /// assert!(approx_eq!(f64, report.rmse.unwrap(), 1.19675583971723e-2, epsilon = 1e-9));
+    /// assert!(approx_eq!(f64, report.rsq.unwrap(), 0.999946, epsilon = 1e-6));
+    ///```
+    ///
+    /// Returns an error if x and y differ in length, or there are fewer than two points.
+    pub fn new(x: &[f64], y: &[f64], model: &[f64; 2]) -> Result<FitReport> {
+        FitReport::weighted(x, y, model, &vec![1.0; x.len()])
+    }
+
+    /// Evaluates a model `[slope, intercept]` fitted with weights, see [`fit_weighted`], over all the x,y data
+    ///
+    /// The weights are relative, scaled to a mean of 1, so the standard error of the regression is that of a
+    /// point of average weight. The residuals themselves are unweighted.
+    ///
+    /// Returns an error if x, y and the weights differ in length, a weight is negative or all are zero, or
+    /// there are fewer than two points.
+    pub fn weighted(x: &[f64], y: &[f64], model: &[f64; 2], weights: &[f64]) -> Result<FitReport> {
+        if x.len() != y.len() {
+            return Err(Error::LengthMismatch {
+                expected: x.len(),
+                found: y.len(),
+            });
+        }
+        if x.len() < 2 {
+            return Err(Error::DegenerateFit);
+        }
+        let length = x.len();
+        let n = length as f64;
+        // Checks the weights, then scales them to a mean of 1
+        stats::weighted_mean(x, weights)?;
+        let mean_weight = stats::mean(weights);
+        let weights: Vec<f64> = weights.iter().map(|weight| weight / mean_weight).collect();
+
+        let residuals: Vec<f64> = x
+            .iter()
+            .zip(y.iter())
+            .map(|(x, y)| y - predict(x, model))
+            .collect();
+        let sse: f64 = residuals
+            .iter()
+            .zip(weights.iter())
+            .map(|(residual, weight)| weight * residual.powi(2))
+            .sum();
+        let dof = length - 2;
+
+        let mut report = FitReport {
+            slope: model[0],
+            intercept: model[1],
+            residuals,
+            sse,
+            dof,
+            rmse: None,
+            standard_error: None,
+            rsq: None,
+            adjusted_rsq: None,
+            slope_error: None,
+            intercept_error: None,
+            covariance: None,
+        };
+        if report.is_exact() {
+            return Ok(report);
+        }
+
+        let mean_x = stats::weighted_mean(x, &weights)?;
+        let sxx = stats::weighted_variance(x, &weights)? * n;
+        let sst = stats::weighted_variance(y, &weights)? * n;
+        let standard_error = (sse / dof as f64).sqrt();
+        let rsq = 1.0 - sse / sst;
+
+        report.rmse = Some((sse / n).sqrt());
+        report.standard_error = Some(standard_error);
+        report.rsq = Some(rsq);
+        report.adjusted_rsq = Some(1.0 - (1.0 - rsq) * (n - 1.0) / dof as f64);
+        report.slope_error = Some(standard_error / sxx.sqrt());
+        report.intercept_error = Some(standard_error * (1.0 / n + mean_x.powi(2) / sxx).sqrt());
+
+        let variance = standard_error.powi(2);
+        let covariance = -variance * mean_x / sxx;
+        report.covariance = Some([
+            [variance / sxx, covariance],
+            [covariance, variance * (1.0 / n + mean_x.powi(2) / sxx)],
+        ]);
+        Ok(report)
+    }
+
+    /// Uncertainty of the model, `None` for an exact fit
+    pub fn uncertainty(&self) -> Option<Uncertainty> {
+        Some(Uncertainty {
+            covariance: self.covariance?,
+            standard_error: self.standard_error?,
+            dof: self.dof,
+        })
+    }
+
+    /// Whether the model passes exactly through the points by construction, leaving no residual information
+    pub fn is_exact(&self) -> bool {
+        self.dof == 0
+    }
+}
+
+/// Significance level below which the extra terms of a polynomial are justified
+pub const SIGNIFICANCE: f64 = 0.05;
+
+/// Goodness of fit of a polynomial to x,y data
+///
+/// As for [`FitReport`], a polynomial through as many points as it has coefficients leaves no residual
+/// information, and the statistics derived from the residuals are `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolyFitReport {
+    /// Coefficients in increasing powers of x
+    pub coefficients: Vec<f64>,
+    /// Residual $`y_i - \hat{y}_i`$ of each point
+    pub residuals: Vec<f64>,
+    /// Sum of squared residuals
+    pub sse: f64,
+    /// Degrees of freedom of the residuals, $`n - d - 1`$
+    pub dof: usize,
+    /// Root mean squared error, $`\sqrt{SSE/n}`$
+    pub rmse: Option<f64>,
+    /// Coefficient of determination, $`R^2 = 1 - SSE/SST`$
+    pub rsq: Option<f64>,
+    /// $`R^2`$ adjusted for the number of coefficients, $`1 - (1 - R^2)(n - 1)/(n - d - 1)`$
+    pub adjusted_rsq: Option<f64>,
+}
+
+impl PolyFitReport {
+    /// Evaluates a polynomial with `coefficients` in increasing powers over all the x,y data
+    ///
+    /// Returns an error if x and y differ in length, or there are fewer points than coefficients.
+    pub fn new(x: &[f64], y: &[f64], coefficients: &[f64]) -> Result<PolyFitReport> {
+        if x.len() != y.len() {
+            return Err(Error::LengthMismatch {
+                expected: x.len(),
+                found: y.len(),
+            });
+        }
+        let degree = coefficients.len().saturating_sub(1);
+        if x.len() < coefficients.len() {
+            return Err(Error::TooFewPoints {
+                degree,
+                found: x.len(),
+            });
+        }
+
+        let residuals: Vec<f64> = x
+            .iter()
+            .zip(y.iter())
+            .map(|(x, y)| y - polyval(x, coefficients))
+            .collect();
+        let sse: f64 = residuals.iter().map(|residual| residual.powi(2)).sum();
+        let dof = x.len() - coefficients.len();
+
+        let mut report = PolyFitReport {
+            coefficients: coefficients.to_vec(),
+            residuals,
+            sse,
+            dof,
+            rmse: None,
+            rsq: None,
+            adjusted_rsq: None,
+        };
+        if report.is_exact() {
+            return Ok(report);
+        }
+
+        let n = x.len() as f64;
+        let rsq = 1.0 - sse / (stats::variance(y) * n);
+        report.rmse = Some((sse / n).sqrt());
+        report.rsq = Some(rsq);
+        report.adjusted_rsq = Some(1.0 - (1.0 - rsq) * (n - 1.0) / dof as f64);
+        Ok(report)
+    }
+
+    /// Degree of the polynomial
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+
+    /// Whether the polynomial passes exactly through the points by construction, leaving no residual information
+    pub fn is_exact(&self) -> bool {
+        self.dof == 0
+    }
+}
+
+/// Extra sum of squares F-test of whether the higher terms of a polynomial are justified by the data,
+/// against a polynomial of lower degree fitted to the same points
+///
+/// $`F = \frac{(SSE_r - SSE_f)/(\nu_r - \nu_f)}{SSE_f/\nu_f}`$
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FTest {
+    /// Degree of the full polynomial
+    pub degree: usize,
+    /// Degree of the reduced polynomial it is tested against
+    pub reduced_degree: usize,
+    /// F statistic
+    pub f: f64,
+    /// Degrees of freedom of the numerator and of the denominator
+    pub dof: (usize, usize),
+    /// Probability of an F statistic at least as large were the higher terms zero
+    pub p_value: f64,
+}
+
+impl FTest {
+    /// Tests the `full` polynomial against the `reduced` one.
+    ///
+    /// Returns `None` unless the full polynomial has the higher degree and leaves residual information.
+    pub fn new(reduced: &PolyFitReport, full: &PolyFitReport) -> Option<FTest> {
+        if full.is_exact() || reduced.dof <= full.dof {
+            return None;
+        }
+        let dof = (reduced.dof - full.dof, full.dof);
+        let f = ((reduced.sse - full.sse).max(0.0) / dof.0 as f64) / (full.sse / dof.1 as f64);
+        let p_value = if f.is_finite() {
+            1.0 - stats::f_cdf(f, dof.0 as f64, dof.1 as f64)
+        } else {
+            0.0
+        };
+        Some(FTest {
+            degree: full.degree(),
+            reduced_degree: reduced.degree(),
+            f,
+            dof,
+            p_value,
+        })
+    }
+
+    /// Whether the higher terms are justified at the significance level `alpha`, e.g. [`SIGNIFICANCE`]
+    pub fn is_significant(&self, alpha: f64) -> bool {
+        self.p_value < alpha
+    }
+}
+
+/// Tests each term of a polynomial of `degree` above the linear one, by fitting polynomials of each degree
+/// and testing every one against that of the degree below it
+///
+/// Returns an F-test for each degree from 2 which leaves residual information, or an error as [`polyfit`].
+pub fn polyfit_term_tests(x: &[f64], y: &[f64], degree: usize) -> Result<Vec<FTest>> {
+    let reports = (1..=degree.max(1))
+        .map(|degree| PolyFitReport::new(x, y, &polyfit(x, y, degree)?))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(reports
+        .windows(2)
+        .filter_map(|pair| FTest::new(&pair[0], &pair[1]))
+        .collect())
+}
+
+/// Uncertainty of a linear model, from which the intervals of its predictions are found
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Uncertainty {
+    /// Covariance matrix of the slope and intercept
+    pub covariance: [[f64; 2]; 2],
+    /// Standard error of the regression, $`s`$
+    pub standard_error: f64,
+    /// Degrees of freedom of the residuals
+    pub dof: usize,
+}
+
+impl Uncertainty {
+    /// Half width of the confidence interval of the mean predicted at x, for a confidence `level` between 0 and 1
+    ///
+    /// $`t \sqrt{(x, 1) \, \Sigma \, (x, 1)^T}`$
+    pub fn confidence(&self, x: &f64, level: &f64) -> f64 {
+        self.t_value(level) * self.variance_of_mean(x).sqrt()
+    }
+
+    /// Half width of the prediction interval of a single new observation at x, for a confidence `level` between 0 and 1
+    ///
+    /// $`t \sqrt{s^2 + (x, 1) \, \Sigma \, (x, 1)^T}`$
+    pub fn prediction(&self, x: &f64, level: &f64) -> f64 {
+        self.t_value(level) * (self.standard_error.powi(2) + self.variance_of_mean(x)).sqrt()
+    }
+
+    fn variance_of_mean(&self, x: &f64) -> f64 {
+        let [[slope, covariance], [_, intercept]] = self.covariance;
+        x * x * slope + 2.0 * x * covariance + intercept
+    }
+
+    fn t_value(&self, level: &f64) -> f64 {
+        stats::student_t_quantile(0.5 + 0.5 * level, self.dof as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{
+        fit, fit_weighted, polyfit, polyfit_term_tests, polyval, robust_fit,
+        weights_from_deviations, FTest, FitReport, PolyFitReport, Robust, SIGNIFICANCE,
+    };
+    use crate::error::Error;
+
+    #[test]
+    fn test_fit_report() {
+        let x = [1.0, 2.0, 3.0, 4.0];
+        let y = [3.1, 4.9, 7.2, 8.8];
+        let model = fit(&x, &y).unwrap();
+        let report = FitReport::new(&x, &y, &model).unwrap();
+
+        assert_eq!(report.dof, 2);
+        assert!(approx_eq!(
+            f64,
+            report.residuals.iter().sum::<f64>(),
+            0.0,
+            epsilon = 1e-12
+        ));
+        assert!(approx_eq!(f64, report.sse, 0.082, epsilon = 1e-12));
+        assert!(approx_eq!(
+            f64,
+            report.rsq.unwrap(),
+            0.99566138,
+            epsilon = 1e-8
+        ));
+        assert!(approx_eq!(
+            f64,
+            report.adjusted_rsq.unwrap(),
+            0.99349206,
+            epsilon = 1e-8
+        ));
+        assert!(approx_eq!(
+            f64,
+            report.slope_error.unwrap(),
+            0.09055385,
+            epsilon = 1e-8
+        ));
+        assert!(approx_eq!(
+            f64,
+            report.intercept_error.unwrap(),
+            0.24799194,
+            epsilon = 1e-8
+        ));
+    }
+
+    #[test]
+    fn test_fit_weighted() {
+        let x = [1.0, 2.0, 3.0, 4.0];
+        let y = [3.1, 4.9, 7.2, 8.8];
+
+        // Equal weights, of any size, give the least squares fit
+        let model = fit_weighted(&x, &y, &[3.0; 4]).unwrap();
+        let line = fit(&x, &y).unwrap();
+        assert!(approx_eq!(f64, model[0], line[0], epsilon = 1e-12));
+        let weighted = FitReport::weighted(&x, &y, &model, &[3.0; 4]).unwrap();
+        let report = FitReport::new(&x, &y, &line).unwrap();
+        assert!(approx_eq!(
+            f64,
+            weighted.slope_error.unwrap(),
+            report.slope_error.unwrap(),
+            epsilon = 1e-12
+        ));
+        assert!(approx_eq!(f64, weighted.sse, report.sse, epsilon = 1e-12));
+
+        // A point of negligible weight is all but left out
+        let weights = weights_from_deviations(&[0.01, 0.01, 10.0, 0.01]).unwrap();
+        let model = fit_weighted(&x, &y, &weights).unwrap();
+        let without = fit(&[1.0, 2.0, 4.0], &[3.1, 4.9, 8.8]).unwrap();
+        assert!(approx_eq!(f64, model[0], without[0], epsilon = 1e-5));
+        let weighted = FitReport::weighted(&x, &y, &model, &weights).unwrap();
+        assert_eq!(weighted.dof, 2);
+        assert!(weighted.residuals[2].abs() > 0.3);
+
+        assert!(weights_from_deviations(&[0.01, 0.0]).is_err());
+        assert!(fit_weighted(&x, &y, &[1.0, 1.0]).is_err());
+    }
+
+    #[test]
+    fn test_fit_report_exact() {
+        let x = [3.97, 10.2];
+        let y = [4.01, 10.01];
+        let model = fit(&x, &y).unwrap();
+        let report = FitReport::new(&x, &y, &model).unwrap();
+
+        assert!(report.is_exact());
+        assert_eq!(report.uncertainty(), None);
+        assert_eq!(report.rsq, None);
+        assert_eq!(report.slope_error, None);
+        assert!(approx_eq!(f64, report.sse, 0.0, epsilon = 1e-20));
+    }
+
+    #[test]
+    fn test_polyfit() {
+        // Cubic through readings in mV, far from the origin
+        let x = [-180.0, -90.0, 0.0, 90.0, 180.0, 240.0];
+        let y: Vec<f64> = x
+            .iter()
+            .map(|x| 7.0 - 0.017 * x + 2e-6 * x * x - 1e-9 * x * x * x)
+            .collect();
+        let coefficients = polyfit(&x, &y, 3).unwrap();
+
+        for (expected, coefficient) in [7.0, -0.017, 2e-6, -1e-9].iter().zip(coefficients.iter()) {
+            assert!(approx_eq!(f64, *coefficient, *expected, epsilon = 1e-9));
+        }
+        assert!(approx_eq!(
+            f64,
+            polyval(&50.0, &coefficients),
+            y[2] - 0.85 + 5e-3 - 1.25e-4,
+            epsilon = 1e-9
+        ));
+
+        // Degree 1 is the linear fit
+        let linear = polyfit(&[1.0, 2.0, 3.0, 4.0], &[3.1, 4.9, 7.2, 8.8], 1).unwrap();
+        let line = fit(&[1.0, 2.0, 3.0, 4.0], &[3.1, 4.9, 7.2, 8.8]).unwrap();
+        assert!(approx_eq!(f64, linear[1], line[0], epsilon = 1e-12));
+        assert!(approx_eq!(f64, linear[0], line[1], epsilon = 1e-12));
+
+        assert!(matches!(
+            polyfit(&[1.0, 2.0], &[1.0, 2.0], 2),
+            Err(Error::TooFewPoints {
+                degree: 2,
+                found: 2
+            })
+        ));
+        assert!(polyfit(&[1.0, 1.0, 1.0], &[1.0, 2.0, 3.0], 1).is_err());
+        assert!(polyfit(&[1.0, 2.0, 3.0], &[1.0, 2.0], 1).is_err());
+    }
+
+    #[test]
+    fn test_polyfit_term_tests() {
+        // Curvature well above the noise justifies the quadratic term, but not a cubic one
+        let x = [1.0, 2.0, 4.0, 7.0, 9.0, 10.0];
+        let noise = [0.004, -0.003, 0.002, -0.004, 0.003, -0.002];
+        let y: Vec<f64> = x
+            .iter()
+            .zip(noise.iter())
+            .map(|(x, noise)| 0.2 + 0.95 * x + 0.01 * x * x + noise)
+            .collect();
+        let tests = polyfit_term_tests(&x, &y, 3).unwrap();
+
+        assert_eq!(tests.len(), 2);
+        assert_eq!((tests[0].reduced_degree, tests[0].degree), (1, 2));
+        assert_eq!(tests[0].dof, (1, 3));
+        assert!(tests[0].is_significant(SIGNIFICANCE));
+        assert!(!tests[1].is_significant(SIGNIFICANCE));
+
+        // A straight line gains nothing from the quadratic term
+        let line: Vec<f64> = x
+            .iter()
+            .zip(noise.iter())
+            .map(|(x, noise)| 0.2 + 0.95 * x + noise)
+            .collect();
+        let tests = polyfit_term_tests(&x, &line, 2).unwrap();
+        assert!(!tests[0].is_significant(SIGNIFICANCE));
+
+        // No test without residual information
+        let exact = PolyFitReport::new(&x[..3], &y[..3], &polyfit(&x[..3], &y[..3], 2).unwrap());
+        assert!(exact.unwrap().is_exact());
+        assert!(polyfit_term_tests(&x[..3], &y[..3], 2).unwrap().is_empty());
+        let reduced = PolyFitReport::new(&x, &y, &polyfit(&x, &y, 1).unwrap()).unwrap();
+        assert_eq!(FTest::new(&reduced, &reduced), None);
+    }
+
+    #[test]
+    fn test_robust_fit() {
+        // The buffer at 6.865 is contaminated, reading 0.3 pH high
+        let x = [1.5, 3.81, 6.95, 8.95, 9.77];
+        let y = [1.679, 4.005, 6.865, 9.18, 10.012];
+        let line = fit(&x, &y).unwrap();
+
+        for method in [Robust::Huber, Robust::TheilSen] {
+            let robust = robust_fit(&x, &y, method).unwrap();
+            assert_eq!(robust.outliers(), vec![2]);
+            assert!((robust.model[0] - 1.007).abs() < (line[0] - 1.007).abs());
+            assert!(robust.weights[2] < 0.5);
+            assert!(robust.weights[0] > 0.9);
+        }
+
+        // Two points are fitted exactly, with full weight
+        let exact = robust_fit(&[3.97, 10.2], &[4.01, 10.01], Robust::Huber).unwrap();
+        let line = fit(&[3.97, 10.2], &[4.01, 10.01]).unwrap();
+        assert!(approx_eq!(f64, exact.model[0], line[0], epsilon = 1e-12));
+        assert_eq!(exact.weights, vec![1.0, 1.0]);
+
+        assert!(robust_fit(&[1.0, 1.0], &[1.0, 2.0], Robust::TheilSen).is_err());
+        assert_eq!("theil-sen".parse::<Robust>(), Ok(Robust::TheilSen));
+    }
 }
@@ -33,8 +33,11 @@ Copyright 2021 Peter Dunne */
 //!-----------------
 //!Slope   0.96308
 //!Offset  0.18657
+//!Exact fit, no residual information
+//!-----------------
+//!Slope   61.43 mV/pH     103.8 % pass
+//!Zero    -4.4 mV         pass
 //!-----------------
-//!
 //!```
 //!
 //!Optional temperature argument:
@@ -47,6 +50,32 @@ Copyright 2021 Peter Dunne */
 //!-----------------
 //!Slope   0.96828
 //!Offset  0.16052
+//!Exact fit, no residual information
+//!-----------------
+//!Slope   60.54 mV/pH     103.3 % pass
+//!Zero    -3.7 mV         pass
+//!-----------------
+//!```
+//!
+//!Temperatures are in ˚C unless followed by a unit, e.g. `-t 72.14F` or `-t 295.45K`, or in the unit given with
+//!`--temperature-unit`, one of `celsius`, `fahrenheit` or `kelvin` (or `C`, `F`, `K`). A temperature in another unit is
+//!shown with its value in ˚C, which the buffers are corrected to and `calibration.ph` stores, and
+//!`caliph report --temperature-unit fahrenheit` shows the stored temperatures in ˚F:
+//!
+//!```console
+//!$ caliph 3.97 10.2 -t 72.14F
+//!
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!Temp.   72.14 ˚F (22.3 ˚C)
+//!-----------------
+//!Slope   0.96828
+//!Offset  0.16052
+//!Exact fit, no residual information
+//!-----------------
+//!Slope   60.54 mV/pH     103.3 % pass
+//!Zero    -3.7 mV         pass
 //!-----------------
 //!```
 //!
@@ -60,25 +89,449 @@ Copyright 2021 Peter Dunne */
 //!-----------------
 //!Slope   0.96828
 //!Offset  0.16052
+//!Exact fit, no residual information
+//!-----------------
+//!Slope   60.54 mV/pH     103.3 % pass
+//!Zero    -3.7 mV         pass
 //!-----------------
 //!
 //!Saved to calibration.ph
 //!```
 //!
-//!## Conversion
+//!The calibration is stored as a versioned TOML record, together with the temperature, buffers, readings,
+//!time of calibration, and the electrode identifier given with `-e`:
 //!
-//!Assuming the `calibration.ph` file exists:
+//!```console
+//!$ caliph 3.97 10.2 -t 22.3 -s -e "InLab 413" && cat calibration.ph
+//!...
+//!version = 1
+//!tool_version = "0.1.5"
+//!timestamp = "2021-11-02T10:41:07.574617811Z"
+//!electrode = "InLab 413"
+//!slope = 0.968282504012841
+//!offset = 0.16051845906902074
+//!temperature = 22.3
+//!buffer_set = "technical"
+//!nominal = [4.01, 10.01]
+//!buffers = [4.0046, 10.036999999999999]
+//!readings = [3.97, 10.2]
+//!reading = "ph"
+//!
+//![acceptance]
+//!slope = 103.2756448511372
+//!slope_verdict = "pass"
+//!offset = -3.7236936114530486
+//!offset_verdict = "pass"
+//!verdict = "pass"
+//!
+//![acceptance.limits]
+//!slope_pass = [95.0, 105.0]
+//!slope_warn = [90.0, 110.0]
+//!offset_pass = 15.0
+//!offset_warn = 30.0
+//!```
+//!
+//!Any number of buffer solutions can be used, by giving their nominal pH values with `-b`.
+//!The temperature dependence of each buffer is taken from the buffer set chosen with `--buffer-set`,
+//!either `technical` (4.01, 7.00, 10.01, the default) or `nist`/`din19266` (1.68, 4.01, 6.86, 9.18, 10.01, 12.45).
+//!A buffer not in the set is an error, exit code 8, rather than being used uncorrected:
 //!
 //!```console
-//!$ conph 3.5
+//!$ caliph 1.5 3.8 6.6 8.9 9.8 -b 1.68,4.01,6.86,9.18,10.01 --buffer-set nist
 //!
-//!---------------
-//!  Converting
-//!---------------
-//!Input   3.5
-//!Output  3.5495
-//!---------------
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!Slope   1.00795 ± 0.00540
+//!Offset  0.17958 ± 0.03704
+//!RMSE    0.02903
+//!R²      0.99991
+//!Adj. R² 0.99989
+//!DoF     3
+//!-----------------
+//!Buffer  Reading Residual
+//!1.6790  1.5     -0.0125
+//!4.0050  3.8     -0.0048
+//!6.8650  6.6     +0.0330
+//!9.1800  8.9     +0.0297
+//!10.0120 9.8     -0.0454
+//!-----------------
+//!Slope   58.69 mV/pH     99.2 %  pass
+//!Zero    13.8 mV         pass
+//!-----------------
+//!```
+//!
+//!Replicate readings of a buffer are separated by `/`, and the line is fitted to the mean reading of each buffer. When
+//!every buffer is read in replicate, the fit is weighted by the inverse variance of each mean, so that a buffer whose
+//!readings scatter counts for less. The standard deviation of a buffer is taken to be at least that of the resolution of
+//!its readings, one unit in their last decimal place. Readings starting with a negative one, e.g. of potentials in mV,
+//!follow `--` after the options:
+//!
+//!```console
+//!$ caliph 1.50/1.52/1.49 3.81/3.80/3.82 6.66/6.58/6.72 8.95/8.96/8.95 9.77/9.79/9.76 -b 1.68,4.01,6.86,9.18,10.01 --buffer-set nist
+//!
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!Buffer  Mean    SD      n
+//!1.6790  1.503   0.0153  3
+//!4.0050  3.810   0.0100  3
+//!6.8650  6.653   0.0702  3
+//!9.1800  8.953   0.0058  3
+//!10.0120 9.773   0.0153  3
+//!-----------------
+//!Slope   1.00669 ± 0.00043
+//!Offset  0.16777 ± 0.00339
+//!RMSE    0.00208
+//!R²      1.00000
+//!Adj. R² 1.00000
+//!DoF     3
+//!Weights 1/u² of the replicates
+//!-----------------
+//!Buffer  Reading Residual        Weight
+//!1.6790  1.503   -0.0022         0.44
+//!4.0050  3.810   +0.0017         1.03
+//!6.8650  6.653   -0.0006         0.02
+//!9.1800  8.953   -0.0010         3.08
+//!10.0120 9.773   +0.0055         0.44
+//!-----------------
+//!Slope   58.77 mV/pH     99.3 %  pass
+//!Zero    12.6 mV         pass
+//!-----------------
+//!```
+//!
+//!The mean, standard deviation and number of readings of each buffer read in replicate are listed before the fit. The
+//!most extreme reading of each buffer is tested for an outlier at the 95 % level, by Grubbs' test, or with
+//!`--outlier-test dixon` by Dixon's Q test, and flagged. With `--reject-outliers` the outliers are dropped from the means
+//!before the fit. A reading differing from another by no more than its last digit is never an outlier:
+//!
+//!```console
+//!$ caliph 4.02/4.03/4.02 7.01/7.02/7.01/7.15/7.00 10.03/10.01/10.02 -b 4.01,7.00,10.01 --reject-outliers
+//!
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!Buffer  Mean    SD      n
+//!4.0100  4.023   0.0058  3
+//!7.0000  7.010   0.0082  4       outlier 7.15 (G = 1.78 > 1.72) rejected
+//!10.0100 10.020  0.0100  3
+//!-----------------
+//!Slope   1.00067 ± 0.00034
+//!Offset  -0.01568 ± 0.00215
+//!RMSE    0.00075
+//!R²      1.00000
+//!Adj. R² 1.00000
+//!DoF     1
+//!Weights 1/u² of the replicates
+//!-----------------
+//!Buffer  Reading Residual        Weight
+//!4.0100  4.023   -0.0003         1.50
+//!7.0000  7.010   +0.0010         1.00
+//!10.0100 10.020  -0.0010         0.50
+//!-----------------
+//!Slope   59.12 mV/pH     99.9 %  pass
+//!Zero    -0.7 mV         pass
+//!-----------------
+//!```
+//!
+//!A contaminated or expired buffer drags a least squares line towards it. With `--robust` the line is fitted by the Huber
+//!estimator, which down-weights the buffers with large residuals, or with `--robust=theil-sen` by the median of the slopes
+//!between all pairs of buffers, which rejects them outright. The weight of each buffer is given, and those weighted below
+//!0.5 are flagged as the buffers to replace:
+//!
+//!```console
+//!$ caliph 1.5 3.81 6.95 8.95 9.77 -b 1.68,4.01,6.86,9.18,10.01 --buffer-set nist --robust
+//!
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!Slope   1.00719
+//!Offset  0.16645
+//!RMSE    0.13484
+//!R²      0.99814
+//!Adj. R² 0.99753
+//!DoF     3
+//!Robust  huber
+//!-----------------
+//!Buffer  Reading Residual        Weight
+//!1.6790  1.5     +0.0018         1.00
+//!4.0050  3.81    +0.0011         1.00
+//!6.8650  6.95    -0.3015         0.02    down-weighted
+//!9.1800  8.95    -0.0008         1.00
+//!10.0120 9.77    +0.0053         1.00
+//!-----------------
+//!Slope   58.74 mV/pH     99.3 %  pass
+//!Zero    12.7 mV         pass
+//!-----------------
+//!```
+//!
+//!Bench meters calibrated with more than two buffers often use a separate slope for each range, as electrodes are rarely
+//!equally linear from acid to alkaline. The calibration model is chosen with `--model`: `linear` (the default), `segmented`
+//!with a line between each pair of neighbouring buffers, stored with the readings dividing them, or a `polynomial`.
+//!The model is stored with its parameters, and `conph` converts each reading with it. With `segmented`, each reading is
+//!converted with the segment it falls in, and the outer segments beyond the outer buffers:
+//!
+//!```console
+//!$ caliph 4.05 7.02 9.9 -b 4.01,7.00,10.01 --model segmented
 //!
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!Slope   1.02554 ± 0.01108
+//!Offset  -0.16188 ± 0.08187
+//!RMSE    0.02647
+//!R²      0.99988
+//!Adj. R² 0.99977
+//!DoF     1
+//!-----------------
+//!Buffer  Reading Residual
+//!4.0100  4.05    +0.0184
+//!7.0000  7.02    -0.0374
+//!10.0100 9.9     +0.0190
+//!-----------------
+//!Model   segmented
+//!x < 7.02: pH = 1.00673 x - 0.06727
+//!x ≥ 7.02: pH = 1.04514 x - 0.33687
+//!-----------------
+//!Slope   57.69 mV/pH     97.5 %  pass
+//!Zero    1.0 mV          pass
+//!-----------------
+//!```
+//!
+//!Older electrodes often curve at the alkaline end, leaving systematic residuals about a line. A `polynomial` of the degree
+//!given with `--degree`, 2 by default, is fitted by least squares to at least one more buffer than its degree. Whether each
+//!term above the linear one is justified by the buffers is given by the F-test of the extra sum of squares against the
+//!polynomial of one degree less, with the p-value of the term, justified below 0.05:
+//!
+//!```console
+//!$ caliph 1.69 4.08 6.89 9.08 9.85 -b 1.68,4.01,6.86,9.18,10.01 --buffer-set nist --model polynomial
+//!
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!Slope   1.02247 ± 0.01012
+//!Offset  -0.11176 ± 0.07105
+//!RMSE    0.05366
+//!R²      0.99971
+//!Adj. R² 0.99961
+//!DoF     3
+//!-----------------
+//!Buffer  Reading Residual
+//!1.6790  1.69    +0.0628
+//!4.0050  4.08    -0.0549
+//!6.8650  6.89    -0.0681
+//!9.1800  9.08    +0.0077
+//!10.0120 9.85    +0.0524
+//!-----------------
+//!Model   polynomial
+//!pH = 0.00817 x² + 0.92703 x + 0.08832
+//!RMSE    0.00107
+//!Adj. R² 1.00000
+//!DoF     2
+//!-----------------
+//!Degree  F       p
+//!2       5055.19 0.0002  justified
+//!-----------------
+//!Slope   57.86 mV/pH     97.8 %  pass
+//!Zero    2.6 mV          pass
+//!-----------------
+//!```
+//!
+//!Electrode potentials in mV, e.g. from a data acquisition board, are calibrated with `--mv`. The electrode is described
+//!by E = E0 − S·pH, with its slope S in mV/pH and in % of the theoretical slope RT·ln10/F, and its
+//!asymmetry potential at pH 7. Readings are also described this way for a meter displaying the pH, which assumes a
+//!theoretical electrode with zero potential at pH 7:
+//!
+//!```console
+//!$ caliph 170 -2 -170 -b 4.01,7.00,10.01 --mv
+//!
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!Slope   -0.01765 ± 0.00015
+//!Offset  6.99490 ± 0.02135
+//!RMSE    0.02135
+//!R²      0.99992
+//!Adj. R² 0.99985
+//!DoF     1
+//!-----------------
+//!Buffer  Reading Residual
+//!4.0100  170     +0.0149
+//!7.0000  -2      -0.0302
+//!10.0100 -170    +0.0153
+//!-----------------
+//!Slope   56.67 mV/pH     95.8 %  pass
+//!Zero    -0.3 mV         pass
+//!-----------------
+//!```
+//!
+//!`conph` then converts potentials in mV, read from the stored calibration, or given with `--mv` for a custom calibration.
+//!
+//!Buffers from lot specific certificates can be described in a TOML file, and added to the buffer set with `--buffers-file`.
+//!Buffers in the file replace those with the same nominal pH in the buffer set:
+//!
+//!```toml
+//!name = "lot 2104"
+//!
+//![[buffer]]
+//!name = "phthalate"
+//!nominal = 4.01
+//!interpolation = "linear" # or "cosine", "catmull-rom", "monotone-cubic", "akima"
+//!temperature = [10.0, 20.0, 25.0, 30.0, 40.0]
+//!ph = [3.998, 4.001, 4.006, 4.012, 4.028]
+//!```
+//!
+//!```console
+//!$ caliph 3.9 9.9 -t 22 --buffers-file lot2104.toml
+//!```
+//!
+//!A buffer can also be described by an equation, pH = A/T + B + C·T + D·T² with T in kelvin, which gives its pH
+//!smoothly at any temperature in its range. A buffer in the file is described by the coefficients of its equation and
+//!the temperatures in ˚C it is valid for, instead of a table. The built in `nist-equations` set describes the DIN 19266
+//!/ NIST standards by least squares fits of the equation to their tables, within 0.002 pH of every point:
+//!
+//!```toml
+//![[buffer]]
+//!name = "phosphate"
+//!nominal = 6.86
+//!equation = { a = 3453.25, b = -20.9993, c = 0.0731207, d = -6.20826e-5, range = [0.0, 95.0] }
+//!```
+//!
+//!The pH of each buffer is interpolated in its table of the temperature dependence, and a temperature outside the table
+//!is an error by default, exit code 5. With `--temperature-policy clamp` the pH at the nearest end of the table is used
+//!instead, and with `--temperature-policy extrapolate` the table is extended by the line through its two nearest points.
+//!Either gives a warning for each buffer used beyond its table, written in the unit of the temperature and stored in ˚C
+//!in the calibration record together with the policy:
+//!
+//!```console
+//!$ caliph 4.1 9.9 -t 97 --temperature-policy clamp
+//!
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!warning: 97 ˚C is outside the range of the pH 4.01 buffer, from 0 ˚C to 95 ˚C, clamped to pH 4.2000 at 95 ˚C
+//!warning: 97 ˚C is outside the range of the pH 10.01 buffer, from 0 ˚C to 95 ˚C, clamped to pH 9.7600 at 95 ˚C
+//!-----------------
+//!Slope   0.95862
+//!Offset  0.26966
+//!Exact fit, no residual information
+//!-----------------
+//!Slope   76.62 mV/pH     104.3 % pass
+//!Zero    -1.5 mV         pass
+//!-----------------
+//!```
+//!
+//!The buffer tables are interpolated linearly between their points by default, which leaves a kink at each point where
+//!the table bends, e.g. where the pH 10.01 buffer turns around near 80 ˚C. With `--interpolation` every table is
+//!interpolated by `linear`, `cosine`, `catmull-rom`, `monotone-cubic` or `akima` instead, which is stored with the
+//!calibration. The monotone cubic never overshoots the table, and is the safer of the smooth interpolations. Whether the
+//!choice matters at a temperature is shown by `routines::interpolation_differences`, which differ by about 0.001 pH for the
+//!pH 10.01 buffer at 82.5 ˚C.
+//!
+//!Each calibration is judged on the electrode it describes, by its slope in % of the Nernstian slope, and its zero point
+//!offset, the potential in mV at pH 7. By default a slope within 95 – 105 % and an offset within ±15 mV pass, and a
+//!slope within 90 – 110 % and an offset within ±30 mV pass with a warning. The limits are set with `--slope-limits`,
+//!`--slope-warn-limits`, `--offset-limit` and `--offset-warn-limit`. The verdict is stored with the calibration,
+//!and `caliph` exits with code 11 when the calibration fails:
+//!
+//!```console
+//!$ caliph 3.5 10.5
+//!
+//!-----------------
+//!  Calibrating
+//!-----------------
+//!Slope   0.85714
+//!Offset  1.01000
+//!Exact fit, no residual information
+//!-----------------
+//!Slope   69.02 mV/pH     116.7 % fail
+//!Zero    0.7 mV          pass
+//!-----------------
+//!error: calibration rejected: slope 116.7 % is outside 90 – 110 %
+//!```
+//!
+//!Between full calibrations, a quick check in one buffer is enough to follow the drift of the offset. With
+//!`--one-point` the offset of the calibration stored in `calibration.ph` is adjusted to a single buffer, keeping the
+//!slope of the full calibration. The stored record is updated, and marked as a partial calibration by an `[adjustment]`
+//!table holding the buffer, reading and previous offset, and the time of the full calibration:
+//!
+//!```console
+//!$ caliph 6.90 -b 7.00 --one-point
+//!
+//!-----------------
+//!  Adjusting
+//!-----------------
+//!Buffer  Reading
+//!7.0000  6.9
+//!-----------------
+//!Slope   1.01652 kept from 2021-11-02 10:41 UTC
+//!Offset  0.00961 → -0.01400
+//!-----------------
+//!Slope   58.20 mV/pH     98.4 %  pass
+//!Zero    5.9 mV          pass
+//!-----------------
+//!
+//!Saved to calibration.ph
+//!```
+//!
+//!The buffer may be read at another temperature to the full calibration with `-t`. The offset is then solved for the
+//!slope compensated to that temperature, as `conph -t` compensates it, so converting the reading at that temperature
+//!gives the buffer pH.
+//!
+//!`caliph report` summarises the calibration stored in `calibration.ph`. With `-u` it gives the measurement uncertainty
+//!budget of the calibration, following the GUM (JCGM 100), for reports under ISO/IEC 17025. Each buffer certificate,
+//!the temperature, and the resolution and repeatability of every reading contribute their standard uncertainty u(xi),
+//!weighted by the sensitivity ci of the result to it. The budget is given for the pH converted from `--reading`, by
+//!default the reading of a solution at pH 7, and for the slope and offset. The inputs are propagated through the
+//!calibration as it was fitted, weighted or robust, and through its segmented or polynomial model. The standard
+//!uncertainties of the sources are set with `--u-buffer`, `--u-temperature`, `--resolution` and `--repeatability`. The
+//!budget is checked by the Monte Carlo method (JCGM 101) with `--monte-carlo`, reproducibly from `--seed`:
+//!
+//!```console
+//!$ caliph 4.05 7.02 9.95 -b 4.01,7.00,10.01 -t 22.3 -s -e "InLab 413" && caliph report -u --monte-carlo 100000 --seed 7
+//!...
+//!
+//!-----------------
+//!  Calibration
+//!-----------------
+//!Time        2021-11-02 10:41:07 UTC
+//!Electrode   InLab 413
+//!Temperature 22.3 ˚C
+//!Slope       1.02242
+//!Offset      -0.14627
+//!-----------------
+//!Buffer      Reading
+//!4.0046      4.05 pH
+//!7.0108      7.02 pH
+//!10.0370     9.95 pH
+//!-----------------
+//!Verdict     pass
+//!-----------------
+//!  Uncertainty budget
+//!-----------------
+//!Source                           u(xi)  Distribution        ci     ui(y)
+//!buffer 4.00                     0.0100  normal          0.3362   0.00336
+//!buffer 7.01                     0.0100  normal          0.3333   0.00333
+//!buffer 10.04                    0.0100  normal          0.3304   0.00330
+//!temperature                     0.1000  normal         -0.0040   0.00040
+//!reading 4.05 resolution         0.0029  rectangular    -0.3438   0.00099
+//!reading 4.05 repeatability      0.0100  normal         -0.3438   0.00344
+//!reading 7.02 resolution         0.0029  rectangular    -0.3408   0.00098
+//!reading 7.02 repeatability      0.0100  normal         -0.3408   0.00341
+//!reading 9.95 resolution         0.0029  rectangular    -0.3379   0.00098
+//!reading 9.95 repeatability      0.0100  normal         -0.3379   0.00338
+//!sample resolution               0.0029  rectangular     1.0224   0.00295
+//!sample repeatability            0.0100  normal          1.0224   0.01022
+//!-----------------
+//!Sample      6.9896 pH
+//!pH          7.0000    u = 0.0136    U = 0.0272 (k = 2)
+//!Slope       1.02242   u = 0.00351   U = 0.00701 (k = 2)
+//!Offset      -0.14627  u = 0.02595   U = 0.05191 (k = 2)
+//!-----------------
+//!Monte Carlo 100000 samples, seed 7
+//!pH          7.0000 ± 0.0135
+//!95 %        6.9735 – 7.0264
+//!-----------------
 //!```
 //!
 //!Custom calibration settings fof the slope and offset:
@@ -91,12 +544,112 @@ Copyright 2021 Peter Dunne */
 //!---------------
 //!  Converting
 //!---------------
-//!Input   3.5
+//!Input   3.5 pH
 //!Output  3.8700
 //!---------------
+//!```
 //!
+//!When the sample is measured at a different temperature to the calibration, `-t` compensates the slope of the
+//!electrode for the change in Nernstian slope around the isopotential point at pH 7. The calibration temperature is read
+//!from `calibration.ph`, or given with `--calibration-temperature` for custom calibrations (25˚C if unknown):
+//!
+//!```console
+//!$ conph 3.5 -c -s 1.1 -o 0.02 -t 40
+//!
+//!---------------
+//!  Converting
+//!---------------
+//!Input   3.5 pH
+//!Output  4.0544
+//!Temp.   40 ˚C (calibrated at 25 ˚C)
+//!---------------
 //!```
 //!
+//!`-t` and `--calibration-temperature` take a unit in the same way as for `caliph`, e.g. `-t 104F`, and the temperatures
+//!are shown in the unit given with `--temperature-unit`, or else in that of the temperature:
+//!
+//!```console
+//!$ conph 3.5 -c -s 1.1 -o 0.02 -t 104F
+//!
+//!---------------
+//!  Converting
+//!---------------
+//!Input   3.5 pH
+//!Output  4.0544
+//!Temp.   104 ˚F (calibrated at 77 ˚F)
+//!---------------
+//!```
+//!
+//!With a calibration of more than two buffers, `-u` gives the interval of each converted pH from the uncertainty of
+//!the fit, at the confidence level given with `--level` (95 % by default). The prediction interval of the single sample
+//!measured is given by default, or the confidence interval of the mean pH with `--interval confidence`:
+//!
+//!```console
+//!$ caliph 1.5 3.8 6.6 8.9 9.8 -b 1.68,4.01,6.86,9.18,10.01 --buffer-set nist -s > /dev/null
+//!$ conph 7 -u
+//!
+//!---------------
+//!  Converting
+//!---------------
+//!Input   7 pH
+//!Output  7.2352 ± 0.1315 (95 %)
+//!Calibrated      2021-11-02 10:41:07 UTC
+//!---------------
+//!```
+//!
+//!Without a pH value, the measurements are read one per line from stdin, or from a file given with `-i`:
+//!
+//!```console
+//!$ printf '3.5\n4.0\n' | conph -c -s 1.1 -o 0.02
+//!3.8700
+//!4.4200
+//!```
+//!
+//!For CSV files, `--column` selects the column of measurements by name, or by position counting from 1.
+//!All columns are passed through, with the converted values appended as a new column:
+//!
+//!```console
+//!$ conph -c -s 1.1 -o 0.02 -i samples.csv --column ph
+//!sample,ph,ph_calibrated
+//!A,3.5,3.8700
+//!B,4.0,4.4200
+//!```
+//!
+//!### Output formats
+//!
+//!Both tools take `-f/--format` with `table` (the default for `caliph` and single pH values), `json`, `csv`, `tsv` or `plain`
+//!(the default for batches). The schema of each format is stable, see the `output` module of the library.
+//!Colour is disabled when stdout is not a terminal, or when `NO_COLOR` is set.
+//!
+//!```console
+//!$ caliph 3.97 10.2 -f plain
+//!0.9630818619582667 0.18656500802568043
+//!$ printf '3.5\n4.0\n' | conph -c -s 1.1 -o 0.02 -f csv
+//!input,output
+//!3.5,3.8700000000000006
+//!4.0,4.42
+//!```
+//!
+//!### Exit codes
+//!
+//!Errors are printed to stderr, and each kind of error has its own exit code:
+//!
+//!| Code | Error                                                |
+//!|------|------------------------------------------------------|
+//!| 1    | other failure                                        |
+//!| 2    | invalid command line arguments                       |
+//!| 3    | numbers of readings and buffers differ               |
+//!| 4    | undetermined fit, the readings do not vary           |
+//!| 5    | temperature outside the range of a buffer            |
+//!| 6    | value not finite                                     |
+//!| 7    | malformed calibration file                           |
+//!| 8    | invalid or unknown buffer                            |
+//!| 9    | invalid input value                                  |
+//!| 10   | file or stream could not be read or written          |
+//!| 11   | calibration failed its acceptance criteria           |
+//!| 12   | calibration has no uncertainty                       |
+//!| 13   | too few points for the degree of a polynomial        |
+//!
 //!## Installing
 //!
 //!The latest version of can be installed or updated with `cargo install`:
@@ -127,48 +680,175 @@ Copyright 2021 Peter Dunne */
 //!For `caliph`:
 //!
 //!```text
-//!caliph 0.1.3
+//!caliph 0.1.5
 //!Peter Dunne
-//!Calculates corrections from 2 point pH calibration
+//!Calculates corrections from a multi-point pH calibration
 //!
 //!USAGE:
-//!    caliph [FLAGS] [OPTIONS] <ph4> <ph10>
+//!    caliph [OPTIONS] <ph_measured>...
+//!    caliph [OPTIONS] <SUBCOMMAND>
 //!
-//!FLAGS:
-//!    -h, --help       Prints help information
-//!    -s, --store      Store calibration to file calib.ph
-//!    -V, --version    Prints version information
+//!ARGS:
+//!    <ph_measured>...    pH, or potential in mV with --mv, measured for each buffer solution,
+//!                        with any replicate readings separated by /
 //!
 //!OPTIONS:
-//!    -t, --temperature <temperature>    temperature of measurement
+//!    -b, --buffers <buffers>
+//!            comma separated nominal pH of each buffer solution [default: 4.01,10.01]
 //!
-//!ARGS:
-//!    <ph4>     pH measured for pH 4.01 buffer solution
-//!    <ph10>    pH measured for pH 10.01 buffer solution
+//!        --buffer-set <buffer_set>
+//!            set of buffer solutions used [default: technical] [possible values: technical, nist,
+//!            din19266, nist-equations]
+//!
+//!        --buffers-file <buffers_file>
+//!            TOML file of custom buffer solutions, added to the buffer set
+//!
+//!        --degree <degree>
+//!            degree of the polynomial model, below the number of buffers [default: 2]
+//!
+//!    -e, --electrode <electrode>
+//!            identifier of the electrode, stored with the calibration
+//!
+//!    -f, --format <format>
+//!            output format [default: table] [possible values: table, json, csv, tsv, plain]
+//!
+//!    -h, --help
+//!            Print help information
+//!
+//!        --interpolation <interpolation>
+//!            interpolation of the buffer tables in temperature [default: that of each buffer, linear
+//!            for the built in sets] [possible values: linear, cosine, catmull-rom, monotone-cubic,
+//!            akima]
+//!
+//!        --model <model>
+//!            calibration model fitted to the buffers [default: linear] [possible values: linear,
+//!            segmented, polynomial]
+//!
+//!        --mv
+//!            Readings are electrode potentials in mV
+//!
+//!        --offset-limit <offset_limit>
+//!            largest zero point offset of the electrode which passes, in mV [default: 15]
+//!
+//!        --offset-warn-limit <offset_warn_limit>
+//!            largest zero point offset of the electrode which passes with a warning, in mV [default:
+//!            30]
+//!
+//!        --one-point
+//!            Adjust the offset of the calibration stored in calibration.ph to a single buffer,
+//!            keeping its slope
+//!
+//!        --outlier-test <outlier_test>
+//!            test for an outlier among the replicate readings of each buffer [default: grubbs]
+//!            [possible values: grubbs, dixon]
+//!
+//!        --reject-outliers
+//!            leave the outliers found by the test out of the mean readings
+//!
+//!        --robust[=<ESTIMATOR>...]
+//!            fit the line robustly, flagging outlying buffers [default: huber] [possible values:
+//!            huber, theil-sen]
+//!
+//!    -s, --store
+//!            Store calibration to file calibration.ph
+//!
+//!        --slope-limits <MIN>,<MAX>
+//!            range of the electrode slope which passes, in % of the Nernstian slope [default: 95,105]
+//!
+//!        --slope-warn-limits <MIN>,<MAX>
+//!            range of the electrode slope which passes with a warning, in % of the Nernstian slope
+//!            [default: 90,110]
+//!
+//!    -t, --temperature <temperature>
+//!            temperature of measurement, in the unit of --temperature-unit unless followed by one,
+//!            e.g. 77F [default: 25]
+//!
+//!        --temperature-policy <temperature_policy>
+//!            handling of a temperature outside the table of a buffer, refusing to calibrate, or using
+//!            the nearest end of the table or extending it with a warning [default: error] [possible
+//!            values: error, clamp, extrapolate]
+//!
+//!        --temperature-unit <temperature_unit>
+//!            unit of the temperatures given and shown [default: that of --temperature, or celsius]
+//!            [possible values: celsius, fahrenheit, kelvin]
+//!
+//!    -V, --version
+//!            Print version information
+//!
+//!SUBCOMMANDS:
+//!    help      Print this message or the help of the given subcommand(s)
+//!    report    Reports on the calibration stored in calibration.ph
 //!```
 //!
 //!and for `conph`
 //!
 //!```text
-//!conph 0.1.3
+//!conph 0.1.5
 //!Peter Dunne
 //!Corrects pH measurement with calibration
 //!
 //!USAGE:
-//!    conph [FLAGS] [OPTIONS] <ph>
+//!    conph [OPTIONS] [ph]
 //!
-//!FLAGS:
-//!    -c, --custom     Custom Input
-//!    -h, --help       Prints help information
-//!    -V, --version    Prints version information
+//!ARGS:
+//!    <ph>    pH, or potential in mV, measured, if absent the values are read from the input
 //!
 //!OPTIONS:
-//!    -o, --offset <offset>              Offset
-//!    -s, --slope <slope>                Slope
-//!    -t, --temperature <temperature>    Temperature of measurement
+//!    -c, --custom
+//!            Custom Input
 //!
-//!ARGS:
-//!    <ph>    pH measured
+//!        --calibration-temperature <calibration_temperature>
+//!            Temperature of the custom calibration [default: 25 ˚C]
+//!
+//!        --column <column>
+//!            Name, or position counting from 1, of the CSV column of measured pH values
+//!
+//!    -d, --delimiter <delimiter>
+//!            Delimiter of the CSV input, a single character or 'tab' [default: ,]
+//!
+//!    -f, --format <format>
+//!            Output format [default: table for a single pH value, plain otherwise] [possible values:
+//!            table, json, csv, tsv, plain]
+//!
+//!    -h, --help
+//!            Print help information
+//!
+//!    -i, --input <input>
+//!            File of measured pH values, one per line or CSV, - for stdin [default: -]
+//!
+//!        --interval <interval>
+//!            Kind of interval, of the mean pH or of the single sample [default: prediction] [possible
+//!            values: confidence, prediction]
+//!
+//!        --level <level>
+//!            Confidence level of the interval, in % [default: 95]
+//!
+//!        --mv
+//!            Custom calibration converts electrode potentials in mV
+//!
+//!        --no-header
+//!            CSV input has no header row
+//!
+//!    -o, --offset <offset>
+//!            Offset
+//!
+//!    -s, --slope <slope>
+//!            Slope
+//!
+//!    -t, --temperature <temperature>
+//!            Temperature of measurement, in the unit of --temperature-unit unless followed by one,
+//!            e.g. 77F
+//!
+//!        --temperature-unit <temperature_unit>
+//!            Unit of the temperatures given and shown [default: that of --temperature, or celsius]
+//!            [possible values: celsius, fahrenheit, kelvin]
+//!
+//!    -u, --uncertainty
+//!            Give the interval of each converted pH value, from a calibration of more than two
+//!            buffers
+//!
+//!    -V, --version
+//!            Print version information
 //!```
 //!
 //!## License
@@ -188,10 +868,23 @@ Copyright 2021 Peter Dunne */
 //! use libcaliph::args::CalibArgs;
 //! use libcaliph::routines::ph_convert;
 //! ```
+pub mod acceptance;
 pub mod args;
+pub mod batch;
+pub mod budget;
+pub mod buffers;
+pub mod electrode;
+pub mod error;
 pub mod fit;
+pub mod model;
+pub mod output;
+pub mod record;
+pub mod replicate;
 pub mod routines;
 pub mod stats;
+pub mod temperature;
+
+pub use error::{Error, Result};
 
 /// Temperature points for pH buffer solutions dependent curves.
 ///
@@ -209,10 +902,110 @@ static PH4_STATIC: [f64; 20] = [
     4.17, 4.19, 4.2,
 ];
 
-/// 4.01 pH buffer solutions temperature dependence
+/// 10.01 pH buffer solutions temperature dependence
 ///
 /// This is in the stack for the lifetime of the program
 static PH10_STATIC: [f64; 20] = [
     10.32, 10.25, 10.18, 10.12, 10.06, 10.01, 9.96, 9.92, 9.88, 9.85, 9.82, 9.79, 9.77, 9.76, 9.75,
     9.74, 9.73, 9.74, 9.75, 9.76,
 ];
+
+/// 7.00 pH technical buffer solutions temperature dependence
+///
+/// This is in the stack for the lifetime of the program
+static PH7_STATIC: [f64; 20] = [
+    7.12, 7.09, 7.06, 7.04, 7.02, 7.0, 6.99, 6.98, 6.97, 6.97, 6.97, 6.97, 6.98, 6.98, 6.99, 7.0,
+    7.0, 7.01, 7.02, 7.03,
+];
+
+/// Temperature points for the DIN 19266 / NIST primary pH standards.
+///
+/// Buffers with a narrower range of validity use the leading part of this table.
+static TEMP_DIN_STATIC: [f64; 16] = [
+    0.0, 5.0, 10.0, 15.0, 20.0, 25.0, 30.0, 35.0, 37.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0, 95.0,
+];
+
+/// 1.68 pH potassium tetroxalate primary standard temperature dependence
+static PH168_DIN_STATIC: [f64; 16] = [
+    1.666, 1.668, 1.670, 1.672, 1.675, 1.679, 1.683, 1.688, 1.691, 1.694, 1.707, 1.723, 1.743,
+    1.766, 1.792, 1.806,
+];
+
+/// 4.01 pH potassium hydrogen phthalate primary standard temperature dependence
+static PH401_DIN_STATIC: [f64; 16] = [
+    4.000, 3.998, 3.997, 3.998, 4.000, 4.005, 4.011, 4.018, 4.022, 4.027, 4.050, 4.080, 4.116,
+    4.159, 4.205, 4.227,
+];
+
+/// 6.86 pH phosphate primary standard temperature dependence
+static PH686_DIN_STATIC: [f64; 16] = [
+    6.984, 6.951, 6.923, 6.900, 6.881, 6.865, 6.853, 6.844, 6.841, 6.838, 6.833, 6.836, 6.845,
+    6.859, 6.876, 6.886,
+];
+
+/// 9.18 pH borax primary standard temperature dependence
+static PH918_DIN_STATIC: [f64; 16] = [
+    9.464, 9.395, 9.332, 9.276, 9.225, 9.180, 9.139, 9.102, 9.088, 9.068, 9.011, 8.962, 8.921,
+    8.885, 8.850, 8.833,
+];
+
+/// 10.01 pH carbonate primary standard temperature dependence, valid from 0 to 50˚C
+static PH1001_DIN_STATIC: [f64; 11] = [
+    10.317, 10.245, 10.179, 10.118, 10.062, 10.012, 9.966, 9.926, 9.910, 9.889, 9.828,
+];
+
+/// 12.45 pH calcium hydroxide primary standard temperature dependence, valid from 0 to 60˚C
+static PH1245_DIN_STATIC: [f64; 12] = [
+    13.423, 13.207, 13.003, 12.810, 12.627, 12.454, 12.289, 12.133, 12.072, 11.984, 11.705, 11.449,
+];
+
+/// Coefficients $`A, B, C, D`$ of $`pH = A/T + B + C T + D T^2`$ for the 1.68 pH potassium tetroxalate primary standard.
+///
+/// The coefficients of the primary standards are least squares fits to the DIN 19266 tables above, over the same
+/// temperatures, and are within 0.002 pH of them. They are not the equations published with the standards.
+static PH168_DIN_EQUATION: [f64; 4] = [
+    -302.283_458_8,
+    5.591_348_083,
+    -0.016_837_178_01,
+    2.386_533_148e-5,
+];
+
+/// Coefficients fitted to the table of the 4.01 pH potassium hydrogen phthalate primary standard
+static PH401_DIN_EQUATION: [f64; 4] = [
+    467.904_452_8,
+    2.028_726_759,
+    -0.003_582_151_836,
+    1.658_593_149e-5,
+];
+
+/// Coefficients fitted to the table of the 6.86 pH phosphate primary standard
+static PH686_DIN_EQUATION: [f64; 4] = [
+    3_453.249_766,
+    -20.999_302_27,
+    0.073_120_665_18,
+    -6.208_263_608e-5,
+];
+
+/// Coefficients fitted to the table of the 9.18 pH borax primary standard
+static PH918_DIN_EQUATION: [f64; 4] = [
+    5_267.998_242,
+    -33.196_319_13,
+    0.115_123_067_0,
+    -1.081_831_504e-4,
+];
+
+/// Coefficients fitted to the table of the 10.01 pH carbonate primary standard, from 0 to 50˚C
+static PH1001_DIN_EQUATION: [f64; 4] = [
+    2_329.572_053,
+    -2.000_223_667,
+    0.011_554_328_63,
+    8.480_626_945e-6,
+];
+
+/// Coefficients fitted to the table of the 12.45 pH calcium hydroxide primary standard, from 0 to 60˚C
+static PH1245_DIN_EQUATION: [f64; 4] = [
+    7_566.034_259,
+    -38.123_350_08,
+    0.117_691_795_5,
+    -1.112_474_563e-4,
+];
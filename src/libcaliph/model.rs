@@ -0,0 +1,811 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Model Module
+//! Provides the [`PhModel`] trait of a calibration model converting readings to pH, and its implementations:
+//!
+//! - `linear`, the [`Calibration`] $`pH = m x + c`$ through all the buffers,
+//! - `segmented`, the [`SegmentedCalibration`] with a line between each pair of neighbouring buffers,
+//! - `polynomial`, the [`PolynomialCalibration`] $`pH = a_0 + a_1 x + \dots + a_d x^d`$.
+//!
+//! A model is stored in the calibration record by its name and parameters, see [`ModelRecord`]. Other models
+//! implement the trait, and are added to a [`ModelRegistry`] to be fitted and loaded by name. Options of a fit, such as
+//! the degree of a polynomial, are given as [`FitOptions`] to the models which take them.
+
+use super::electrode::Reading;
+use super::error::{Error, Result};
+use super::fit::{self, polyfit_term_tests, polyval, FTest, PolyFitReport};
+use super::routines::{
+    ph_calibration_points, ph_calibration_segmented, Calibration, SegmentedCalibration,
+    ISOPOTENTIAL_PH, ZERO_CELSIUS,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Names of the built in models
+pub const MODEL_NAMES: [&str; 3] = ["linear", "segmented", "polynomial"];
+
+/// Degree of a polynomial model fitted without a degree
+pub const DEFAULT_DEGREE: usize = 2;
+
+/// Options of a fit beyond the readings and buffers, which only some models take
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FitOptions {
+    /// Degree of a polynomial, [`DEFAULT_DEGREE`] if not given
+    pub degree: Option<usize>,
+}
+
+/// Calibration model converting readings to pH
+pub trait PhModel: fmt::Debug {
+    /// Fits the model to pairs of readings and buffer pH values
+    fn fit(readings: &[f64], ph_buffer: &[f64]) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Fits the model with options it takes, see [`PhModel::takes`]
+    fn fit_with(readings: &[f64], ph_buffer: &[f64], _options: &FitOptions) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::fit(readings, ph_buffer)
+    }
+
+    /// Whether the model is fitted with the options, by default only when none are given
+    fn takes(options: &FitOptions) -> bool
+    where
+        Self: Sized,
+    {
+        *options == FitOptions::default()
+    }
+
+    /// Loads the model from its stored parameters
+    fn from_parameters(parameters: &toml::Value) -> Result<Self>
+    where
+        Self: Sized;
+
+    /// Name of the model, under which it is stored
+    fn name(&self) -> &str;
+
+    /// Parameters of the model, to be stored
+    fn to_parameters(&self) -> Result<toml::Value>;
+
+    /// Converts a reading to pH
+    fn convert(&self, reading: &f64) -> f64;
+
+    /// Reading giving the pH, the inverse of [`PhModel::convert`]
+    fn inverse(&self, ph: &f64) -> Result<f64>;
+
+    /// Description of the model and its parameters, e.g. `pH = 1.02554 x - 0.16188`
+    fn describe(&self) -> String;
+
+    /// Model rescaled around a pivot pH, $`pH' = p + r (pH - p)`$, used to compensate for temperature
+    fn rescale(&self, ratio: &f64, pivot: &f64) -> Box<dyn PhModel>;
+
+    /// Model converting the reading rescaled around a pivot, $`pH' = f(p + r (x - p))`$, used to compensate a
+    /// reading of pH for temperature
+    fn rescale_reading(&self, ratio: &f64, pivot: &f64) -> Box<dyn PhModel>;
+
+    /// Options fitting a model of the same form to other readings, e.g. the degree of a polynomial
+    fn options(&self) -> FitOptions {
+        FitOptions::default()
+    }
+
+    /// Goodness of fit to the readings and buffers, for a model which is not a line
+    fn fit_report(&self, _readings: &[f64], _ph_buffer: &[f64]) -> Result<Option<PolyFitReport>> {
+        Ok(None)
+    }
+
+    /// F-test of each term the model adds to a line, as for [`fit::polyfit_term_tests`]
+    fn term_tests(&self, _readings: &[f64], _ph_buffer: &[f64]) -> Result<Vec<FTest>> {
+        Ok(Vec::new())
+    }
+
+    /// Name and parameters of the model, to be stored in a calibration record
+    fn to_record(&self) -> Result<ModelRecord> {
+        Ok(ModelRecord {
+            name: self.name().to_string(),
+            parameters: self.to_parameters()?,
+        })
+    }
+}
+
+/// Stored model, see [`crate::record`]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModelRecord {
+    /// Name of the model
+    pub name: String,
+    /// Parameters of the model, as given by [`PhModel::to_parameters`]
+    pub parameters: toml::Value,
+}
+
+type FitModel = fn(&[f64], &[f64], &FitOptions) -> Result<Box<dyn PhModel>>;
+type LoadModel = fn(&toml::Value) -> Result<Box<dyn PhModel>>;
+type TakesOptions = fn(&FitOptions) -> bool;
+
+/// Models which can be fitted and loaded by name
+pub struct ModelRegistry {
+    models: Vec<(String, FitModel, LoadModel, TakesOptions)>,
+}
+
+impl ModelRegistry {
+    /// Registry without any models
+    pub fn empty() -> ModelRegistry {
+        ModelRegistry { models: Vec::new() }
+    }
+
+    /// Adds a model under the name it is stored with, replacing any model of the same name
+    pub fn register<M: PhModel + 'static>(&mut self, name: &str) {
+        self.models.retain(|(other, _, _, _)| other != name);
+        self.models
+            .push((name.to_string(), fit_boxed::<M>, load_boxed::<M>, M::takes));
+    }
+
+    /// Names of the registered models
+    pub fn names(&self) -> Vec<&str> {
+        self.models
+            .iter()
+            .map(|(name, _, _, _)| name.as_str())
+            .collect()
+    }
+
+    /// Fits the named model to pairs of readings and buffer pH values
+    pub fn fit(&self, name: &str, readings: &[f64], ph_buffer: &[f64]) -> Result<Box<dyn PhModel>> {
+        self.fit_with(name, readings, ph_buffer, &FitOptions::default())
+    }
+
+    /// Fits the named model with options, returning an error if it does not take them
+    pub fn fit_with(
+        &self,
+        name: &str,
+        readings: &[f64],
+        ph_buffer: &[f64],
+        options: &FitOptions,
+    ) -> Result<Box<dyn PhModel>> {
+        if !self.takes(name, options)? {
+            return Err(Error::InvalidInput {
+                line: None,
+                message: format!("the {} model is fitted without these options", name),
+            });
+        }
+        let (_, fit, _, _) = self.find(name)?;
+        fit(readings, ph_buffer, options)
+    }
+
+    /// Fits a model of the same name and options as `model` to other readings and buffer pH values
+    pub fn refit(
+        &self,
+        model: &dyn PhModel,
+        readings: &[f64],
+        ph_buffer: &[f64],
+    ) -> Result<Box<dyn PhModel>> {
+        self.fit_with(model.name(), readings, ph_buffer, &model.options())
+    }
+
+    /// Whether the named model is fitted with the options
+    pub fn takes(&self, name: &str, options: &FitOptions) -> Result<bool> {
+        let (_, _, _, takes) = self.find(name)?;
+        Ok(takes(options))
+    }
+
+    /// Loads a stored model
+    pub fn load(&self, record: &ModelRecord) -> Result<Box<dyn PhModel>> {
+        let (_, _, load, _) = self.find(&record.name)?;
+        load(&record.parameters)
+    }
+
+    fn find(&self, name: &str) -> Result<&(String, FitModel, LoadModel, TakesOptions)> {
+        self.models
+            .iter()
+            .find(|(other, _, _, _)| other == name)
+            .ok_or_else(|| Error::MalformedCalibration(format!("unknown model {}", name)))
+    }
+}
+
+impl Default for ModelRegistry {
+    /// Registry of the built in models
+    fn default() -> Self {
+        let mut registry = ModelRegistry::empty();
+        registry.register::<Calibration<f64>>("linear");
+        registry.register::<SegmentedCalibration>("segmented");
+        registry.register::<PolynomialCalibration>("polynomial");
+        registry
+    }
+}
+
+fn fit_boxed<M: PhModel + 'static>(
+    readings: &[f64],
+    ph_buffer: &[f64],
+    options: &FitOptions,
+) -> Result<Box<dyn PhModel>> {
+    Ok(Box::new(M::fit_with(readings, ph_buffer, options)?))
+}
+
+fn load_boxed<M: PhModel + 'static>(parameters: &toml::Value) -> Result<Box<dyn PhModel>> {
+    Ok(Box::new(M::from_parameters(parameters)?))
+}
+
+/// Rescales a model calibrated at `calibration_temperature` for a measurement made at `temperature`.
+///
+/// The Nernstian slope of an electrode is proportional to the absolute temperature, so it is scaled by
+/// $`T_{cal}/T`$ around the isopotential point. For readings in mV the pH is scaled around the electrode at pH 7, and
+/// for a meter displaying the pH the reading is scaled around 7 before the model converts it, which for a linear model
+/// is [`crate::routines::temperature_compensation`].
+pub fn compensate(
+    model: &dyn PhModel,
+    reading: Reading,
+    calibration_temperature: &f64,
+    temperature: &f64,
+) -> Box<dyn PhModel> {
+    let ratio = (calibration_temperature + ZERO_CELSIUS) / (temperature + ZERO_CELSIUS);
+    match reading {
+        Reading::Ph => model.rescale_reading(&ratio, &ISOPOTENTIAL_PH),
+        Reading::Millivolt => model.rescale(&ratio, &ISOPOTENTIAL_PH),
+    }
+}
+
+/// Parameters of a linear model
+#[derive(Serialize, Deserialize)]
+struct LinearParameters {
+    slope: f64,
+    offset: f64,
+}
+
+impl PhModel for Calibration<f64> {
+    fn fit(readings: &[f64], ph_buffer: &[f64]) -> Result<Self> {
+        ph_calibration_points(readings, ph_buffer)
+    }
+
+    fn from_parameters(parameters: &toml::Value) -> Result<Self> {
+        let parameters: LinearParameters = from_value(parameters)?;
+        check_parameters(&[parameters.slope, parameters.offset])?;
+        Ok(Calibration::new(
+            parameters.slope,
+            parameters.offset,
+            None,
+            None,
+        ))
+    }
+
+    fn name(&self) -> &str {
+        "linear"
+    }
+
+    fn to_parameters(&self) -> Result<toml::Value> {
+        to_value(&LinearParameters {
+            slope: self.slope,
+            offset: self.offset,
+        })
+    }
+
+    fn convert(&self, reading: &f64) -> f64 {
+        fit::predict(reading, &[self.slope, self.offset])
+    }
+
+    fn inverse(&self, ph: &f64) -> Result<f64> {
+        if self.slope == 0.0 {
+            return Err(Error::DegenerateFit);
+        }
+        Ok((ph - self.offset) / self.slope)
+    }
+
+    fn describe(&self) -> String {
+        format!("pH = {}", terms(&[self.offset, self.slope]))
+    }
+
+    fn rescale(&self, ratio: &f64, pivot: &f64) -> Box<dyn PhModel> {
+        Box::new(Calibration::new(
+            self.slope * ratio,
+            pivot + ratio * (self.offset - pivot),
+            self.rms,
+            self.rsq,
+        ))
+    }
+
+    fn rescale_reading(&self, ratio: &f64, pivot: &f64) -> Box<dyn PhModel> {
+        Box::new(Calibration::new(
+            self.slope * ratio,
+            self.offset + self.slope * pivot * (1.0 - ratio),
+            self.rms,
+            self.rsq,
+        ))
+    }
+}
+
+impl PhModel for SegmentedCalibration {
+    fn fit(readings: &[f64], ph_buffer: &[f64]) -> Result<Self> {
+        ph_calibration_segmented(readings, ph_buffer)
+    }
+
+    fn from_parameters(parameters: &toml::Value) -> Result<Self> {
+        let parameters: SegmentedCalibration = from_value(parameters)?;
+        SegmentedCalibration::new(parameters.breakpoints, parameters.segments)
+    }
+
+    fn name(&self) -> &str {
+        "segmented"
+    }
+
+    fn to_parameters(&self) -> Result<toml::Value> {
+        to_value(self)
+    }
+
+    fn convert(&self, reading: &f64) -> f64 {
+        fit::predict(reading, self.segment(reading))
+    }
+
+    fn inverse(&self, ph: &f64) -> Result<f64> {
+        // The reading must fall in the segment it was inverted with
+        self.segments
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| segment[0] != 0.0)
+            .map(|(index, segment)| (index, (ph - segment[1]) / segment[0]))
+            .find(|(index, reading)| {
+                let above = *index == 0 || *reading >= self.breakpoints[index - 1];
+                let below = *index == self.breakpoints.len() || *reading < self.breakpoints[*index];
+                above && below
+            })
+            .map(|(_, reading)| reading)
+            .ok_or_else(|| Error::InvalidInput {
+                line: None,
+                message: format!("no reading gives pH {}", ph),
+            })
+    }
+
+    fn describe(&self) -> String {
+        self.segments
+            .iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                let below = index.checked_sub(1).map(|below| self.breakpoints[below]);
+                let range = match (below, self.breakpoints.get(index)) {
+                    (None, Some(above)) => format!("x < {}", above),
+                    (Some(below), None) => format!("x ≥ {}", below),
+                    (Some(below), Some(above)) => format!("{} ≤ x < {}", below, above),
+                    (None, None) => "all x".to_string(),
+                };
+                format!("{}: pH = {}", range, terms(&[segment[1], segment[0]]))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn rescale(&self, ratio: &f64, pivot: &f64) -> Box<dyn PhModel> {
+        Box::new(SegmentedCalibration {
+            breakpoints: self.breakpoints.clone(),
+            segments: self
+                .segments
+                .iter()
+                .map(|segment| [segment[0] * ratio, pivot + ratio * (segment[1] - pivot)])
+                .collect(),
+        })
+    }
+
+    /// Moves each breakpoint to the reading rescaled onto it, keeping the segments continuous
+    fn rescale_reading(&self, ratio: &f64, pivot: &f64) -> Box<dyn PhModel> {
+        let shift = pivot * (1.0 - ratio);
+        Box::new(SegmentedCalibration {
+            breakpoints: self
+                .breakpoints
+                .iter()
+                .map(|breakpoint| (breakpoint - shift) / ratio)
+                .collect(),
+            segments: self
+                .segments
+                .iter()
+                .map(|segment| [segment[0] * ratio, segment[1] + segment[0] * shift])
+                .collect(),
+        })
+    }
+}
+
+/// Polynomial calibration, $`pH = a_0 + a_1 x + \dots + a_d x^d`$
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PolynomialCalibration {
+    /// Coefficients in increasing powers of the reading
+    pub coefficients: Vec<f64>,
+}
+
+impl PolynomialCalibration {
+    /// Least squares polynomial calibration of the given degree through pairs of readings and buffer pH values
+    pub fn fit_degree(
+        readings: &[f64],
+        ph_buffer: &[f64],
+        degree: usize,
+    ) -> Result<PolynomialCalibration> {
+        Ok(PolynomialCalibration {
+            coefficients: fit::polyfit(readings, ph_buffer, degree)?,
+        })
+    }
+
+    /// Degree of the polynomial
+    pub fn degree(&self) -> usize {
+        self.coefficients.len().saturating_sub(1)
+    }
+}
+
+impl PhModel for PolynomialCalibration {
+    /// Fits a polynomial of the default degree, see [`PolynomialCalibration::fit_degree`] for any other
+    fn fit(readings: &[f64], ph_buffer: &[f64]) -> Result<Self> {
+        PolynomialCalibration::fit_degree(readings, ph_buffer, DEFAULT_DEGREE)
+    }
+
+    fn fit_with(readings: &[f64], ph_buffer: &[f64], options: &FitOptions) -> Result<Self> {
+        let degree = options.degree.unwrap_or(DEFAULT_DEGREE);
+        PolynomialCalibration::fit_degree(readings, ph_buffer, degree)
+    }
+
+    fn takes(_options: &FitOptions) -> bool {
+        true
+    }
+
+    fn from_parameters(parameters: &toml::Value) -> Result<Self> {
+        let parameters: PolynomialCalibration = from_value(parameters)?;
+        if parameters.coefficients.is_empty() {
+            return Err(Error::MalformedCalibration(
+                "polynomial without coefficients".to_string(),
+            ));
+        }
+        check_parameters(&parameters.coefficients)?;
+        Ok(parameters)
+    }
+
+    fn name(&self) -> &str {
+        "polynomial"
+    }
+
+    fn to_parameters(&self) -> Result<toml::Value> {
+        to_value(self)
+    }
+
+    fn convert(&self, reading: &f64) -> f64 {
+        polyval(reading, &self.coefficients)
+    }
+
+    /// Solves for the reading by Newton's method, starting from the linear terms
+    fn inverse(&self, ph: &f64) -> Result<f64> {
+        let derivative: Vec<f64> = self
+            .coefficients
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(power, coefficient)| power as f64 * coefficient)
+            .collect();
+        let mut reading = match self.coefficients.get(1) {
+            Some(slope) if *slope != 0.0 => (ph - self.coefficients[0]) / slope,
+            _ => 0.0,
+        };
+        for _ in 0..100 {
+            let gradient = polyval(&reading, &derivative);
+            if gradient == 0.0 || !gradient.is_finite() {
+                break;
+            }
+            let step = (self.convert(&reading) - ph) / gradient;
+            reading -= step;
+            if step.abs() <= 1e-12 * reading.abs().max(1.0) {
+                return Ok(reading);
+            }
+        }
+        Err(Error::InvalidInput {
+            line: None,
+            message: format!("no reading gives pH {}", ph),
+        })
+    }
+
+    fn describe(&self) -> String {
+        format!("pH = {}", terms(&self.coefficients))
+    }
+
+    fn options(&self) -> FitOptions {
+        FitOptions {
+            degree: Some(self.degree()),
+        }
+    }
+
+    fn fit_report(&self, readings: &[f64], ph_buffer: &[f64]) -> Result<Option<PolyFitReport>> {
+        PolyFitReport::new(readings, ph_buffer, &self.coefficients).map(Some)
+    }
+
+    fn term_tests(&self, readings: &[f64], ph_buffer: &[f64]) -> Result<Vec<FTest>> {
+        polyfit_term_tests(readings, ph_buffer, self.degree())
+    }
+
+    fn rescale(&self, ratio: &f64, pivot: &f64) -> Box<dyn PhModel> {
+        let mut coefficients: Vec<f64> = self
+            .coefficients
+            .iter()
+            .map(|coefficient| coefficient * ratio)
+            .collect();
+        if let Some(constant) = coefficients.first_mut() {
+            *constant += pivot * (1.0 - ratio);
+        }
+        Box::new(PolynomialCalibration { coefficients })
+    }
+
+    /// Substitutes the rescaled reading $`(1 - r) p + r x`$ into the polynomial by Horner's method
+    fn rescale_reading(&self, ratio: &f64, pivot: &f64) -> Box<dyn PhModel> {
+        let shift = pivot * (1.0 - ratio);
+        let mut coefficients: Vec<f64> = Vec::with_capacity(self.coefficients.len());
+        for coefficient in self.coefficients.iter().rev() {
+            // Multiplies the polynomial so far by (shift + ratio x), then adds the coefficient
+            coefficients.push(0.0);
+            for power in (0..coefficients.len()).rev() {
+                let lower = if power > 0 {
+                    coefficients[power - 1]
+                } else {
+                    0.0
+                };
+                coefficients[power] = coefficients[power] * shift + lower * ratio;
+            }
+            coefficients[0] += coefficient;
+        }
+        Box::new(PolynomialCalibration { coefficients })
+    }
+}
+
+/// Polynomial with coefficients in increasing powers of x, written from the highest power
+fn terms(coefficients: &[f64]) -> String {
+    let mut terms = String::new();
+    for (power, coefficient) in coefficients.iter().enumerate().rev() {
+        let variable = match power {
+            0 => String::new(),
+            1 => " x".to_string(),
+            2 => " x²".to_string(),
+            3 => " x³".to_string(),
+            power => format!(" x^{}", power),
+        };
+        let magnitude = format!("{:.5e}", coefficient.abs());
+        let magnitude = if (1e-3..1e3).contains(&coefficient.abs()) {
+            format!("{:.5}", coefficient.abs())
+        } else {
+            magnitude
+        };
+        if terms.is_empty() {
+            let sign = if *coefficient < 0.0 { "-" } else { "" };
+            terms.push_str(&format!("{}{}{}", sign, magnitude, variable));
+        } else {
+            let sign = if *coefficient < 0.0 { '-' } else { '+' };
+            terms.push_str(&format!(" {} {}{}", sign, magnitude, variable));
+        }
+    }
+    terms
+}
+
+/// Checks the stored parameters are finite
+fn check_parameters(parameters: &[f64]) -> Result<()> {
+    if parameters.iter().all(|parameter| parameter.is_finite()) {
+        Ok(())
+    } else {
+        Err(Error::NonFinite("model parameters".to_string()))
+    }
+}
+
+fn to_value<T: Serialize>(parameters: &T) -> Result<toml::Value> {
+    toml::Value::try_from(parameters).map_err(|err| Error::MalformedCalibration(err.to_string()))
+}
+
+fn from_value<'de, T: Deserialize<'de>>(parameters: &toml::Value) -> Result<T> {
+    parameters
+        .clone()
+        .try_into()
+        .map_err(|err: toml::de::Error| Error::MalformedCalibration(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{
+        compensate, FitOptions, ModelRegistry, PhModel, PolynomialCalibration, MODEL_NAMES,
+    };
+    use crate::electrode::{Electrode, Reading};
+    use crate::error::Result;
+    use crate::routines::{
+        temperature_compensation, Calibration, SegmentedCalibration, ISOPOTENTIAL_PH, ZERO_CELSIUS,
+    };
+
+    const READINGS: [f64; 5] = [1.5, 3.8, 6.6, 8.9, 9.8];
+    const BUFFERS: [f64; 5] = [1.679, 4.005, 6.865, 9.18, 10.012];
+
+    #[test]
+    fn test_models_round_trip() {
+        let registry = ModelRegistry::default();
+        assert_eq!(registry.names(), MODEL_NAMES.to_vec());
+
+        for name in MODEL_NAMES.iter() {
+            let model = registry.fit(name, &READINGS, &BUFFERS).unwrap();
+            assert_eq!(model.name(), *name);
+
+            let loaded = registry.load(&model.to_record().unwrap()).unwrap();
+            for reading in [0.0, 5.0, 7.0, 12.0].iter() {
+                assert!(approx_eq!(
+                    f64,
+                    loaded.convert(reading),
+                    model.convert(reading),
+                    epsilon = 1e-12
+                ));
+                let ph = model.convert(reading);
+                assert!(approx_eq!(
+                    f64,
+                    model.inverse(&ph).unwrap(),
+                    *reading,
+                    epsilon = 1e-9
+                ));
+            }
+        }
+        assert!(registry
+            .load(&super::ModelRecord {
+                name: "spline".to_string(),
+                parameters: toml::Value::Table(Default::default()),
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_models_fit() {
+        let segmented = SegmentedCalibration::fit(&READINGS, &BUFFERS).unwrap();
+        for (reading, buffer) in READINGS.iter().zip(BUFFERS.iter()) {
+            assert!(approx_eq!(
+                f64,
+                segmented.convert(reading),
+                *buffer,
+                epsilon = 1e-12
+            ));
+        }
+
+        // A quadratic through points on a parabola
+        let readings = [1.0, 4.0, 7.0, 10.0];
+        let buffers: Vec<f64> = readings
+            .iter()
+            .map(|x| 0.1 + 0.9 * x + 0.01 * x * x)
+            .collect();
+        let polynomial = PolynomialCalibration::fit(&readings, &buffers).unwrap();
+        assert_eq!(polynomial.degree(), 2);
+        assert!(approx_eq!(
+            f64,
+            polynomial.coefficients[2],
+            0.01,
+            epsilon = 1e-12
+        ));
+        assert_eq!(
+            polynomial.describe(),
+            "pH = 0.01000 x² + 0.90000 x + 0.10000"
+        );
+
+        let linear = Calibration::fit(&[3.97, 10.2], &[4.01, 10.01]).unwrap();
+        assert_eq!(linear.describe(), "pH = 0.96308 x + 0.18657");
+    }
+
+    #[test]
+    fn test_registry_options() {
+        let registry = ModelRegistry::default();
+        let cubic = FitOptions { degree: Some(3) };
+        assert!(registry.takes("polynomial", &cubic).unwrap());
+        assert!(!registry.takes("linear", &cubic).unwrap());
+        assert!(registry
+            .fit_with("segmented", &READINGS, &BUFFERS, &cubic)
+            .is_err());
+
+        // Refitted to other buffers, a model keeps its degree
+        let model = registry
+            .fit_with("polynomial", &READINGS, &BUFFERS, &cubic)
+            .unwrap();
+        assert_eq!(model.options(), cubic);
+        let refitted = registry.refit(model.as_ref(), &READINGS, &[1.68, 4.01, 6.86, 9.18, 10.01]);
+        assert_eq!(refitted.unwrap().options(), cubic);
+        assert_eq!(model.term_tests(&READINGS, &BUFFERS).unwrap().len(), 2);
+
+        let linear = registry.fit("linear", &READINGS, &BUFFERS).unwrap();
+        assert!(linear.fit_report(&READINGS, &BUFFERS).unwrap().is_none());
+        assert!(linear.term_tests(&READINGS, &BUFFERS).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_compensate() {
+        let linear = Calibration::new(1.02, 0.05, None, None);
+
+        // Meters displaying the pH are compensated around the reading of 7
+        let compensated = compensate(&linear, Reading::Ph, &25.0, &40.0);
+        let expected = temperature_compensation(&[1.02, 0.05], &25.0, &40.0);
+        assert!(approx_eq!(
+            f64,
+            compensated.convert(&4.0),
+            expected[0] * 4.0 + expected[1],
+            epsilon = 1e-12
+        ));
+
+        // Potentials in mV are compensated as the electrode they describe
+        let millivolt = Calibration::new(-1.0 / 57.0, 7.2, None, None);
+        let compensated = compensate(&millivolt, Reading::Millivolt, &25.0, &40.0);
+        let [slope, offset] = Electrode::from_calibration(&millivolt, Reading::Millivolt, &25.0)
+            .at_temperature(&40.0)
+            .millivolt_calibration();
+        assert!(approx_eq!(
+            f64,
+            compensated.convert(&100.0),
+            slope * 100.0 + offset,
+            epsilon = 1e-12
+        ));
+
+        // Segments stay continuous at their breakpoints
+        let segmented = SegmentedCalibration::fit(&READINGS, &BUFFERS).unwrap();
+        let compensated = compensate(&segmented, Reading::Ph, &25.0, &40.0);
+        assert!(approx_eq!(
+            f64,
+            compensated.convert(&(6.6 - 1e-9)),
+            compensated.convert(&6.6),
+            epsilon = 1e-6
+        ));
+
+        // A reading of pH is compensated about 7 before any model converts it, as for the linear model
+        let ratio = (25.0 + ZERO_CELSIUS) / (40.0 + ZERO_CELSIUS);
+        let polynomial = PolynomialCalibration::fit_degree(&READINGS, &BUFFERS, 3).unwrap();
+        for model in [&segmented as &dyn PhModel, &polynomial] {
+            let compensated = compensate(model, Reading::Ph, &25.0, &40.0);
+            for reading in [2.0, 4.1, 6.6, 7.0, 9.5, 12.0] {
+                let rescaled = ISOPOTENTIAL_PH + ratio * (reading - ISOPOTENTIAL_PH);
+                assert!(approx_eq!(
+                    f64,
+                    compensated.convert(&reading),
+                    model.convert(&rescaled),
+                    epsilon = 1e-9
+                ));
+            }
+        }
+    }
+
+    /// Model with a fixed offset, as added by a user of the crate
+    #[derive(Debug)]
+    struct Offset(f64);
+
+    impl PhModel for Offset {
+        fn fit(readings: &[f64], ph_buffer: &[f64]) -> Result<Self> {
+            Ok(Offset(ph_buffer[0] - readings[0]))
+        }
+
+        fn from_parameters(parameters: &toml::Value) -> Result<Self> {
+            Ok(Offset(parameters["offset"].as_float().unwrap_or_default()))
+        }
+
+        fn name(&self) -> &str {
+            "offset"
+        }
+
+        fn to_parameters(&self) -> Result<toml::Value> {
+            let mut parameters = toml::value::Table::new();
+            parameters.insert("offset".to_string(), toml::Value::Float(self.0));
+            Ok(toml::Value::Table(parameters))
+        }
+
+        fn convert(&self, reading: &f64) -> f64 {
+            reading + self.0
+        }
+
+        fn inverse(&self, ph: &f64) -> Result<f64> {
+            Ok(ph - self.0)
+        }
+
+        fn describe(&self) -> String {
+            format!("pH = x + {}", self.0)
+        }
+
+        fn rescale(&self, _ratio: &f64, _pivot: &f64) -> Box<dyn PhModel> {
+            Box::new(Offset(self.0))
+        }
+
+        fn rescale_reading(&self, _ratio: &f64, _pivot: &f64) -> Box<dyn PhModel> {
+            Box::new(Offset(self.0))
+        }
+    }
+
+    #[test]
+    fn test_registry_custom_model() {
+        let mut registry = ModelRegistry::default();
+        registry.register::<Offset>("offset");
+
+        let model = registry.fit("offset", &[6.9], &[7.0]).unwrap();
+        let loaded = registry.load(&model.to_record().unwrap()).unwrap();
+        assert!(approx_eq!(f64, loaded.convert(&4.0), 4.1, epsilon = 1e-12));
+    }
+}
@@ -0,0 +1,298 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Output Module
+//! Provides the machine readable output formats of `caliph` and `conph`.
+//!
+//! The schema of each format is stable:
+//!
+//! | Format  | `caliph`                                                            | `conph`                        |
+//! |---------|---------------------------------------------------------------------|--------------------------------|
+//! | `json`  | the calibration record, see [`crate::record`]                       | array of `{"input", "output"}` |
+//! | `csv`   | `slope,offset,rms,rsq,temperature,nominal,buffer,reading,verdict`, one row per buffer | `input,output` |
+//! | `tsv`   | as `csv`, separated by tabs                                         | as `csv`, separated by tabs    |
+//! | `plain` | `slope offset` on a single line                                     | one output per line            |
+//!
+//! With an uncertainty, each conversion of `conph` also has the half width of its interval `uncertainty`, and its
+//! confidence `level` in %, given after the output in `plain`.
+//!
+//! Missing values are left empty in `csv` and `tsv`, and are omitted in `json`.
+//!
+//! The schema does not change with `--temperature-unit`, the temperature is always in ˚C.
+
+use super::error::Result;
+use super::record::CalibrationRecord;
+use serde::Serialize;
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::str::FromStr;
+use termcolor::ColorChoice;
+
+/// Names of the output formats
+pub const FORMAT_NAMES: [&str; 5] = ["table", "json", "csv", "tsv", "plain"];
+
+/// Output format of the command line tools
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Decorated table for reading in a terminal
+    Table,
+    Json,
+    Csv,
+    Tsv,
+    /// Bare values, separated by whitespace
+    Plain,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Table => "table",
+            Format::Json => "json",
+            Format::Csv => "csv",
+            Format::Tsv => "tsv",
+            Format::Plain => "plain",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "plain" => Ok(Format::Plain),
+            _ => Err(format!("Unknown format {}", s)),
+        }
+    }
+}
+
+/// Colour of the terminal output, disabled when stdout is not a terminal or `NO_COLOR` is set
+pub fn color_choice() -> ColorChoice {
+    let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    if no_color || !io::stdout().is_terminal() {
+        ColorChoice::Never
+    } else {
+        ColorChoice::Auto
+    }
+}
+
+/// Measured and converted pH value
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct Conversion {
+    pub input: f64,
+    pub output: f64,
+    /// Half width of the interval of the output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uncertainty: Option<f64>,
+    /// Confidence level of the interval, in %
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<f64>,
+}
+
+impl Conversion {
+    pub fn new(input: f64, output: f64) -> Conversion {
+        Conversion {
+            input,
+            output,
+            uncertainty: None,
+            level: None,
+        }
+    }
+
+    /// Modifies the interval of the output, with its confidence level in %
+    pub fn with_uncertainty(&self, uncertainty: f64, level: f64) -> Conversion {
+        Conversion {
+            uncertainty: Some(uncertainty),
+            level: Some(level),
+            ..*self
+        }
+    }
+}
+
+/// Writes a calibration in one of the machine readable formats
+///
+/// # Panics
+///
+/// Panics if the format is `Format::Table`, which is written by the binaries themselves.
+pub fn write_calibration<W: Write>(
+    mut writer: W,
+    record: &CalibrationRecord,
+    format: Format,
+) -> Result<()> {
+    match format {
+        Format::Table => unreachable!("tables are written by the binaries"),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut writer, record)?;
+            writeln!(writer)?;
+        }
+        Format::Csv | Format::Tsv => {
+            let mut csv_writer = csv::WriterBuilder::new()
+                .delimiter(delimiter(format))
+                .from_writer(writer);
+            csv_writer.write_record([
+                "slope",
+                "offset",
+                "rms",
+                "rsq",
+                "temperature",
+                "nominal",
+                "buffer",
+                "reading",
+                "verdict",
+            ])?;
+            for (index, reading) in record.readings.iter().enumerate() {
+                csv_writer.write_record([
+                    record.slope.to_string(),
+                    record.offset.to_string(),
+                    optional(record.rms),
+                    optional(record.rsq),
+                    optional(record.temperature),
+                    optional(record.nominal.get(index).copied()),
+                    optional(record.buffers.get(index).copied()),
+                    reading.to_string(),
+                    record
+                        .acceptance
+                        .as_ref()
+                        .map(|acceptance| acceptance.verdict.to_string())
+                        .unwrap_or_default(),
+                ])?;
+            }
+            csv_writer.flush()?;
+        }
+        Format::Plain => writeln!(writer, "{} {}", record.slope, record.offset)?,
+    }
+    Ok(())
+}
+
+/// Writes measured and converted pH values in one of the machine readable formats
+///
+/// # Panics
+///
+/// Panics if the format is `Format::Table`, which is written by the binaries themselves.
+pub fn write_conversions<W: Write>(
+    mut writer: W,
+    conversions: &[Conversion],
+    format: Format,
+) -> Result<()> {
+    match format {
+        Format::Table => unreachable!("tables are written by the binaries"),
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut writer, conversions)?;
+            writeln!(writer)?;
+        }
+        Format::Csv | Format::Tsv => {
+            let mut csv_writer = csv::WriterBuilder::new()
+                .delimiter(delimiter(format))
+                .from_writer(writer);
+            for conversion in conversions {
+                csv_writer.serialize(conversion)?;
+            }
+            csv_writer.flush()?;
+        }
+        Format::Plain => {
+            for conversion in conversions {
+                match conversion.uncertainty {
+                    Some(uncertainty) => writeln!(
+                        writer,
+                        "{:.4} {:.4} {}",
+                        conversion.output,
+                        uncertainty,
+                        conversion.level.unwrap_or_default()
+                    )?,
+                    None => writeln!(writer, "{:.4}", conversion.output)?,
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn delimiter(format: Format) -> u8 {
+    match format {
+        Format::Tsv => b'\t',
+        _ => b',',
+    }
+}
+
+fn optional(value: Option<f64>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_calibration, write_conversions, Conversion, Format};
+    use crate::record::CalibrationRecord;
+
+    #[test]
+    fn test_format_from_str() {
+        assert_eq!("JSON".parse::<Format>().unwrap(), Format::Json);
+        assert!("xml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn test_write_calibration() {
+        let mut record = CalibrationRecord::legacy(1.25, 0.5);
+        record.temperature = Some(25.0);
+        record.nominal = vec![4.01, 10.01];
+        record.buffers = vec![4.01, 10.01];
+        record.readings = vec![3.9, 9.8];
+
+        let mut output = Vec::new();
+        write_calibration(&mut output, &record, Format::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "slope,offset,rms,rsq,temperature,nominal,buffer,reading,verdict\n\
+             1.25,0.5,,,25,4.01,4.01,3.9,\n\
+             1.25,0.5,,,25,10.01,10.01,9.8,\n"
+        );
+
+        let mut output = Vec::new();
+        write_calibration(&mut output, &record, Format::Plain).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "1.25 0.5\n");
+
+        let mut output = Vec::new();
+        write_calibration(&mut output, &record, Format::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(value["slope"], 1.25);
+        assert_eq!(value["readings"][1], 9.8);
+    }
+
+    #[test]
+    fn test_write_conversions() {
+        let conversions = [Conversion::new(3.5, 3.87), Conversion::new(4.0, 4.42)];
+        let mut output = Vec::new();
+        write_conversions(&mut output, &conversions, Format::Tsv).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "input\toutput\n3.5\t3.87\n4.0\t4.42\n"
+        );
+
+        let mut output = Vec::new();
+        write_conversions(&mut output, &conversions[..1], Format::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(value[0]["output"], 3.87);
+        assert!(value[0].get("uncertainty").is_none());
+    }
+
+    #[test]
+    fn test_write_conversions_uncertainty() {
+        let conversions = [Conversion::new(3.5, 3.87).with_uncertainty(0.031, 95.0)];
+        let mut output = Vec::new();
+        write_conversions(&mut output, &conversions, Format::Csv).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "input,output,uncertainty,level\n3.5,3.87,0.031,95.0\n"
+        );
+
+        let mut output = Vec::new();
+        write_conversions(&mut output, &conversions, Format::Plain).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "3.8700 0.0310 95\n");
+    }
+}
@@ -0,0 +1,608 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Record Module
+//! Provides the calibration record stored by `caliph -s` and read by `conph`.
+//!
+//! The record is a versioned TOML file, which keeps the calibration together with the
+//! measurements and buffers it was made from:
+//!
+//! ```toml
+//! version = 1
+//! tool_version = "0.1.5"
+//! timestamp = "2021-11-02T10:41:07Z"
+//! electrode = "InLab 413"
+//! slope = 0.96828
+//! offset = 0.16052
+//! temperature = 22.3
+//! buffer_set = "technical"
+//! nominal = [4.01, 10.01]
+//! buffers = [4.0046, 10.037]
+//! readings = [3.97, 10.2]
+//! reading = "ph"
+//! temperature_policy = "error"
+//!
+//! [acceptance]
+//! slope = 103.28
+//! slope_verdict = "pass"
+//! offset = -3.72
+//! offset_verdict = "pass"
+//! verdict = "pass"
+//!
+//! [acceptance.limits]
+//! slope_pass = [95.0, 105.0]
+//! slope_warn = [90.0, 110.0]
+//! offset_pass = 15.0
+//! offset_warn = 30.0
+//! ```
+//!
+//! Buffers read in replicate keep all their readings as `replicates = [[4.02, 4.03, 4.02], ...]`, of which
+//! `readings` holds the means. Buffers used beyond their tables by a `clamp` or `extrapolate` temperature policy,
+//! see [`crate::routines::TemperaturePolicy`], are noted in `temperature_warnings`.
+//!
+//! A one-point calibration, see [`crate::routines::ph_calibration_one_point`], adjusts the offset of the stored
+//! record, which is then marked as a partial calibration by an `[adjustment]` table:
+//!
+//! ```toml
+//! [adjustment]
+//! calibrated = "2021-11-02T10:41:07Z"
+//! previous_offset = 0.16052
+//! nominal = 7.0
+//! buffer = 7.0
+//! reading = 6.95
+//! temperature = 25.0
+//! ```
+//!
+//! Any other model, see [`crate::model`], is stored as a `[model]` table of its `name` and `parameters`,
+//! and is then used for conversions instead of the slope and offset, e.g. a segmented calibration:
+//!
+//! ```toml
+//! [model]
+//! name = "segmented"
+//!
+//! [model.parameters]
+//! breakpoints = [7.02]
+//! segments = [[1.00673, -0.06727], [1.04514, -0.33687]]
+//! ```
+//!
+//! Legacy files holding only the slope and offset, separated by whitespace, can still be read.
+
+use super::acceptance::Acceptance;
+use super::buffers::InterpolationMethod;
+use super::electrode::Reading;
+use super::error::{Error, Result};
+use super::fit::{Robust, RobustFit, Uncertainty};
+use super::model::{ModelRecord, ModelRegistry, PhModel};
+use super::replicate::Replicates;
+use super::routines::{Calibration, RangeWarning, TemperaturePolicy};
+use super::temperature::TemperatureUnit;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Version of the calibration record format written by this version of the library
+pub const RECORD_VERSION: u32 = 1;
+
+/// Default file name of the calibration record
+pub const RECORD_FILE: &str = "calibration.ph";
+
+/// One-point adjustment of the offset of a stored calibration, whose slope is kept
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Adjustment {
+    /// Time of the full calibration whose slope is kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calibrated: Option<DateTime<Utc>>,
+    /// Offset of the calibration before the adjustment
+    pub previous_offset: f64,
+    /// Nominal pH of the buffer
+    pub nominal: f64,
+    /// pH of the buffer at the temperature of the adjustment
+    pub buffer: f64,
+    /// Reading of the buffer, in the unit of the record
+    pub reading: f64,
+    /// Temperature of the adjustment, in ˚C
+    pub temperature: f64,
+    /// Warning of the buffer clamped or extrapolated beyond its table, see [`TemperaturePolicy`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+}
+
+/// Calibration together with its provenance
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CalibrationRecord {
+    /// Version of the record format, 0 for legacy files
+    pub version: u32,
+    /// Version of the tool that wrote the record
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+    /// Time the calibration was made
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Identifier of the calibrated electrode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub electrode: Option<String>,
+    pub slope: f64,
+    pub offset: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rms: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rsq: Option<f64>,
+    /// Temperature of the calibration, in ˚C
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Name of the buffer set used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub buffer_set: Option<String>,
+    /// Interpolation of every buffer table, absent for the interpolation of each buffer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interpolation: Option<InterpolationMethod>,
+    /// Nominal pH of each buffer
+    #[serde(default)]
+    pub nominal: Vec<f64>,
+    /// pH of each buffer at the calibration temperature
+    #[serde(default)]
+    pub buffers: Vec<f64>,
+    /// Reading for each buffer, in the unit of `reading`
+    #[serde(default)]
+    pub readings: Vec<f64>,
+    /// Replicate readings of each buffer, of which `readings` are the means, if read in replicate
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replicates: Vec<Vec<f64>>,
+    /// Quantity read from the meter, pH if absent
+    #[serde(default)]
+    pub reading: Reading,
+    /// Handling of a temperature outside the table of a buffer, an error if absent
+    #[serde(default)]
+    pub temperature_policy: TemperaturePolicy,
+    /// Warnings of the buffers clamped or extrapolated beyond their tables, in ˚C
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub temperature_warnings: Vec<String>,
+    /// Robust estimator of the calibration, absent for least squares
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub robust: Option<Robust>,
+    /// Weight of each reading in a weighted or robust calibration
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weights: Vec<f64>,
+    /// One-point adjustment of the offset, if the calibration was last adjusted rather than made in full
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adjustment: Option<Adjustment>,
+    /// Model used for conversions instead of the linear calibration, if calibrated with `--model`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<ModelRecord>,
+    /// Uncertainty of the fit, absent for an exact fit through two points
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uncertainty: Option<Uncertainty>,
+    /// Acceptance criteria evaluated for the calibration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub acceptance: Option<Acceptance>,
+}
+
+impl CalibrationRecord {
+    /// Creates a record of a calibration made now with the current tool version.
+    pub fn new(
+        calibration: &Calibration<f64>,
+        temperature: f64,
+        readings: &[f64],
+        buffers: &[f64],
+    ) -> CalibrationRecord {
+        CalibrationRecord {
+            version: RECORD_VERSION,
+            tool_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            timestamp: Some(Utc::now()),
+            electrode: None,
+            slope: calibration.slope,
+            offset: calibration.offset,
+            rms: calibration.rms,
+            rsq: calibration.rsq,
+            temperature: Some(temperature),
+            buffer_set: None,
+            interpolation: None,
+            nominal: Vec::new(),
+            buffers: buffers.to_vec(),
+            readings: readings.to_vec(),
+            replicates: Vec::new(),
+            reading: Reading::Ph,
+            temperature_policy: TemperaturePolicy::Error,
+            temperature_warnings: Vec::new(),
+            robust: None,
+            weights: Vec::new(),
+            adjustment: None,
+            model: None,
+            uncertainty: None,
+            acceptance: None,
+        }
+    }
+
+    /// Record of a legacy calibration file, which only holds the slope and offset
+    pub fn legacy(slope: f64, offset: f64) -> CalibrationRecord {
+        CalibrationRecord {
+            version: 0,
+            tool_version: None,
+            timestamp: None,
+            electrode: None,
+            slope,
+            offset,
+            rms: None,
+            rsq: None,
+            temperature: None,
+            buffer_set: None,
+            interpolation: None,
+            nominal: Vec::new(),
+            buffers: Vec::new(),
+            readings: Vec::new(),
+            replicates: Vec::new(),
+            reading: Reading::Ph,
+            temperature_policy: TemperaturePolicy::Error,
+            temperature_warnings: Vec::new(),
+            robust: None,
+            weights: Vec::new(),
+            adjustment: None,
+            model: None,
+            uncertainty: None,
+            acceptance: None,
+        }
+    }
+
+    /// Modifies the electrode identifier
+    pub fn with_electrode(&self, electrode: Option<String>) -> CalibrationRecord {
+        CalibrationRecord {
+            electrode,
+            ..self.clone()
+        }
+    }
+
+    /// Modifies the buffer set name and the nominal pH of the buffers
+    pub fn with_buffer_set(&self, buffer_set: &str, nominal: &[f64]) -> CalibrationRecord {
+        CalibrationRecord {
+            buffer_set: Some(buffer_set.to_string()),
+            nominal: nominal.to_vec(),
+            ..self.clone()
+        }
+    }
+
+    /// Modifies the interpolation of every buffer table
+    pub fn with_interpolation(
+        &self,
+        interpolation: Option<InterpolationMethod>,
+    ) -> CalibrationRecord {
+        CalibrationRecord {
+            interpolation,
+            ..self.clone()
+        }
+    }
+
+    /// Modifies the replicate readings of each buffer, kept only if any buffer was read more than once
+    pub fn with_replicates(&self, replicates: &[Replicates]) -> CalibrationRecord {
+        let replicates = if replicates
+            .iter()
+            .any(|replicates| replicates.readings.len() > 1)
+        {
+            replicates
+                .iter()
+                .map(|replicates| replicates.readings.clone())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        CalibrationRecord {
+            replicates,
+            ..self.clone()
+        }
+    }
+
+    /// Modifies the quantity read from the meter
+    pub fn with_reading(&self, reading: Reading) -> CalibrationRecord {
+        CalibrationRecord {
+            reading,
+            ..self.clone()
+        }
+    }
+
+    /// Modifies the handling of a temperature outside the table of a buffer, and the warnings it gave
+    pub fn with_temperature_policy(
+        &self,
+        temperature_policy: TemperaturePolicy,
+        warnings: &[RangeWarning],
+    ) -> CalibrationRecord {
+        CalibrationRecord {
+            temperature_policy,
+            temperature_warnings: warnings.iter().map(celsius_warning).collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Modifies the robust estimator of the calibration, and the weights it gave the readings
+    pub fn with_robust(&self, robust: Option<&RobustFit>) -> CalibrationRecord {
+        CalibrationRecord {
+            robust: robust.map(|robust| robust.method),
+            weights: robust
+                .map(|robust| robust.weights.clone())
+                .unwrap_or_default(),
+            ..self.clone()
+        }
+    }
+
+    /// Modifies the weight of each reading in a weighted calibration
+    pub fn with_weights(&self, weights: Vec<f64>) -> CalibrationRecord {
+        CalibrationRecord {
+            weights,
+            ..self.clone()
+        }
+    }
+
+    /// Modifies the model used for conversions
+    pub fn with_model(&self, model: Option<ModelRecord>) -> CalibrationRecord {
+        CalibrationRecord {
+            model,
+            ..self.clone()
+        }
+    }
+
+    /// Modifies the uncertainty of the fit
+    pub fn with_uncertainty(&self, uncertainty: Option<Uncertainty>) -> CalibrationRecord {
+        CalibrationRecord {
+            uncertainty,
+            ..self.clone()
+        }
+    }
+
+    /// Modifies the acceptance criteria evaluated for the calibration
+    pub fn with_acceptance(&self, acceptance: Acceptance) -> CalibrationRecord {
+        CalibrationRecord {
+            acceptance: Some(acceptance),
+            ..self.clone()
+        }
+    }
+
+    /// Adjusts the offset of the calibration by a one-point calibration made now, keeping the slope.
+    ///
+    /// The full calibration is kept, with the time it was made, and the uncertainty and acceptance of its fit
+    /// are dropped as they no longer describe the calibration. Returns an error if the record holds a model
+    /// other than the linear calibration, which has no single offset to adjust.
+    pub fn adjusted(
+        &self,
+        calibration: &Calibration<f64>,
+        nominal: f64,
+        buffer: f64,
+        reading: f64,
+        temperature: f64,
+        warning: Option<&RangeWarning>,
+    ) -> Result<CalibrationRecord> {
+        if let Some(model) = &self.model {
+            return Err(Error::InvalidInput {
+                line: None,
+                message: format!(
+                    "a one-point calibration only adjusts a linear calibration, not a {} one",
+                    model.name
+                ),
+            });
+        }
+        let calibrated = match &self.adjustment {
+            Some(adjustment) => adjustment.calibrated,
+            None => self.timestamp,
+        };
+        Ok(CalibrationRecord {
+            tool_version: Some(env!("CARGO_PKG_VERSION").to_string()),
+            timestamp: Some(Utc::now()),
+            offset: calibration.offset,
+            adjustment: Some(Adjustment {
+                calibrated,
+                previous_offset: self.offset,
+                nominal,
+                buffer,
+                reading,
+                temperature,
+                warning: warning.map(celsius_warning),
+            }),
+            uncertainty: None,
+            acceptance: None,
+            ..self.clone()
+        })
+    }
+
+    /// Whether the calibration was last adjusted by a one-point calibration, rather than made in full
+    pub fn is_partial(&self) -> bool {
+        self.adjustment.is_some()
+    }
+
+    /// The calibration held in the record
+    pub fn calibration(&self) -> Calibration<f64> {
+        Calibration::new(self.slope, self.offset, self.rms, self.rsq)
+    }
+
+    /// The model used for conversions, loaded from `registry` if stored, the linear calibration otherwise
+    pub fn model(&self, registry: &ModelRegistry) -> Result<Box<dyn PhModel>> {
+        match &self.model {
+            Some(model) => registry.load(model),
+            None => Ok(Box::new(self.calibration())),
+        }
+    }
+
+    /// Serialises the record to TOML
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).map_err(|err| Error::MalformedCalibration(err.to_string()))
+    }
+
+    /// Parses a calibration record, falling back to the legacy format of two floats
+    pub fn parse(contents: &str) -> Result<CalibrationRecord> {
+        match toml::from_str::<CalibrationRecord>(contents) {
+            Ok(record) => {
+                if record.version > RECORD_VERSION {
+                    return Err(Error::MalformedCalibration(format!(
+                        "version {} is newer than the supported version {}",
+                        record.version, RECORD_VERSION
+                    )));
+                }
+                Ok(record)
+            }
+            Err(toml_err) => {
+                let values = contents
+                    .split_whitespace()
+                    .map(|s| s.parse::<f64>())
+                    .collect::<std::result::Result<Vec<_>, _>>();
+                match values.as_deref() {
+                    Ok([slope, offset]) => Ok(CalibrationRecord::legacy(*slope, *offset)),
+                    _ => Err(Error::MalformedCalibration(format!(
+                        "not a calibration record, {}",
+                        toml_err
+                    ))),
+                }
+            }
+        }
+    }
+
+    /// Reads a calibration record from a file
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<CalibrationRecord> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        })?;
+        CalibrationRecord::parse(&contents).map_err(|err| match err {
+            Error::MalformedCalibration(message) => {
+                Error::MalformedCalibration(format!("{}: {}", path.display(), message))
+            }
+            err => err,
+        })
+    }
+
+    /// Writes the calibration record to a file
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_toml()?).map_err(|source| Error::File {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+}
+
+/// Writes a warning in ˚C for the record, whatever unit it was shown in
+fn celsius_warning(warning: &RangeWarning) -> String {
+    warning.in_unit(TemperatureUnit::Celsius).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{CalibrationRecord, RECORD_VERSION};
+    use crate::acceptance::{Acceptance, AcceptanceLimits};
+    use crate::buffers::InterpolationMethod;
+    use crate::electrode::{Electrode, Reading};
+    use crate::fit::{Robust, RobustFit, Uncertainty};
+    use crate::model::{ModelRegistry, PhModel};
+    use crate::routines::{Calibration, RangeWarning, SegmentedCalibration, TemperaturePolicy};
+    use crate::temperature::TemperatureUnit;
+
+    #[test]
+    fn test_record_round_trip() {
+        let calibration = Calibration::new(0.96828, 0.16052, Some(0.0), Some(1.0));
+        let record = CalibrationRecord::new(&calibration, 22.3, &[3.97, 10.2], &[4.0046, 10.037])
+            .with_electrode(Some("InLab 413".to_string()))
+            .with_buffer_set("technical", &[4.01, 10.01])
+            .with_interpolation(Some(InterpolationMethod::Akima));
+        let electrode = Electrode::from_calibration(&calibration, Reading::Ph, &22.3);
+        let record = record
+            .with_uncertainty(Some(Uncertainty {
+                covariance: [[2.9e-5, -1.8e-4], [-1.8e-4, 1.4e-3]],
+                standard_error: 0.037,
+                dof: 3,
+            }))
+            .with_acceptance(Acceptance::evaluate(
+                &electrode,
+                &AcceptanceLimits::default(),
+            ))
+            .with_temperature_policy(
+                TemperaturePolicy::Clamp,
+                &[RangeWarning {
+                    nominal: 4.01,
+                    temperature: 97.0,
+                    range: (0.0, 95.0),
+                    policy: TemperaturePolicy::Clamp,
+                    ph: 4.2,
+                    unit: TemperatureUnit::Celsius,
+                }],
+            )
+            .with_robust(Some(&RobustFit {
+                method: Robust::TheilSen,
+                model: [0.96828, 0.16052],
+                weights: vec![1.0, 1.0],
+                scale: 1e-9,
+            }));
+
+        let contents = record.to_toml().unwrap();
+        let parsed = CalibrationRecord::parse(&contents).unwrap();
+
+        assert_eq!(parsed, record);
+        assert_eq!(parsed.version, RECORD_VERSION);
+    }
+
+    #[test]
+    fn test_record_model() {
+        let calibration = Calibration::new(1.0, 0.0, None, None);
+        let record =
+            CalibrationRecord::new(&calibration, 25.0, &[4.0, 7.0, 10.0], &[4.0, 7.0, 10.0]);
+        let registry = ModelRegistry::default();
+        assert_eq!(record.model(&registry).unwrap().name(), "linear");
+
+        let segmented =
+            SegmentedCalibration::new(vec![7.02], vec![[1.02, -0.12], [1.05, -0.35]]).unwrap();
+        let record = record.with_model(Some(segmented.to_record().unwrap()));
+        let parsed = CalibrationRecord::parse(&record.to_toml().unwrap()).unwrap();
+        let model = parsed.model(&registry).unwrap();
+
+        assert_eq!(parsed, record);
+        assert_eq!(model.name(), "segmented");
+        assert!(approx_eq!(f64, model.convert(&9.0), 1.05 * 9.0 - 0.35));
+    }
+
+    #[test]
+    fn test_record_adjusted() {
+        let calibration = Calibration::new(1.02, 0.1, None, None);
+        let record = CalibrationRecord::new(&calibration, 25.0, &[3.9, 9.7], &[4.01, 10.01]);
+        assert!(!record.is_partial());
+
+        let adjusted = calibration.with_offset(0.05);
+        let first = record
+            .adjusted(&adjusted, 7.0, 7.0, 6.81, 24.0, None)
+            .unwrap();
+        let parsed = CalibrationRecord::parse(&first.to_toml().unwrap()).unwrap();
+        assert_eq!(parsed, first);
+        assert!(parsed.is_partial());
+        assert_eq!(parsed.slope, 1.02);
+        assert_eq!(parsed.offset, 0.05);
+        assert_eq!(parsed.readings, record.readings);
+
+        let second = first
+            .adjusted(&calibration, 7.0, 7.0, 6.76, 24.0, None)
+            .unwrap();
+        let adjustment = second.adjustment.unwrap();
+        assert_eq!(adjustment.calibrated, record.timestamp);
+        assert_eq!(adjustment.previous_offset, 0.05);
+
+        let segmented =
+            SegmentedCalibration::new(vec![7.02], vec![[1.02, -0.12], [1.05, -0.35]]).unwrap();
+        let record = record.with_model(Some(segmented.to_record().unwrap()));
+        assert!(record
+            .adjusted(&adjusted, 7.0, 7.0, 6.81, 24.0, None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_record_legacy() {
+        let record = CalibrationRecord::parse("0.96828\t0.16052").unwrap();
+
+        assert_eq!(record, CalibrationRecord::legacy(0.96828, 0.16052));
+        assert_eq!(record.reading, Reading::Ph);
+    }
+
+    #[test]
+    fn test_record_invalid() {
+        assert!(CalibrationRecord::parse("").is_err());
+        assert!(CalibrationRecord::parse("0.96828").is_err());
+        assert!(CalibrationRecord::parse("slope = 1.0").is_err());
+        assert!(CalibrationRecord::parse("version = 99\nslope = 1.0\noffset = 0.0").is_err());
+    }
+}
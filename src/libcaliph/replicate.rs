@@ -0,0 +1,345 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Replicate Module
+//! Provides the replicate readings of a buffer, given on the command line separated by `/`, e.g. `4.02/4.03/4.01`.
+//!
+//! The calibration is fitted to the mean of the readings of each buffer. When every buffer has replicates, each
+//! mean is weighted by the inverse of its variance, $`w_i = n_i / s_i^2`$, so that a buffer whose readings
+//! scatter counts for less, see [`crate::fit::fit_weighted`].
+//!
+//! Before fitting, the readings of each buffer are checked for an outlier at the 95 % level, by Grubbs' test or
+//! by Dixon's Q test, see [`OutlierTest`].
+
+use super::stats;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Separator of the replicate readings of a buffer
+pub const REPLICATE_SEPARATOR: char = '/';
+
+/// Names of the outlier tests
+pub const OUTLIER_TEST_NAMES: [&str; 2] = ["grubbs", "dixon"];
+
+/// Critical values of Dixon's Q at the 95 % level, for 3 to 10 readings
+const DIXON_Q_95: [f64; 8] = [0.970, 0.829, 0.710, 0.625, 0.568, 0.526, 0.493, 0.466];
+
+/// Test for a single outlier among the replicate readings of a buffer, at the 95 % level
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutlierTest {
+    /// Grubbs' test, of the largest deviation from the mean in standard deviations, for 3 or more readings
+    Grubbs,
+    /// Dixon's Q test, of the gap between the extreme reading and its neighbour over the range, for 3 to 10
+    /// readings
+    Dixon,
+}
+
+impl fmt::Display for OutlierTest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            OutlierTest::Grubbs => "grubbs",
+            OutlierTest::Dixon => "dixon",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for OutlierTest {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "grubbs" => Ok(OutlierTest::Grubbs),
+            "dixon" => Ok(OutlierTest::Dixon),
+            _ => Err(format!("Unknown outlier test {}", s)),
+        }
+    }
+}
+
+/// Reading found to be an outlier
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outlier {
+    /// Index of the reading among the replicates
+    pub index: usize,
+    pub reading: f64,
+    /// Statistic of the test, G or Q
+    pub statistic: f64,
+    /// Critical value of the statistic at the 95 % level
+    pub critical: f64,
+}
+
+/// Replicate readings of a buffer
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replicates {
+    /// Readings, in the order they were taken
+    pub readings: Vec<f64>,
+    /// Resolution of the readings, one unit in the last decimal place given
+    pub resolution: f64,
+}
+
+impl Replicates {
+    /// Replicates of the given readings, with the resolution of the meter
+    pub fn new(readings: Vec<f64>, resolution: f64) -> Replicates {
+        Replicates {
+            readings,
+            resolution,
+        }
+    }
+
+    /// Mean of the readings
+    pub fn mean(&self) -> f64 {
+        stats::mean(&self.readings)
+    }
+
+    /// Sample standard deviation of the readings, `None` for a single reading
+    pub fn standard_deviation(&self) -> Option<f64> {
+        let count = self.readings.len();
+        if count < 2 {
+            return None;
+        }
+        Some((stats::variance(&self.readings) * count as f64 / (count - 1) as f64).sqrt())
+    }
+
+    /// Standard uncertainty of the mean, $`s/\sqrt{n}`$, `None` for a single reading.
+    ///
+    /// Readings which agree to the last digit still differ by up to the resolution, so the standard deviation
+    /// is taken to be at least that of a rectangular distribution over the resolution, $`\delta/\sqrt{12}`$.
+    pub fn standard_error(&self) -> Option<f64> {
+        let deviation = self
+            .standard_deviation()?
+            .max(self.resolution / 12_f64.sqrt());
+        Some(deviation / (self.readings.len() as f64).sqrt())
+    }
+
+    /// The most extreme reading, if the test finds it to be an outlier.
+    ///
+    /// Returns `None` if there is no outlier, or the test does not apply to the number of readings. A reading
+    /// within the resolution of another is never an outlier, as both tests flag any third reading when the
+    /// other two agree.
+    pub fn outlier(&self, test: OutlierTest) -> Option<Outlier> {
+        let count = self.readings.len();
+        if count < 3 {
+            return None;
+        }
+        let mean = self.mean();
+        let (index, reading) = self
+            .readings
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| (a - mean).abs().total_cmp(&(b - mean).abs()))?;
+        // Readings differing from all others by no more than their last digit are not outliers
+        let gap = self
+            .readings
+            .iter()
+            .enumerate()
+            .filter(|(other, _)| *other != index)
+            .map(|(_, other)| (other - reading).abs())
+            .fold(f64::INFINITY, f64::min);
+        if gap <= self.resolution * (1.0 + 1e-9) {
+            return None;
+        }
+        let (statistic, critical) = match test {
+            OutlierTest::Grubbs => {
+                let deviation = self.standard_deviation()?;
+                if deviation == 0.0 {
+                    return None;
+                }
+                ((reading - mean).abs() / deviation, grubbs_critical(count))
+            }
+            OutlierTest::Dixon => {
+                let critical = *DIXON_Q_95.get(count - 3)?;
+                let mut sorted = self.readings.clone();
+                sorted.sort_by(|a, b| a.total_cmp(b));
+                let range = sorted[count - 1] - sorted[0];
+                if range == 0.0 {
+                    return None;
+                }
+                let gap = if reading == sorted[0] {
+                    sorted[1] - sorted[0]
+                } else {
+                    sorted[count - 1] - sorted[count - 2]
+                };
+                (gap / range, critical)
+            }
+        };
+        if statistic > critical {
+            Some(Outlier {
+                index,
+                reading,
+                statistic,
+                critical,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Replicates without the reading at `index`
+    pub fn without(&self, index: usize) -> Replicates {
+        let mut readings = self.readings.clone();
+        readings.remove(index);
+        Replicates {
+            readings,
+            resolution: self.resolution,
+        }
+    }
+}
+
+/// Critical value of Grubbs' two sided test at the 95 % level for `count` readings,
+/// $`G = \frac{n - 1}{\sqrt{n}} \sqrt{\frac{t^2}{n - 2 + t^2}}`$ with $`t`$ at $`\alpha/2n`$ and $`n - 2`$ degrees of freedom
+fn grubbs_critical(count: usize) -> f64 {
+    let n = count as f64;
+    let t = stats::student_t_quantile(1.0 - 0.05 / (2.0 * n), n - 2.0);
+    (n - 1.0) / n.sqrt() * (t * t / (n - 2.0 + t * t)).sqrt()
+}
+
+impl fmt::Display for Replicates {
+    /// Writes a single reading as it is, and the mean of replicates to one more decimal place than the readings
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.readings.as_slice() {
+            [reading] => write!(f, "{}", reading),
+            _ => {
+                let decimals = if self.resolution > 0.0 {
+                    (-self.resolution.log10()).round().max(0.0) as usize + 1
+                } else {
+                    4
+                };
+                write!(f, "{:.*}", decimals, self.mean())
+            }
+        }
+    }
+}
+
+impl FromStr for Replicates {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut readings = Vec::new();
+        let mut resolution = f64::INFINITY;
+        for value in s.split(REPLICATE_SEPARATOR) {
+            let value = value.trim();
+            match value.parse::<f64>() {
+                Ok(reading) if reading.is_finite() => readings.push(reading),
+                _ => return Err(format!("{:?} is not a finite number", value)),
+            }
+            resolution = resolution.min(resolution_of(value));
+        }
+        Ok(Replicates::new(readings, resolution))
+    }
+}
+
+/// Resolution of a reading as written, one unit in its last decimal place, or 0 if written with an exponent
+fn resolution_of(value: &str) -> f64 {
+    if value.contains(['e', 'E']) {
+        return 0.0;
+    }
+    match value.split_once('.') {
+        Some((_, decimals)) => 10_f64.powi(-(decimals.len() as i32)),
+        None => 1.0,
+    }
+}
+
+/// Weights of the mean readings of each buffer, $`1/u^2`$ from their standard errors.
+///
+/// Returns `None` unless every buffer has replicate readings.
+pub fn replicate_weights(replicates: &[Replicates]) -> Option<Vec<f64>> {
+    replicates
+        .iter()
+        .map(|replicates| replicates.standard_error().map(|error| error.powi(-2)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::approx_eq;
+
+    use super::{replicate_weights, OutlierTest, Replicates};
+
+    #[test]
+    fn test_replicates_parse() {
+        let replicates: Replicates = "4.02/4.05/4.02".parse().unwrap();
+        assert_eq!(replicates.readings, vec![4.02, 4.05, 4.02]);
+        assert!(approx_eq!(f64, replicates.resolution, 0.01));
+        assert!(approx_eq!(f64, replicates.mean(), 4.03, epsilon = 1e-12));
+        assert_eq!(replicates.to_string(), "4.030");
+        assert!(approx_eq!(
+            f64,
+            replicates.standard_deviation().unwrap(),
+            0.017320508,
+            epsilon = 1e-9
+        ));
+
+        let single: Replicates = "-170".parse().unwrap();
+        assert!(approx_eq!(f64, single.resolution, 1.0));
+        assert_eq!(single.standard_deviation(), None);
+        assert_eq!(single.to_string(), "-170");
+
+        assert!("4.02/x".parse::<Replicates>().is_err());
+        assert!("4.02//4.01".parse::<Replicates>().is_err());
+    }
+
+    #[test]
+    fn test_replicate_weights() {
+        let replicates: Vec<Replicates> = ["4.01/4.03/4.02", "7.00/7.00/7.00", "9.9/10.1"]
+            .iter()
+            .map(|value| value.parse().unwrap())
+            .collect();
+        let weights = replicate_weights(&replicates).unwrap();
+
+        assert!(approx_eq!(
+            f64,
+            weights[0],
+            3.0 / 0.01_f64.powi(2),
+            epsilon = 1e-6
+        ));
+        // Readings agreeing to the last digit are limited by the resolution
+        assert!(approx_eq!(
+            f64,
+            weights[1],
+            36.0 / 0.01_f64.powi(2),
+            epsilon = 1e-6
+        ));
+        assert!(weights[2] < weights[0]);
+
+        let single = vec!["4.01".parse().unwrap(), "7.00/7.01".parse().unwrap()];
+        assert_eq!(replicate_weights(&single), None);
+    }
+
+    #[test]
+    fn test_outlier() {
+        let replicates: Replicates = "7.01/7.02/7.01/7.15/7.00".parse().unwrap();
+        for test in [OutlierTest::Grubbs, OutlierTest::Dixon] {
+            let outlier = replicates.outlier(test).unwrap();
+            assert_eq!(outlier.index, 3);
+            assert!(approx_eq!(f64, outlier.reading, 7.15));
+            assert!(outlier.statistic > outlier.critical);
+        }
+        // Critical values of the published tables
+        let grubbs = replicates.outlier(OutlierTest::Grubbs).unwrap();
+        assert!(approx_eq!(f64, grubbs.critical, 1.715, epsilon = 1e-3));
+        let dixon = replicates.outlier(OutlierTest::Dixon).unwrap();
+        assert!(approx_eq!(f64, dixon.critical, 0.710));
+
+        let without = replicates.without(3);
+        assert_eq!(without.readings, vec![7.01, 7.02, 7.01, 7.00]);
+        assert_eq!(without.outlier(OutlierTest::Grubbs), None);
+
+        // Too few, or identical, readings have no outlier
+        let pair: Replicates = "7.01/7.50".parse().unwrap();
+        assert_eq!(pair.outlier(OutlierTest::Grubbs), None);
+        let same: Replicates = "7.01/7.01/7.01".parse().unwrap();
+        assert_eq!(same.outlier(OutlierTest::Dixon), None);
+        let digit: Replicates = "4.02/4.03/4.02".parse().unwrap();
+        assert_eq!(digit.outlier(OutlierTest::Grubbs), None);
+        assert_eq!(digit.outlier(OutlierTest::Dixon), None);
+        let many: Replicates = "7.0/7.0/7.0/7.0/7.0/7.0/7.0/7.0/7.0/7.0/9.0"
+            .parse()
+            .unwrap();
+        assert_eq!(many.outlier(OutlierTest::Dixon), None);
+        assert!(many.outlier(OutlierTest::Grubbs).is_some());
+    }
+}
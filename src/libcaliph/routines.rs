@@ -5,24 +5,49 @@ Copyright 2021 Peter Dunne */
 //! # Routines Module
 //! Provides the functions needed to calibrate a pH meter, and to perform the conversion of a measurement with a known calibration.
 
-use super::fit;
+use super::buffers::{Buffer, BufferCurve, BufferSet, InterpolationMethod};
+use super::electrode::Reading;
+use super::error::{Error, Result};
+use super::fit::{self, FitReport, Robust, RobustFit, Uncertainty};
+use super::model::{compensate, PhModel};
+use super::temperature::{Temperature, TemperatureUnit};
 use super::{PH10_STATIC, PH4_STATIC, TEMP_STATIC};
 use float_cmp::ApproxEq;
-use splines::{Interpolation, Key, Spline};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// 0˚C in Kelvin
+pub const ZERO_CELSIUS: f64 = 273.15;
+
+/// Molar gas constant, in J/(mol K)
+pub const GAS_CONSTANT: f64 = 8.314_462_618;
+
+/// Faraday constant, in C/mol
+pub const FARADAY_CONSTANT: f64 = 96_485.332_12;
+
+/// pH of the isopotential point of the electrode, where the potential is independent of temperature
+pub const ISOPOTENTIAL_PH: f64 = 7.0;
 
 /// Calibration struct as a convenience wrapper.
 ///
-/// This includes optional elements for goodness of fit variables. The calibration model is linear, i.e. $`y  = m x + c`$
+/// This includes optional elements for goodness of fit variables. The calibration model is linear, i.e. $`y  = m x + c`$,
+/// see [`PhModel`] for the other models.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Calibration<F> {
-    ///
+    /// Slope of the calibration, $`m`$
     pub slope: F,
+    /// Offset of the calibration, $`c`$
     pub offset: F,
+    /// Root mean squared error of the fit
     pub rms: Option<F>,
+    /// R-squared goodness of fit
     pub rsq: Option<F>,
 }
 
 /// Implements ApproxEq trait for Calibration struct
-impl<'a, M: Copy + Default, F: Copy + ApproxEq<Margin = M>> ApproxEq for &'a Calibration<F> {
+impl<M: Copy + Default, F: Copy + ApproxEq<Margin = M>> ApproxEq for &Calibration<F> {
     type Margin = M;
 
     fn approx_eq<T: Into<Self::Margin>>(self, other: Self, margin: T) -> bool {
@@ -78,66 +103,614 @@ where
     }
 }
 
+impl From<&FitReport> for Calibration<f64> {
+    fn from(report: &FitReport) -> Self {
+        Calibration::new(report.slope, report.intercept, report.rmse, report.rsq)
+    }
+}
+
 /// Calculates the calibration values at give temperature for the measured pH values
-pub fn ph_calibration(ph_measured: &[f64; 2], temperature: &f64) -> Calibration<f64> {
-    let ph4_cal = interp_ph4(temperature).unwrap_or(4.01);
-    let ph10_cal = interp_ph10(temperature).unwrap_or(10.01);
+pub fn ph_calibration(ph_measured: &[f64; 2], temperature: &f64) -> Result<Calibration<f64>> {
+    ph_calibration_multi(
+        ph_measured,
+        &[4.01, 10.01],
+        temperature,
+        &BufferSet::technical(),
+    )
+}
 
-    let ph_cal = [ph4_cal, ph10_cal];
+/// Calculates the calibration values at a given temperature for any number of buffer solutions.
+///
+/// `ph_nominal` holds the nominal pH of the buffer used for each value in `ph_measured`, each of which is corrected to
+/// `temperature` by `buffer_set`. Returns an error for a buffer not in the set.
+pub fn ph_calibration_multi(
+    ph_measured: &[f64],
+    ph_nominal: &[f64],
+    temperature: &f64,
+    buffer_set: &BufferSet,
+) -> Result<Calibration<f64>> {
+    let ph_cal = buffer_values(ph_nominal, temperature, buffer_set)?;
 
-    let calibration = fit::fit(ph_measured, &ph_cal);
-    let fit_eval = fit::evaluate(ph_measured, &ph_cal, &calibration);
+    ph_calibration_points(ph_measured, &ph_cal)
+}
 
-    Calibration::new(
-        calibration[0],
-        calibration[1],
-        Some(fit_eval[0]),
-        Some(fit_eval[1]),
-    )
+/// Gives the pH of each buffer solution at the given temperature, from their nominal pH values.
+///
+/// Each buffer is corrected to `temperature` by `buffer_set`. Returns an error for a buffer not in the set, or if the
+/// temperature is outside the range of any of the buffers.
+pub fn buffer_values(
+    ph_nominal: &[f64],
+    temperature: &f64,
+    buffer_set: &BufferSet,
+) -> Result<Vec<f64>> {
+    let (ph_buffer, _) = buffer_values_with_policy(
+        ph_nominal,
+        temperature,
+        buffer_set,
+        TemperaturePolicy::Error,
+    )?;
+    Ok(ph_buffer)
+}
+
+/// Names of the temperature policies
+pub const TEMPERATURE_POLICY_NAMES: [&str; 3] = ["error", "clamp", "extrapolate"];
+
+/// Handling of a temperature outside the table of a buffer solution
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperaturePolicy {
+    /// Refuse to calibrate
+    #[default]
+    Error,
+    /// Use the pH at the nearest end of the table, with a warning
+    Clamp,
+    /// Extend the line through the two nearest table points, with a warning
+    Extrapolate,
 }
 
-/// Converts the measured pH to a calibrated one using a known calibration
-pub fn ph_convert(ph_measured: &f64, calibration: &[f64; 2]) -> f64 {
-    fit::predict(ph_measured, calibration)
+impl fmt::Display for TemperaturePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            TemperaturePolicy::Error => "error",
+            TemperaturePolicy::Clamp => "clamp",
+            TemperaturePolicy::Extrapolate => "extrapolate",
+        };
+        write!(f, "{}", name)
+    }
 }
 
-/// Interpolates the temperature dependence of a pH 4.01 buffer solution to give an arbitrary pH value between 5 to 95˚C
-pub fn interp_ph4(temperature: &f64) -> Option<f64> {
-    let pairs_iter = TEMP_STATIC.iter().zip(PH4_STATIC.iter());
-    let zipped_points: Vec<_> = pairs_iter
-        .map(|(x, y)| Key::new(*x, *y, Interpolation::Linear))
-        .collect();
+impl FromStr for TemperaturePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(TemperaturePolicy::Error),
+            "clamp" => Ok(TemperaturePolicy::Clamp),
+            "extrapolate" => Ok(TemperaturePolicy::Extrapolate),
+            _ => Err(format!("Unknown temperature policy {}", s)),
+        }
+    }
+}
 
-    let spline = Spline::from_vec(zipped_points);
+/// Warning that a buffer was used outside its table, by a clamping or extrapolating [`TemperaturePolicy`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RangeWarning {
+    /// Nominal pH of the buffer
+    pub nominal: f64,
+    /// Temperature of the calibration, in ˚C
+    pub temperature: f64,
+    /// Lowest and highest temperatures the buffer is tabulated for
+    pub range: (f64, f64),
+    /// Policy giving the pH of the buffer
+    pub policy: TemperaturePolicy,
+    /// pH of the buffer given by the policy
+    pub ph: f64,
+    /// Unit the temperatures are written in
+    pub unit: TemperatureUnit,
+}
 
-    spline.sample(*temperature)
+impl RangeWarning {
+    /// Writes the temperatures of the warning in `unit`, as given by the user, rather than in ˚C
+    pub fn in_unit(&self, unit: TemperatureUnit) -> RangeWarning {
+        RangeWarning { unit, ..*self }
+    }
 }
 
-/// Interpolates the temperature dependence of a pH 10.01 buffer solution to give an arbitrary pH value between 5 to 95˚C
-pub fn interp_ph10(temperature: &f64) -> Option<f64> {
-    let pairs_iter = TEMP_STATIC.iter().zip(PH10_STATIC.iter());
-    let zipped_points: Vec<_> = pairs_iter
-        .map(|(x, y)| Key::new(*x, *y, Interpolation::Linear))
+impl fmt::Display for RangeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let in_unit = |celsius: f64| Temperature::from_celsius(celsius).to_unit(self.unit);
+        write!(
+            f,
+            "{} is outside the range of the pH {} buffer, from {} to {}, ",
+            in_unit(self.temperature).with_celsius(),
+            self.nominal,
+            in_unit(self.range.0),
+            in_unit(self.range.1)
+        )?;
+        match self.policy {
+            TemperaturePolicy::Clamp => {
+                let end = self.temperature.clamp(self.range.0, self.range.1);
+                write!(f, "clamped to pH {:.4} at {}", self.ph, in_unit(end))
+            }
+            _ => write!(f, "extrapolated to pH {:.4}", self.ph),
+        }
+    }
+}
+
+/// Gives the pH of each buffer solution at the given temperature, handling a temperature outside the table of a
+/// buffer by `policy`.
+///
+/// Each buffer is corrected to `temperature` by `buffer_set`, and a buffer not in the set is an error rather than
+/// being used at its nominal pH, which would hide a typing error. Returns the pH values with a warning for each buffer
+/// clamped or extrapolated, or an error for the [`TemperaturePolicy::Error`] policy if the temperature is outside the
+/// range of any of the buffers.
+pub fn buffer_values_with_policy(
+    ph_nominal: &[f64],
+    temperature: &f64,
+    buffer_set: &BufferSet,
+    policy: TemperaturePolicy,
+) -> Result<(Vec<f64>, Vec<RangeWarning>)> {
+    if !temperature.is_finite() {
+        return Err(Error::NonFinite("temperature".to_string()));
+    }
+    let mut warnings = Vec::new();
+    let ph_buffer = ph_nominal
+        .iter()
+        .map(|nominal| {
+            let buffer = buffer_set.find(nominal).ok_or_else(|| {
+                Error::InvalidBuffer(format!(
+                    "there is no pH {} buffer in the {} set",
+                    nominal, buffer_set.name
+                ))
+            })?;
+            if let Some(ph) = interp_buffer(buffer, temperature) {
+                return Ok(ph);
+            }
+            let range = buffer.temperature_range();
+            let outside = *temperature < range.0 || *temperature > range.1;
+            if !outside {
+                return Err(Error::InvalidBuffer(format!(
+                    "buffer {} cannot be interpolated at {} ˚C",
+                    buffer.name, temperature
+                )));
+            }
+            let ph = match policy {
+                TemperaturePolicy::Clamp => buffer.curve.clamped(temperature),
+                TemperaturePolicy::Extrapolate => buffer.curve.extrapolated(temperature),
+                TemperaturePolicy::Error => None,
+            };
+            match ph {
+                Some(ph) => {
+                    warnings.push(RangeWarning {
+                        nominal: *nominal,
+                        temperature: *temperature,
+                        range,
+                        policy,
+                        ph,
+                        unit: TemperatureUnit::Celsius,
+                    });
+                    Ok(ph)
+                }
+                None => Err(Error::TemperatureOutOfRange {
+                    nominal: *nominal,
+                    temperature: *temperature,
+                    range,
+                    unit: TemperatureUnit::Celsius,
+                }),
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((ph_buffer, warnings))
+}
+
+/// Least squares calibration from pairs of measured and buffer pH values.
+///
+/// The RMS and R-squared are evaluated over all points, and are `None` for an exact fit through two points.
+/// Returns an error if `ph_measured` and `ph_buffer` are not of equal length, or if the fit is undetermined.
+pub fn ph_calibration_points(ph_measured: &[f64], ph_buffer: &[f64]) -> Result<Calibration<f64>> {
+    Ok(Calibration::from(&ph_fit_report(ph_measured, ph_buffer)?))
+}
+
+/// One-point calibration, adjusting the offset of an existing calibration to a single buffer and keeping its slope.
+///
+/// Between full calibrations, the drift of an electrode is mostly in its offset, which is checked with one buffer.
+/// Returns an error if the measured or buffer pH is not finite.
+pub fn ph_calibration_one_point(
+    calibration: &Calibration<f64>,
+    ph_measured: &f64,
+    ph_buffer: &f64,
+) -> Result<Calibration<f64>> {
+    if !ph_measured.is_finite() || !ph_buffer.is_finite() {
+        return Err(Error::NonFinite("one-point calibration".to_string()));
+    }
+    Ok(calibration.with_offset(ph_buffer - calibration.slope * ph_measured))
+}
+
+/// One-point calibration with the buffer read at a different temperature to the calibration, adjusting its offset so
+/// that the calibration compensated to `temperature`, see [`crate::model::compensate`], gives the buffer pH.
+///
+/// The calibration keeps its slope and `calibration_temperature`, so converting the reading at `temperature` returns
+/// the buffer pH. Returns an error if the measured or buffer pH is not finite.
+pub fn ph_calibration_one_point_at(
+    calibration: &Calibration<f64>,
+    reading: Reading,
+    ph_measured: &f64,
+    ph_buffer: &f64,
+    calibration_temperature: &f64,
+    temperature: &f64,
+) -> Result<Calibration<f64>> {
+    if !ph_measured.is_finite() || !ph_buffer.is_finite() {
+        return Err(Error::NonFinite("one-point calibration".to_string()));
+    }
+    // The compensated pH is linear in the offset, so two offsets give the one reaching the buffer pH
+    let compensated = |offset: f64| {
+        compensate(
+            &calibration.with_offset(offset),
+            reading,
+            calibration_temperature,
+            temperature,
+        )
+        .convert(ph_measured)
+    };
+    let (at_zero, at_one) = (compensated(0.0), compensated(1.0));
+    Ok(calibration.with_offset((ph_buffer - at_zero) / (at_one - at_zero)))
+}
+
+/// Least squares fit of pairs of measured and buffer pH values, with its full goodness of fit
+pub fn ph_fit_report(ph_measured: &[f64], ph_buffer: &[f64]) -> Result<FitReport> {
+    let calibration = fit::fit(ph_measured, ph_buffer)?;
+    FitReport::new(ph_measured, ph_buffer, &calibration)
+}
+
+/// Weighted least squares fit of pairs of measured and buffer pH values, with its full goodness of fit,
+/// see [`fit::fit_weighted`]
+pub fn ph_fit_report_weighted(
+    ph_measured: &[f64],
+    ph_buffer: &[f64],
+    weights: &[f64],
+) -> Result<FitReport> {
+    let calibration = fit::fit_weighted(ph_measured, ph_buffer, weights)?;
+    FitReport::weighted(ph_measured, ph_buffer, &calibration, weights)
+}
+
+/// Robust fit of pairs of measured and buffer pH values, tolerating an outlying buffer, see [`fit::robust_fit`]
+pub fn ph_fit_robust(ph_measured: &[f64], ph_buffer: &[f64], method: Robust) -> Result<RobustFit> {
+    fit::robust_fit(ph_measured, ph_buffer, method)
+}
+
+/// Converts the measured pH to a calibrated one using a known calibration, of any model
+pub fn ph_convert<M: PhModel + ?Sized>(ph_measured: &f64, calibration: &M) -> f64 {
+    calibration.convert(ph_measured)
+}
+
+/// Piecewise linear calibration, with its own slope and offset for each range of measured values.
+///
+/// Bench meters calibrated with more than two buffers use a separate segment between each pair of
+/// neighbouring buffers, e.g. an acid segment from pH 4 to 7 and an alkaline segment from pH 7 to 10.
+/// Measured values beyond the outer buffers are converted with the outer segments.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SegmentedCalibration {
+    /// Measured values dividing the segments, in increasing order
+    pub breakpoints: Vec<f64>,
+    /// Slope and offset of each segment, one more than the breakpoints
+    pub segments: Vec<[f64; 2]>,
+}
+
+impl SegmentedCalibration {
+    /// Creates a segmented calibration, checking that the breakpoints are finite and strictly
+    /// increasing, with one segment more than breakpoints
+    pub fn new(breakpoints: Vec<f64>, segments: Vec<[f64; 2]>) -> Result<SegmentedCalibration> {
+        if segments.len() != breakpoints.len() + 1 {
+            return Err(Error::LengthMismatch {
+                expected: breakpoints.len() + 1,
+                found: segments.len(),
+            });
+        }
+        if breakpoints
+            .iter()
+            .chain(segments.iter().flatten())
+            .any(|value| !value.is_finite())
+        {
+            return Err(Error::NonFinite("segmented calibration".to_string()));
+        }
+        if !breakpoints.windows(2).all(|pair| pair[0] < pair[1]) {
+            return Err(Error::MalformedCalibration(
+                "breakpoints are not strictly increasing".to_string(),
+            ));
+        }
+        Ok(SegmentedCalibration {
+            breakpoints,
+            segments,
+        })
+    }
+
+    /// Segmented calibration of a single linear segment
+    pub fn linear(calibration: &[f64; 2]) -> SegmentedCalibration {
+        SegmentedCalibration {
+            breakpoints: Vec::new(),
+            segments: vec![*calibration],
+        }
+    }
+
+    /// Slope and offset of the segment the measured value falls in
+    pub fn segment(&self, ph_measured: &f64) -> &[f64; 2] {
+        let index = self
+            .breakpoints
+            .partition_point(|breakpoint| breakpoint <= ph_measured);
+        &self.segments[index]
+    }
+}
+
+/// Segmented calibration through pairs of measured and buffer pH values, with a segment between each pair
+/// of neighbouring measured values.
+///
+/// The inner measured values become the breakpoints, so two points give a single linear segment.
+/// Returns an error if `ph_measured` and `ph_buffer` are not of equal length, if there are fewer than two points,
+/// or if a measured value is repeated.
+pub fn ph_calibration_segmented(
+    ph_measured: &[f64],
+    ph_buffer: &[f64],
+) -> Result<SegmentedCalibration> {
+    if ph_measured.len() != ph_buffer.len() {
+        return Err(Error::LengthMismatch {
+            expected: ph_measured.len(),
+            found: ph_buffer.len(),
+        });
+    }
+    if ph_measured.len() < 2 {
+        return Err(Error::DegenerateFit);
+    }
+    let mut points: Vec<(f64, f64)> = ph_measured
+        .iter()
+        .copied()
+        .zip(ph_buffer.iter().copied())
+        .collect();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let segments = points
+        .windows(2)
+        .map(|pair| fit::fit(&[pair[0].0, pair[1].0], &[pair[0].1, pair[1].1]))
+        .collect::<Result<Vec<_>>>()?;
+    let breakpoints = points[1..points.len() - 1]
+        .iter()
+        .map(|(measured, _)| *measured)
         .collect();
+    SegmentedCalibration::new(breakpoints, segments)
+}
+
+/// Kind of interval around a converted pH value
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interval {
+    /// Interval of the mean pH of samples giving the same reading
+    Confidence,
+    /// Interval of the pH of the single sample measured, including the scatter of the calibration
+    #[default]
+    Prediction,
+}
 
-    let spline = Spline::from_vec(zipped_points);
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Interval::Confidence => "confidence",
+            Interval::Prediction => "prediction",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "confidence" => Ok(Interval::Confidence),
+            "prediction" => Ok(Interval::Prediction),
+            _ => Err(format!("Unknown interval {}", s)),
+        }
+    }
+}
 
-    spline.sample(*temperature)
+/// Calibrated pH, with the half widths of its intervals
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PhInterval {
+    pub ph: f64,
+    /// Half width of the confidence interval
+    pub confidence: f64,
+    /// Half width of the prediction interval
+    pub prediction: f64,
+    /// Confidence level of the intervals, between 0 and 1
+    pub level: f64,
+}
+
+impl PhInterval {
+    /// Half width of the given kind of interval
+    pub fn half_width(&self, interval: Interval) -> f64 {
+        match interval {
+            Interval::Confidence => self.confidence,
+            Interval::Prediction => self.prediction,
+        }
+    }
+}
+
+/// Converts the measured pH to a calibrated one, with its Student-t confidence and prediction
+/// intervals at a confidence `level` between 0 and 1.
+///
+/// The intervals come from the uncertainty of the calibration, see [`FitReport::uncertainty`].
+pub fn ph_convert_interval<M: PhModel + ?Sized>(
+    ph_measured: &f64,
+    calibration: &M,
+    uncertainty: &Uncertainty,
+    level: &f64,
+) -> Result<PhInterval> {
+    if !(*level > 0.0 && *level < 1.0) {
+        return Err(Error::InvalidInput {
+            line: None,
+            message: format!("the confidence level {} must lie between 0 and 1", level),
+        });
+    }
+    Ok(PhInterval {
+        ph: ph_convert(ph_measured, calibration),
+        confidence: uncertainty.confidence(ph_measured, level),
+        prediction: uncertainty.prediction(ph_measured, level),
+        level: *level,
+    })
+}
+
+/// Converts each measured pH to a calibrated one using a known calibration
+pub fn ph_convert_all<M: PhModel + ?Sized>(ph_measured: &[f64], calibration: &M) -> Vec<f64> {
+    ph_convert_iter(ph_measured.iter().copied(), calibration).collect()
+}
+
+/// Lazily converts an iterator of measured pH values to calibrated ones using a known calibration
+pub fn ph_convert_iter<'a, I, M>(
+    ph_measured: I,
+    calibration: &'a M,
+) -> impl Iterator<Item = f64> + 'a
+where
+    I: IntoIterator<Item = f64>,
+    M: PhModel + ?Sized,
+    I::IntoIter: 'a,
+{
+    ph_measured
+        .into_iter()
+        .map(move |ph| ph_convert(&ph, calibration))
+}
+
+/// Converts the measured pH to a calibrated one, compensating for a measurement made at a
+/// different temperature to the calibration, see [`crate::model::compensate`].
+pub fn ph_convert_at(
+    ph_measured: &f64,
+    calibration: &dyn PhModel,
+    calibration_temperature: &f64,
+    temperature: &f64,
+) -> f64 {
+    compensate(
+        calibration,
+        Reading::Ph,
+        calibration_temperature,
+        temperature,
+    )
+    .convert(ph_measured)
+}
+
+/// Rescales a calibration made at `calibration_temperature` to a measurement made at `temperature`.
+///
+/// The Nernstian slope of an electrode is proportional to the absolute temperature, so the
+/// slope is scaled by $`T_{cal}/T`$ around the isopotential point at pH 7, giving
+///
+/// $`pH = m \frac{T_{cal}}{T} (x - 7) + 7 m + c`$
+pub fn temperature_compensation(
+    calibration: &[f64; 2],
+    calibration_temperature: &f64,
+    temperature: &f64,
+) -> [f64; 2] {
+    let ratio = (calibration_temperature + ZERO_CELSIUS) / (temperature + ZERO_CELSIUS);
+    let slope = calibration[0] * ratio;
+    let offset = calibration[1] + calibration[0] * ISOPOTENTIAL_PH * (1.0 - ratio);
+    [slope, offset]
+}
+
+/// Theoretical (Nernstian) slope of a pH electrode at the given temperature in ˚C, in mV per pH unit.
+///
+/// $`S = \frac{R T \ln 10}{F}`$, which is 59.16 mV at 25˚C
+pub fn nernst_slope(temperature: &f64) -> f64 {
+    1000.0 * GAS_CONSTANT * (temperature + ZERO_CELSIUS) * std::f64::consts::LN_10
+        / FARADAY_CONSTANT
+}
+
+/// Evaluates the temperature dependence of a buffer solution to give its pH at an arbitrary temperature within its
+/// table or the range of its equation
+pub fn interp_buffer(buffer: &Buffer, temperature: &f64) -> Option<f64> {
+    buffer.curve.ph(temperature)
+}
+
+/// pH of a buffer solution by one interpolation of its table, and its difference from the buffer's own
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InterpolationDifference {
+    pub method: InterpolationMethod,
+    /// pH by the interpolation, `None` if it cannot be sampled at the temperature
+    pub ph: Option<f64>,
+    /// Difference from the pH by the interpolation of the buffer, `None` if either cannot be sampled
+    pub difference: Option<f64>,
+}
+
+/// Compares the pH of a buffer solution at a temperature within its table by each interpolation, see
+/// [`InterpolationMethod`], with the pH by the interpolation of the buffer.
+///
+/// The differences show whether the choice of interpolation matters at the temperature, against the uncertainty of the
+/// buffer. Interpolations needing more points than the table has are left out, and a buffer described by an equation
+/// has none.
+pub fn interpolation_differences(
+    buffer: &Buffer,
+    temperature: &f64,
+) -> Vec<InterpolationDifference> {
+    let points = match &buffer.curve {
+        BufferCurve::Table(table) => table.temperature().len(),
+        BufferCurve::Equation(_) => return Vec::new(),
+    };
+    let reference = interp_buffer(buffer, temperature);
+    InterpolationMethod::ALL
+        .iter()
+        .filter(|method| points >= method.min_points())
+        .map(|method| {
+            let ph = buffer.curve.with_interpolation(*method).ph(temperature);
+            InterpolationDifference {
+                method: *method,
+                ph,
+                difference: ph.zip(reference).map(|(ph, reference)| ph - reference),
+            }
+        })
+        .collect()
+}
+
+/// Temperature dependence of a pH 4.01 buffer solution between 5 to 95˚C, built on first use
+pub fn ph4_curve() -> &'static BufferCurve {
+    static CURVE: OnceLock<BufferCurve> = OnceLock::new();
+    CURVE.get_or_init(|| BufferCurve::table(&TEMP_STATIC, &PH4_STATIC))
+}
+
+/// Temperature dependence of a pH 10.01 buffer solution between 5 to 95˚C, built on first use
+pub fn ph10_curve() -> &'static BufferCurve {
+    static CURVE: OnceLock<BufferCurve> = OnceLock::new();
+    CURVE.get_or_init(|| BufferCurve::table(&TEMP_STATIC, &PH10_STATIC))
+}
+
+/// Interpolates the temperature dependence of a pH 4.01 buffer solution to give an arbitrary pH value between 5 to 95˚C
+pub fn interp_ph4(temperature: &f64) -> Option<f64> {
+    ph4_curve().ph(temperature)
+}
+
+/// Interpolates the temperature dependence of a pH 10.01 buffer solution to give an arbitrary pH value between 5 to 95˚C
+pub fn interp_ph10(temperature: &f64) -> Option<f64> {
+    ph10_curve().ph(temperature)
 }
 
 #[cfg(test)]
 mod tests {
     use float_cmp::approx_eq;
 
-    use crate::routines::Calibration;
+    use crate::buffers::{Buffer, BufferSet, InterpolationMethod};
+    use crate::electrode::Reading;
+    use crate::error::Error;
+    use crate::model::compensate;
+    use crate::routines::{Calibration, ZERO_CELSIUS};
+    use crate::temperature::{Temperature, TemperatureUnit};
+    use crate::{PH4_STATIC, TEMP_STATIC};
 
-    use super::{ph_calibration, ph_convert};
+    use super::{
+        buffer_values, buffer_values_with_policy, interp_buffer, interp_ph10, interp_ph4,
+        interpolation_differences, nernst_slope, ph10_curve, ph4_curve, ph_calibration,
+        ph_calibration_multi, ph_calibration_one_point, ph_calibration_one_point_at,
+        ph_calibration_segmented, ph_convert, ph_convert_all, ph_convert_at, ph_convert_interval,
+        ph_convert_iter, ph_fit_report, temperature_compensation, SegmentedCalibration,
+        TemperaturePolicy,
+    };
 
     #[test]
     fn test_ph_calibration() {
         let temperature = 21.0;
         let ph_measured = [3.75, 9.49];
-        let res = ph_calibration(&ph_measured, &temperature);
+        let res = ph_calibration(&ph_measured, &temperature).unwrap();
         let slope = 1.053658536585366;
         let offset = 0.05078048780487787;
         let test_calib = Calibration::default().with_slope(slope).with_offset(offset);
@@ -145,13 +718,412 @@ mod tests {
         assert!(approx_eq!(&Calibration<f64>, &res, &test_calib))
     }
 
+    #[test]
+    fn test_ph_calibration_multi() {
+        let temperature = 25.0;
+        let ph_measured = [3.8, 6.9, 9.8];
+        let ph_nominal = [4.01, 7.0, 10.01];
+        let res = ph_calibration_multi(
+            &ph_measured,
+            &ph_nominal,
+            &temperature,
+            &BufferSet::technical(),
+        )
+        .unwrap();
+        assert!(res.rms.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn test_ph_calibration_multi_unknown_buffer() {
+        let ph_measured = [1.5, 3.8, 6.6, 8.9, 9.8];
+        let ph_nominal = [1.68, 4.01, 6.86, 9.18, 10.01];
+        let res = ph_calibration_multi(&ph_measured, &ph_nominal, &25.0, &BufferSet::technical());
+        assert!(matches!(res, Err(Error::InvalidBuffer(_))));
+    }
+
+    #[test]
+    fn test_ph_calibration_multi_nist() {
+        let temperature = 25.0;
+        let ph_measured = [1.5, 3.8, 6.6, 8.9, 9.8];
+        let ph_nominal = [1.68, 4.01, 6.86, 9.18, 10.01];
+        let res = ph_calibration_multi(&ph_measured, &ph_nominal, &temperature, &BufferSet::nist())
+            .unwrap();
+
+        assert!(approx_eq!(f64, res.slope, 1.0079452, epsilon = 1e-6));
+        assert!(approx_eq!(f64, res.offset, 0.1795755, epsilon = 1e-6));
+    }
+
+    #[test]
+    fn test_ph_calibration_errors() {
+        let set = BufferSet::technical();
+
+        let mismatched = ph_calibration_multi(&[3.9, 6.9, 9.9], &[4.01, 10.01], &25.0, &set);
+        assert!(matches!(mismatched, Err(Error::LengthMismatch { .. })));
+
+        let degenerate = ph_calibration_multi(&[3.9, 3.9], &[4.01, 10.01], &25.0, &set);
+        assert!(matches!(degenerate, Err(Error::DegenerateFit)));
+
+        let too_hot = ph_calibration(&[3.9, 9.9], &97.0);
+        assert!(matches!(
+            too_hot,
+            Err(Error::TemperatureOutOfRange { range, .. }) if range == (0.0, 95.0)
+        ));
+
+        let non_finite = ph_calibration(&[f64::NAN, 9.9], &25.0);
+        assert!(matches!(non_finite, Err(Error::NonFinite(_))));
+    }
+
+    #[test]
+    fn test_ph_calibration_one_point() {
+        let calibration = Calibration::new(1.02, 0.1, Some(0.01), Some(0.999));
+        let res = ph_calibration_one_point(&calibration, &6.95, &7.0).unwrap();
+
+        assert_eq!(res.slope, 1.02);
+        assert!(approx_eq!(f64, res.offset, 7.0 - 1.02 * 6.95));
+        assert!(approx_eq!(f64, ph_convert(&6.95, &res), 7.0));
+
+        let non_finite = ph_calibration_one_point(&calibration, &f64::NAN, &7.0);
+        assert!(matches!(non_finite, Err(Error::NonFinite(_))));
+    }
+
+    #[test]
+    fn test_ph_calibration_one_point_at() {
+        let calibration = Calibration::new(1.02, 0.1, None, None);
+        for reading in [Reading::Ph, Reading::Millivolt] {
+            let (measured, buffer) = match reading {
+                Reading::Ph => (6.9, 7.06),
+                Reading::Millivolt => (10.0, 6.84),
+            };
+            let calibration = match reading {
+                Reading::Ph => calibration,
+                Reading::Millivolt => Calibration::new(-0.0169, 7.0, None, None),
+            };
+            let adjusted = ph_calibration_one_point_at(
+                &calibration,
+                reading,
+                &measured,
+                &buffer,
+                &25.0,
+                &10.0,
+            )
+            .unwrap();
+            assert_eq!(adjusted.slope, calibration.slope);
+            // Converted back at its own temperature, as conph does
+            let converted = compensate(&adjusted, reading, &25.0, &10.0).convert(&measured);
+            assert!(approx_eq!(f64, converted, buffer, epsilon = 1e-12));
+        }
+
+        // At the calibration temperature, the same as without compensation
+        let same =
+            ph_calibration_one_point_at(&calibration, Reading::Ph, &6.95, &7.0, &25.0, &25.0)
+                .unwrap();
+        let plain = ph_calibration_one_point(&calibration, &6.95, &7.0).unwrap();
+        assert!(approx_eq!(f64, same.offset, plain.offset, epsilon = 1e-12));
+    }
+
+    #[test]
+    fn test_buffer_values_table_ends() {
+        let set = BufferSet::technical();
+        let values = buffer_values(&[4.01, 10.01], &95.0, &set).unwrap();
+        assert_eq!(values, vec![4.2, 9.76]);
+    }
+
+    #[test]
+    fn test_buffer_values_with_policy() {
+        let set = BufferSet::technical();
+        let (values, warnings) =
+            buffer_values_with_policy(&[4.01, 10.01], &25.0, &set, TemperaturePolicy::Clamp)
+                .unwrap();
+        assert_eq!(values, vec![4.01, 10.01]);
+        assert!(warnings.is_empty());
+
+        let too_hot =
+            buffer_values_with_policy(&[4.01, 10.01], &97.0, &set, TemperaturePolicy::Error);
+        assert!(matches!(too_hot, Err(Error::TemperatureOutOfRange { .. })));
+        // Written in the unit of the user, with the value in ˚C of the table
+        let fahrenheit = Temperature::new(210.0, TemperatureUnit::Fahrenheit).celsius();
+        let too_hot =
+            buffer_values_with_policy(&[4.01], &fahrenheit, &set, TemperaturePolicy::Error)
+                .unwrap_err()
+                .in_unit(TemperatureUnit::Fahrenheit);
+        assert_eq!(
+            too_hot.to_string(),
+            "210 ˚F (98.9 ˚C) is outside the range of the pH 4.01 buffer, from 32 ˚F to 203 ˚F"
+        );
+
+        let unknown =
+            buffer_values_with_policy(&[4.01, 9.81], &25.0, &set, TemperaturePolicy::Clamp);
+        assert!(matches!(unknown, Err(Error::InvalidBuffer(_))));
+
+        let (values, warnings) =
+            buffer_values_with_policy(&[4.01, 10.01], &97.0, &set, TemperaturePolicy::Clamp)
+                .unwrap();
+        assert_eq!(values, vec![4.2, 9.76]);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].policy, TemperaturePolicy::Clamp);
+        assert_eq!(warnings[1].nominal, 10.01);
+        assert_eq!(
+            warnings[0].in_unit(TemperatureUnit::Kelvin).to_string(),
+            "370.15 K (97.0 ˚C) is outside the range of the pH 4.01 buffer, from 273.15 K to 368.15 K, \
+             clamped to pH 4.2000 at 368.15 K"
+        );
+
+        // Extended by the slope of the first interval of the table
+        let (values, warnings) =
+            buffer_values_with_policy(&[4.01], &-2.0, &set, TemperaturePolicy::Extrapolate)
+                .unwrap();
+        let slope = (PH4_STATIC[1] - PH4_STATIC[0]) / (TEMP_STATIC[1] - TEMP_STATIC[0]);
+        assert!(approx_eq!(
+            f64,
+            values[0],
+            PH4_STATIC[0] + slope * (-2.0 - TEMP_STATIC[0])
+        ));
+        assert_eq!(warnings[0].ph, values[0]);
+    }
+
+    #[test]
+    fn test_interp_buffer() {
+        let technical = BufferSet::technical();
+        let ph7 = technical.find(&7.0).unwrap();
+        assert!(approx_eq!(f64, interp_buffer(ph7, &0.0).unwrap(), 7.12));
+        assert!(approx_eq!(f64, interp_buffer(ph7, &22.5).unwrap(), 7.01));
+
+        let nist = BufferSet::nist();
+        let carbonate = nist.find(&10.01).unwrap();
+        assert!(approx_eq!(
+            f64,
+            interp_buffer(carbonate, &25.0).unwrap(),
+            10.012
+        ));
+        assert!(interp_buffer(carbonate, &60.0).is_none());
+    }
+
+    #[test]
+    fn test_buffer_curve_static() {
+        assert!(std::ptr::eq(ph4_curve(), ph4_curve()));
+        assert!(approx_eq!(f64, interp_ph4(&25.0).unwrap(), 4.01));
+        assert!(approx_eq!(f64, interp_ph10(&25.0).unwrap(), 10.01));
+
+        let temperatures = [0.0, 22.5, 25.0, 95.0, 97.0];
+        let sampled = ph10_curve().ph_all(&temperatures);
+        assert_eq!(sampled.len(), temperatures.len());
+        for (t, ph) in temperatures.iter().zip(sampled.iter()) {
+            assert_eq!(*ph, interp_ph10(t));
+        }
+        assert!(sampled[3].is_some());
+        assert!(sampled[4].is_none());
+    }
+
+    #[test]
+    fn test_interp_buffer_catmull_rom() {
+        let temperature = [0.0, 10.0, 20.0, 30.0];
+        let ph = [4.0, 4.0, 4.1, 4.3];
+        let linear = Buffer::new("test", 4.01, &temperature, &ph);
+        let catmull_rom = linear.with_interpolation(InterpolationMethod::CatmullRom);
+
+        assert!(approx_eq!(
+            f64,
+            interp_buffer(&linear, &15.0).unwrap(),
+            4.05
+        ));
+        assert!(interp_buffer(&catmull_rom, &15.0).unwrap() < 4.05);
+        // Straight in the end intervals, which have no outer neighbour
+        assert!(approx_eq!(
+            f64,
+            interp_buffer(&catmull_rom, &5.0).unwrap(),
+            4.0
+        ));
+        assert!(approx_eq!(
+            f64,
+            interp_buffer(&catmull_rom, &25.0).unwrap(),
+            4.2
+        ));
+    }
+
+    #[test]
+    fn test_interp_buffer_ends() {
+        for set in [BufferSet::technical(), BufferSet::nist()] {
+            for method in InterpolationMethod::ALL {
+                let set = set.with_interpolation(method);
+                for buffer in &set.buffers {
+                    let (low, high) = buffer.temperature_range();
+                    for t in [low, low + 0.5, high - 0.5, high] {
+                        assert!(
+                            interp_buffer(buffer, &t).is_some(),
+                            "{} by {} at {} ˚C",
+                            buffer.name,
+                            method,
+                            t
+                        );
+                    }
+                }
+                let (values, warnings) =
+                    buffer_values_with_policy(&[4.01], &2.0, &set, TemperaturePolicy::Clamp)
+                        .unwrap();
+                assert!(values[0].is_finite());
+                assert!(warnings.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_interp_buffer_cubic() {
+        // A table turning around, as the pH 10.01 buffer near 80˚C
+        let temperature = [60.0, 65.0, 70.0, 75.0, 80.0, 85.0, 90.0];
+        let ph = [9.83, 9.79, 9.77, 9.76, 9.76, 9.77, 9.79];
+        let linear = Buffer::new("test", 10.01, &temperature, &ph);
+        let monotone = linear.with_interpolation(InterpolationMethod::MonotoneCubic);
+        let akima = linear.with_interpolation(InterpolationMethod::Akima);
+
+        for buffer in [&monotone, &akima] {
+            for (t, expected) in temperature.iter().zip(ph.iter()) {
+                let sampled = interp_buffer(buffer, t).unwrap();
+                assert!(approx_eq!(f64, sampled, *expected, epsilon = 1e-12));
+            }
+            // Sampled in the first and last intervals
+            assert!(interp_buffer(buffer, &61.0).is_some());
+            assert!(interp_buffer(buffer, &89.0).is_some());
+        }
+        // Flat between the two equal points, where the linear interpolation is too
+        assert!(approx_eq!(
+            f64,
+            interp_buffer(&monotone, &77.5).unwrap(),
+            9.76,
+            epsilon = 1e-12
+        ));
+        // Curved where the table bends, rather than kinked
+        let bend = interp_buffer(&monotone, &67.5).unwrap();
+        assert!(bend < interp_buffer(&linear, &67.5).unwrap());
+        assert!(bend > 9.77);
+    }
+
+    #[test]
+    fn test_interpolation_differences() {
+        let technical = BufferSet::technical();
+        let ph10 = technical.find(&10.01).unwrap();
+        let differences = interpolation_differences(ph10, &82.5);
+
+        assert_eq!(differences.len(), InterpolationMethod::ALL.len());
+        assert_eq!(differences[0].method, InterpolationMethod::Linear);
+        assert_eq!(differences[0].difference, Some(0.0));
+        for difference in &differences {
+            assert!(difference.difference.unwrap().abs() < 0.01);
+        }
+
+        let short = Buffer::new("test", 4.01, &[20.0, 25.0, 30.0], &[4.0, 4.01, 4.02]);
+        let differences = interpolation_differences(&short, &22.0);
+        assert!(differences
+            .iter()
+            .all(|difference| difference.method != InterpolationMethod::CatmullRom));
+    }
+
+    #[test]
+    fn test_ph_calibration_segmented() {
+        // Steeper in the alkaline range
+        let ph_measured = [9.9, 4.05, 7.02];
+        let ph_buffer = [10.01, 4.01, 7.0];
+        let calibration = ph_calibration_segmented(&ph_measured, &ph_buffer).unwrap();
+
+        assert_eq!(calibration.breakpoints, vec![7.02]);
+        assert_eq!(calibration.segments.len(), 2);
+        for (measured, buffer) in ph_measured.iter().zip(ph_buffer.iter()) {
+            assert!(approx_eq!(
+                f64,
+                ph_convert(measured, &calibration),
+                *buffer,
+                epsilon = 1e-12
+            ));
+        }
+        // Continuous at the breakpoint, and extrapolated by the outer segments
+        assert!(approx_eq!(
+            f64,
+            ph_convert(&(7.02 - 1e-9), &calibration),
+            ph_convert(&7.02, &calibration),
+            epsilon = 1e-6
+        ));
+        assert_eq!(calibration.segment(&2.0), &calibration.segments[0]);
+        assert_eq!(calibration.segment(&12.0), &calibration.segments[1]);
+
+        let linear = ph_calibration_segmented(&[3.97, 10.2], &[4.01, 10.01]).unwrap();
+        assert!(linear.breakpoints.is_empty());
+        assert!(ph_calibration_segmented(&[7.0, 7.0, 9.0], &[4.01, 7.0, 10.01]).is_err());
+        assert!(SegmentedCalibration::new(vec![7.0], vec![[1.0, 0.0]]).is_err());
+        assert!(SegmentedCalibration::new(vec![7.0, 5.0], vec![[1.0, 0.0]; 3]).is_err());
+    }
+
     #[test]
     fn test_ph_conversion() {
         let ph_measured = 4.0;
         let calib = Calibration::default().with_slope(2.0);
-        let result = ph_convert(&ph_measured, &[calib.slope, calib.offset]);
+        let result = ph_convert(&ph_measured, &calib);
         let test_ph = 8.0;
 
         assert!(approx_eq!(f64, result, test_ph))
     }
+
+    #[test]
+    fn test_ph_conversion_all() {
+        let calibration = Calibration::new(2.0, 0.5, None, None);
+        let result = ph_convert_all(&[1.0, 2.0, 3.0], &calibration);
+        assert_eq!(result, vec![2.5, 4.5, 6.5]);
+
+        let lazy: Vec<f64> = ph_convert_iter(vec![1.0, 2.0, 3.0], &calibration).collect();
+        assert_eq!(lazy, result);
+    }
+
+    #[test]
+    fn test_ph_conversion_temperature() {
+        let calibration = Calibration::new(1.02, 0.05, None, None);
+        let ph_measured = 4.0;
+
+        let same = ph_convert_at(&ph_measured, &calibration, &25.0, &25.0);
+        assert!(approx_eq!(
+            f64,
+            same,
+            ph_convert(&ph_measured, &calibration)
+        ));
+
+        // The isopotential point is unchanged
+        let iso = ph_convert_at(&7.0, &calibration, &25.0, &40.0);
+        assert!(approx_eq!(f64, iso, ph_convert(&7.0, &calibration)));
+
+        let hot = ph_convert_at(&ph_measured, &calibration, &25.0, &40.0);
+        let expected = 1.02 * (7.0 - 3.0 * 298.15 / 313.15) + 0.05;
+        assert!(approx_eq!(f64, hot, expected, epsilon = 1e-12));
+    }
+
+    #[test]
+    fn test_temperature_compensation() {
+        let compensated = temperature_compensation(&[1.0, 0.0], &0.0, &ZERO_CELSIUS);
+        assert!(approx_eq!(f64, compensated[0], 0.5));
+        assert!(approx_eq!(f64, compensated[1], 3.5));
+    }
+
+    #[test]
+    fn test_nernst_slope() {
+        assert!(approx_eq!(f64, nernst_slope(&25.0), 59.159, epsilon = 1e-3));
+        assert!(approx_eq!(f64, nernst_slope(&0.0), 54.199, epsilon = 1e-3));
+    }
+
+    #[test]
+    fn test_ph_convert_interval() {
+        let ph_measured = [1.5, 3.8, 6.6, 8.9, 9.8];
+        let ph_buffer = [1.679, 4.005, 6.865, 9.18, 10.012];
+        let report = ph_fit_report(&ph_measured, &ph_buffer).unwrap();
+        let uncertainty = report.uncertainty().unwrap();
+        let calibration = Calibration::from(&report);
+
+        let interval = ph_convert_interval(&7.0, &calibration, &uncertainty, &0.95).unwrap();
+        assert!(approx_eq!(f64, interval.ph, ph_convert(&7.0, &calibration)));
+        assert!(interval.confidence > 0.0);
+        assert!(interval.prediction > interval.confidence);
+
+        // Wider at a higher confidence level, and further from the centre of the calibration
+        let wide = ph_convert_interval(&7.0, &calibration, &uncertainty, &0.99).unwrap();
+        assert!(wide.prediction > interval.prediction);
+        let far = ph_convert_interval(&13.0, &calibration, &uncertainty, &0.95).unwrap();
+        assert!(far.confidence > interval.confidence);
+
+        assert!(ph_convert_interval(&7.0, &calibration, &uncertainty, &95.0).is_err());
+    }
 }
@@ -6,6 +6,8 @@ Copyright 2021 Peter Dunne */
 //! # Stats Module
 //! Provides simple stats formulae
 
+use super::error::{Error, Result};
+
 /// Returns the mean of an array of floats
 pub fn mean(values: &[f64]) -> f64 {
     let length: usize = values.len();
@@ -18,6 +20,18 @@ pub fn mean(values: &[f64]) -> f64 {
     values.iter().sum::<f64>() / length as f64
 }
 
+/// Returns the median of an array of floats, 0 if empty
+pub fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0_f64;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    // The two middle values of an even count, or the middle value twice of an odd one
+    let count = sorted.len();
+    0.5 * (sorted[(count - 1) / 2] + sorted[count / 2])
+}
+
 /// Returns variance of an array of floats
 pub fn variance(values: &[f64]) -> f64 {
     if values.is_empty() {
@@ -27,16 +41,19 @@ pub fn variance(values: &[f64]) -> f64 {
     values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / values.len() as f64
 }
 
-/// Returns covariance of two input arrays
-pub fn covariance(x: &[f64], y: &[f64]) -> f64 {
+/// Returns covariance of two input arrays, which must be of equal length
+pub fn covariance(x: &[f64], y: &[f64]) -> Result<f64> {
     if x.len() != y.len() {
-        panic!("x and y must be of equal length.");
+        return Err(Error::LengthMismatch {
+            expected: x.len(),
+            found: y.len(),
+        });
     }
 
     let length: usize = x.len();
 
     if length == 0 {
-        return 0_f64;
+        return Ok(0_f64);
     }
 
     let mean_x = mean(x);
@@ -48,12 +65,189 @@ pub fn covariance(x: &[f64], y: &[f64]) -> f64 {
         .map(|(x, y)| (x - mean_x) * (y - mean_y))
         .sum();
 
-    covariance / length as f64
+    Ok(covariance / length as f64)
+}
+
+/// Checks the weights pair with the values, and are non-negative with a positive sum
+fn check_weights(values: &[f64], weights: &[f64]) -> Result<f64> {
+    if values.len() != weights.len() {
+        return Err(Error::LengthMismatch {
+            expected: values.len(),
+            found: weights.len(),
+        });
+    }
+    let total: f64 = weights.iter().sum();
+    let valid = weights
+        .iter()
+        .all(|weight| weight.is_finite() && *weight >= 0.0);
+    if !valid || total <= 0.0 || !total.is_finite() {
+        return Err(Error::InvalidInput {
+            line: None,
+            message: "weights must be non-negative and finite, and not all zero".to_string(),
+        });
+    }
+    Ok(total)
+}
+
+/// Returns the weighted mean of an array of floats, $`\sum_i w_i x_i / \sum_i w_i`$
+pub fn weighted_mean(values: &[f64], weights: &[f64]) -> Result<f64> {
+    let total = check_weights(values, weights)?;
+    Ok(values
+        .iter()
+        .zip(weights.iter())
+        .map(|(value, weight)| value * weight)
+        .sum::<f64>()
+        / total)
+}
+
+/// Returns the weighted variance of an array of floats, $`\sum_i w_i (x_i - \bar{x}_w)^2 / \sum_i w_i`$
+pub fn weighted_variance(values: &[f64], weights: &[f64]) -> Result<f64> {
+    weighted_covariance(values, values, weights)
+}
+
+/// Returns the weighted covariance of two input arrays, which must be of equal length to the weights
+pub fn weighted_covariance(x: &[f64], y: &[f64], weights: &[f64]) -> Result<f64> {
+    if x.len() != y.len() {
+        return Err(Error::LengthMismatch {
+            expected: x.len(),
+            found: y.len(),
+        });
+    }
+    let total = check_weights(x, weights)?;
+    let mean_x = weighted_mean(x, weights)?;
+    let mean_y = weighted_mean(y, weights)?;
+    let covariance: f64 = x
+        .iter()
+        .zip(y.iter())
+        .zip(weights.iter())
+        .map(|((x, y), weight)| weight * (x - mean_x) * (y - mean_y))
+        .sum();
+    Ok(covariance / total)
+}
+
+/// Returns the natural logarithm of the gamma function, using the Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let series = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + series.ln()
+}
+
+/// Returns the regularised incomplete beta function $`I_x(a, b)`$, evaluated by its continued fraction
+fn incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    } else if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges quickly below the mean of the distribution
+    if x > (a + 1.0) / (a + b + 2.0) {
+        return 1.0 - incomplete_beta(1.0 - x, b, a);
+    }
+
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    d = 1.0 / if d.abs() < TINY { TINY } else { d };
+    let mut fraction = d;
+    for m in 1..200 {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            d = 1.0 / if d.abs() < TINY { TINY } else { d };
+            c = 1.0 + numerator / c;
+            c = if c.abs() < TINY { TINY } else { c };
+            fraction *= c * d;
+        }
+        if (c * d - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    front * fraction / a
+}
+
+/// Returns the cumulative distribution function of Student's t distribution with `dof` degrees of freedom
+pub fn student_t_cdf(t: f64, dof: f64) -> f64 {
+    let tail = 0.5 * incomplete_beta(dof / (dof + t * t), 0.5 * dof, 0.5);
+    if t >= 0.0 {
+        1.0 - tail
+    } else {
+        tail
+    }
+}
+
+/// Returns the cumulative distribution function of the F distribution with `dof_numerator` and
+/// `dof_denominator` degrees of freedom
+pub fn f_cdf(f: f64, dof_numerator: f64, dof_denominator: f64) -> f64 {
+    if f <= 0.0 {
+        return 0.0;
+    }
+    let x = dof_numerator * f / (dof_numerator * f + dof_denominator);
+    incomplete_beta(x, 0.5 * dof_numerator, 0.5 * dof_denominator)
+}
+
+/// Returns the quantile of Student's t distribution with `dof` degrees of freedom, i.e. the value $`t`$
+/// below which the given fraction of the distribution lies.
+///
+/// Returns NaN unless `probability` lies between 0 and 1, and `dof` is positive.
+pub fn student_t_quantile(probability: f64, dof: f64) -> f64 {
+    if !(probability > 0.0 && probability < 1.0 && dof > 0.0) {
+        return f64::NAN;
+    }
+    if probability < 0.5 {
+        return -student_t_quantile(1.0 - probability, dof);
+    }
+
+    let mut low = 0.0;
+    let mut high = 1.0;
+    while student_t_cdf(high, dof) < probability {
+        low = high;
+        high *= 2.0;
+    }
+    for _ in 0..100 {
+        let middle = 0.5 * (low + high);
+        if student_t_cdf(middle, dof) < probability {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    0.5 * (low + high)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{covariance, mean, variance};
+    use super::{
+        covariance, f_cdf, mean, median, student_t_cdf, student_t_quantile, variance,
+        weighted_covariance, weighted_mean, weighted_variance,
+    };
+    use crate::error::Error;
     use float_cmp::approx_eq;
 
     #[test]
@@ -80,6 +274,13 @@ mod tests {
         assert!(approx_eq!(f64, 2.0_f64, result));
     }
 
+    #[test]
+    fn test_median() {
+        assert!(approx_eq!(f64, median(&[3.0, 1.0, 2.0]), 2.0));
+        assert!(approx_eq!(f64, median(&[4.0, 1.0, 3.0, 2.0]), 2.5));
+        assert!(approx_eq!(f64, median(&[]), 0.0));
+    }
+
     #[test]
     fn test_variance() {
         let values = vec![1.0, 2.0, 3.0, 4.0];
@@ -100,7 +301,7 @@ mod tests {
     fn test_varaince_single() {
         let x: Vec<f64> = vec![2.0];
         let y: Vec<f64> = vec![1.0];
-        let result: f64 = covariance(&x, &y);
+        let result: f64 = covariance(&x, &y).unwrap();
 
         assert!(approx_eq!(f64, 0.0_f64, result));
     }
@@ -109,7 +310,7 @@ mod tests {
     fn test_covariance() {
         let x = vec![1.0, 2.0, 3.0, 4.0];
         let y = vec![3.0, 4.0, 5.0, 6.0];
-        let result: f64 = covariance(&x, &y);
+        let result: f64 = covariance(&x, &y).unwrap();
 
         assert!(approx_eq!(f64, 1.25_f64, result));
     }
@@ -117,16 +318,127 @@ mod tests {
     #[test]
     fn test_covariance_empty() {
         let values: Vec<f64> = Vec::new();
-        let result: f64 = covariance(&values, &values);
+        let result: f64 = covariance(&values, &values).unwrap();
 
         assert!(approx_eq!(f64, 0.0_f64, result));
     }
 
     #[test]
-    #[should_panic(expected = "x and y must be of equal length.")]
     fn test_covariance_wrong_lengths() {
         let x = vec![1.0, 2.0, 3.0, 4.0];
         let y = vec![3.0, 4.0, 5.0];
-        covariance(&x, &y);
+        let result = covariance(&x, &y);
+
+        assert!(matches!(
+            result,
+            Err(Error::LengthMismatch {
+                expected: 4,
+                found: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_student_t() {
+        assert!(approx_eq!(
+            f64,
+            student_t_cdf(0.0, 4.0),
+            0.5,
+            epsilon = 1e-12
+        ));
+        assert!(approx_eq!(
+            f64,
+            student_t_cdf(1.0, 1.0),
+            0.75,
+            epsilon = 1e-12
+        ));
+        assert!(approx_eq!(
+            f64,
+            student_t_quantile(0.975, 1.0),
+            12.7062,
+            epsilon = 1e-4
+        ));
+        assert!(approx_eq!(
+            f64,
+            student_t_quantile(0.975, 3.0),
+            3.18245,
+            epsilon = 1e-5
+        ));
+        assert!(approx_eq!(
+            f64,
+            student_t_quantile(0.975, 10.0),
+            2.22814,
+            epsilon = 1e-5
+        ));
+        assert!(approx_eq!(
+            f64,
+            student_t_quantile(0.005, 5.0),
+            -4.03214,
+            epsilon = 1e-5
+        ));
+        assert!(student_t_quantile(1.0, 5.0).is_nan());
+    }
+
+    #[test]
+    fn test_f_cdf() {
+        // Critical values at the 95 % level
+        assert!(approx_eq!(
+            f64,
+            f_cdf(18.513, 1.0, 2.0),
+            0.95,
+            epsilon = 1e-5
+        ));
+        assert!(approx_eq!(
+            f64,
+            f_cdf(4.1028, 2.0, 10.0),
+            0.95,
+            epsilon = 1e-5
+        ));
+        // The square of Student's t has an F distribution
+        assert!(approx_eq!(
+            f64,
+            f_cdf(4.0, 1.0, 3.0),
+            2.0 * student_t_cdf(2.0, 3.0) - 1.0,
+            epsilon = 1e-12
+        ));
+        assert!(approx_eq!(f64, f_cdf(0.0, 2.0, 5.0), 0.0));
+    }
+
+    #[test]
+    fn test_weighted() {
+        let x = [1.0, 2.0, 3.0, 4.0];
+        let y = [2.0, 4.1, 5.9, 8.2];
+
+        // Equal weights give the unweighted statistics
+        let ones = [1.0; 4];
+        assert!(approx_eq!(f64, weighted_mean(&x, &ones).unwrap(), mean(&x)));
+        assert!(approx_eq!(
+            f64,
+            weighted_variance(&x, &ones).unwrap(),
+            variance(&x)
+        ));
+        assert!(approx_eq!(
+            f64,
+            weighted_covariance(&x, &y, &ones).unwrap(),
+            covariance(&x, &y).unwrap()
+        ));
+
+        // An integer weight counts a value that many times
+        let weights = [1.0, 3.0, 1.0, 1.0];
+        let repeated = [1.0, 2.0, 2.0, 2.0, 3.0, 4.0];
+        assert!(approx_eq!(
+            f64,
+            weighted_mean(&x, &weights).unwrap(),
+            mean(&repeated)
+        ));
+        assert!(approx_eq!(
+            f64,
+            weighted_variance(&x, &weights).unwrap(),
+            variance(&repeated)
+        ));
+
+        assert!(weighted_mean(&x, &[1.0, 1.0]).is_err());
+        assert!(weighted_mean(&x, &[0.0; 4]).is_err());
+        assert!(weighted_mean(&x, &[1.0, -1.0, 1.0, 1.0]).is_err());
     }
 }
@@ -0,0 +1,305 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+License, v. 2.0. If a copy of the MPL was not distributed with this
+file, You can obtain one at https://mozilla.org/MPL/2.0/.
+Copyright 2021 Peter Dunne */
+//! # Temperature Module
+//! Provides temperatures carrying their unit, ˚C, ˚F or K, and the conversions between them.
+//!
+//! The rest of the library works in ˚C, i.e. the buffer tables, the calibration record and its warnings, so a
+//! temperature in another unit is converted with [`Temperature::celsius`] where it enters.
+//!
+//! A temperature is parsed from a number with an optional unit, e.g. `77F`, `25 ˚C` or `298.15K`, while a bare number
+//! is in the unit given to [`Temperature::parse_in`], ˚C by default:
+//!
+//! ```
+//! use libcaliph::temperature::{Temperature, TemperatureUnit};
+//!
+//! let temperature: Temperature = "77F".parse().unwrap();
+//! assert_eq!(temperature.unit, TemperatureUnit::Fahrenheit);
+//! assert_eq!(temperature.celsius(), 25.0);
+//! assert_eq!(temperature.to_unit(TemperatureUnit::Kelvin).to_string(), "298.15 K");
+//! ```
+
+use super::routines::ZERO_CELSIUS;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Names of the temperature units
+pub const TEMPERATURE_UNIT_NAMES: [&str; 3] = ["celsius", "fahrenheit", "kelvin"];
+
+/// Unit of a temperature
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TemperatureUnit {
+    /// Degrees Celsius, ˚C
+    #[default]
+    Celsius,
+    /// Degrees Fahrenheit, ˚F
+    Fahrenheit,
+    /// Kelvin, K
+    Kelvin,
+}
+
+impl TemperatureUnit {
+    /// Symbol of the unit, as printed after a temperature
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "˚C",
+            TemperatureUnit::Fahrenheit => "˚F",
+            TemperatureUnit::Kelvin => "K",
+        }
+    }
+
+    /// Converts a temperature in this unit to ˚C
+    pub fn to_celsius(&self, value: f64) -> f64 {
+        match self {
+            TemperatureUnit::Celsius => value,
+            TemperatureUnit::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+            TemperatureUnit::Kelvin => value - ZERO_CELSIUS,
+        }
+    }
+
+    /// Converts a temperature in ˚C to this unit
+    pub fn from_celsius(&self, celsius: f64) -> f64 {
+        match self {
+            TemperatureUnit::Celsius => celsius,
+            TemperatureUnit::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            TemperatureUnit::Kelvin => celsius + ZERO_CELSIUS,
+        }
+    }
+}
+
+impl fmt::Display for TemperatureUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl FromStr for TemperatureUnit {
+    type Err = String;
+
+    /// Parses the name or symbol of a unit, with or without the degree sign
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let name = s.trim().trim_start_matches(['˚', '°', 'º']);
+        match name.to_lowercase().as_str() {
+            "c" | "celsius" => Ok(TemperatureUnit::Celsius),
+            "f" | "fahrenheit" => Ok(TemperatureUnit::Fahrenheit),
+            "k" | "kelvin" => Ok(TemperatureUnit::Kelvin),
+            _ => Err(format!("Unknown temperature unit {}", s)),
+        }
+    }
+}
+
+/// A temperature and its unit
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Temperature {
+    pub value: f64,
+    pub unit: TemperatureUnit,
+}
+
+impl Default for Temperature {
+    /// 25˚C, the temperature of a calibration when none is given
+    fn default() -> Self {
+        Temperature::from_celsius(25.0)
+    }
+}
+
+impl Temperature {
+    /// Creates a temperature in the unit
+    pub fn new(value: f64, unit: TemperatureUnit) -> Temperature {
+        Temperature { value, unit }
+    }
+
+    /// Creates a temperature in ˚C
+    pub fn from_celsius(celsius: f64) -> Temperature {
+        Temperature::new(celsius, TemperatureUnit::Celsius)
+    }
+
+    /// Temperature in ˚C
+    pub fn celsius(&self) -> f64 {
+        self.unit.to_celsius(self.value)
+    }
+
+    /// Temperature in ˚F
+    pub fn fahrenheit(&self) -> f64 {
+        TemperatureUnit::Fahrenheit.from_celsius(self.celsius())
+    }
+
+    /// Temperature in K
+    pub fn kelvin(&self) -> f64 {
+        TemperatureUnit::Kelvin.from_celsius(self.celsius())
+    }
+
+    /// The same temperature in another unit
+    pub fn to_unit(&self, unit: TemperatureUnit) -> Temperature {
+        if unit == self.unit {
+            *self
+        } else {
+            Temperature::new(unit.from_celsius(self.celsius()), unit)
+        }
+    }
+
+    /// Writes the temperature followed by its value in ˚C to a tenth of a degree when in another unit, e.g.
+    /// `210 ˚F (98.9 ˚C)`, for the messages which relate a temperature to the ˚C of the buffer tables
+    pub fn with_celsius(&self) -> String {
+        match self.unit {
+            TemperatureUnit::Celsius => self.to_string(),
+            _ => format!("{} ({:.1})", self, self.to_unit(TemperatureUnit::Celsius)),
+        }
+    }
+
+    /// Parses a number followed by an optional unit, a bare number being in `unit`.
+    ///
+    /// Returns an error for a temperature which is not finite, or below absolute zero.
+    pub fn parse_in(s: &str, unit: TemperatureUnit) -> std::result::Result<Self, String> {
+        let s = s.trim();
+        // The unit starts at the first letter or degree sign after the number, the exponent of a number excepted
+        let split = s
+            .char_indices()
+            .find(|(index, c)| {
+                (c.is_alphabetic() && !(matches!(c, 'e' | 'E') && is_exponent(&s[index + 1..])))
+                    || matches!(c, '˚' | '°' | 'º')
+            })
+            .map(|(index, _)| index)
+            .unwrap_or(s.len());
+        let (number, suffix) = s.split_at(split);
+        let value = match number.trim().parse::<f64>() {
+            Ok(value) if value.is_finite() => value,
+            _ => return Err(format!("{:?} is not a finite temperature", s)),
+        };
+        let unit = if suffix.is_empty() {
+            unit
+        } else {
+            suffix.parse()?
+        };
+        let temperature = Temperature::new(value, unit);
+        if temperature.kelvin() < 0.0 {
+            return Err(format!("{:?} is below absolute zero", s));
+        }
+        Ok(temperature)
+    }
+}
+
+/// Checks if the rest of a number after an `e` is its exponent
+fn is_exponent(rest: &str) -> bool {
+    let digits = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+    digits.starts_with(|c: char| c.is_ascii_digit())
+}
+
+impl fmt::Display for Temperature {
+    /// Writes the value and the symbol of its unit, rounded to a millionth of a degree so that conversions read cleanly
+    /// unless a precision is given
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match f.precision() {
+            Some(precision) => write!(f, "{:.*} {}", precision, self.value, self.unit),
+            None => {
+                let rounded = (self.value * 1e6).round() / 1e6;
+                write!(f, "{} {}", rounded, self.unit)
+            }
+        }
+    }
+}
+
+impl FromStr for Temperature {
+    type Err = String;
+
+    /// Parses a number followed by an optional unit, a bare number being in ˚C
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        Temperature::parse_in(s, TemperatureUnit::Celsius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Temperature, TemperatureUnit};
+    use float_cmp::approx_eq;
+
+    #[test]
+    fn test_temperature_conversions() {
+        let body = Temperature::new(98.6, TemperatureUnit::Fahrenheit);
+        assert!(approx_eq!(f64, body.celsius(), 37.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, body.kelvin(), 310.15, epsilon = 1e-9));
+        assert_eq!(body.to_unit(TemperatureUnit::Fahrenheit), body);
+
+        let freezing = Temperature::from_celsius(0.0);
+        assert_eq!(freezing.fahrenheit(), 32.0);
+        assert_eq!(freezing.to_unit(TemperatureUnit::Kelvin).value, 273.15);
+        // -40 is the same in ˚C and ˚F
+        assert_eq!(Temperature::from_celsius(-40.0).fahrenheit(), -40.0);
+
+        for unit in [
+            TemperatureUnit::Celsius,
+            TemperatureUnit::Fahrenheit,
+            TemperatureUnit::Kelvin,
+        ] {
+            assert!(approx_eq!(
+                f64,
+                unit.to_celsius(unit.from_celsius(22.3)),
+                22.3,
+                epsilon = 1e-12
+            ));
+        }
+    }
+
+    #[test]
+    fn test_temperature_from_str() {
+        let parse = |s: &str| s.parse::<Temperature>().unwrap();
+        assert_eq!(parse("25"), Temperature::from_celsius(25.0));
+        assert_eq!(
+            parse("77F"),
+            Temperature::new(77.0, TemperatureUnit::Fahrenheit)
+        );
+        assert_eq!(
+            parse("298.15 K"),
+            Temperature::new(298.15, TemperatureUnit::Kelvin)
+        );
+        assert_eq!(parse("22.3˚C"), Temperature::from_celsius(22.3));
+        assert_eq!(
+            parse("-4 °f"),
+            Temperature::new(-4.0, TemperatureUnit::Fahrenheit)
+        );
+        assert_eq!(parse("2.5e1"), Temperature::from_celsius(25.0));
+        assert_eq!(
+            parse("3e2 kelvin"),
+            Temperature::new(300.0, TemperatureUnit::Kelvin)
+        );
+
+        assert_eq!(
+            Temperature::parse_in("77", TemperatureUnit::Fahrenheit).unwrap(),
+            parse("77F")
+        );
+        assert_eq!(
+            Temperature::parse_in("25C", TemperatureUnit::Fahrenheit).unwrap(),
+            parse("25")
+        );
+
+        assert!("".parse::<Temperature>().is_err());
+        assert!("77R".parse::<Temperature>().is_err());
+        assert!("inf".parse::<Temperature>().is_err());
+        assert!("-300".parse::<Temperature>().is_err());
+        assert!("-1K".parse::<Temperature>().is_err());
+    }
+
+    #[test]
+    fn test_temperature_display() {
+        let temperature = Temperature::from_celsius(22.3).to_unit(TemperatureUnit::Fahrenheit);
+        assert_eq!(temperature.to_string(), "72.14 ˚F");
+        assert_eq!(format!("{:.1}", temperature), "72.1 ˚F");
+        assert_eq!(
+            Temperature::from_celsius(25.0)
+                .to_unit(TemperatureUnit::Kelvin)
+                .to_string(),
+            "298.15 K"
+        );
+        assert_eq!(
+            Temperature::new(210.0, TemperatureUnit::Fahrenheit).with_celsius(),
+            "210 ˚F (98.9 ˚C)"
+        );
+        assert_eq!(
+            Temperature::from_celsius(98.888_888_888_888_89).with_celsius(),
+            "98.888889 ˚C"
+        );
+        assert_eq!("Kelvin".parse(), Ok(TemperatureUnit::Kelvin));
+    }
+}